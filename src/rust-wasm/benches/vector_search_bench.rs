@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use meta_media_search_wasm::vector_search::*;
 
 fn benchmark_cosine_similarity(c: &mut Criterion) {
@@ -6,10 +6,10 @@ fn benchmark_cosine_similarity(c: &mut Criterion) {
 
     for size in [128, 384, 768, 1536].iter() {
         let a: Vec<f32> = (0..*size).map(|i| i as f32).collect();
-        let b: Vec<f32> = (0..*size).map(|i| (i + 1) as f32).collect();
+        let other: Vec<f32> = (0..*size).map(|i| (i + 1) as f32).collect();
 
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
-            b.iter(|| cosine_similarity(black_box(&a), black_box(&a)))
+            b.iter(|| cosine_similarity(black_box(&a), black_box(&other)))
         });
     }
 
@@ -21,10 +21,10 @@ fn benchmark_euclidean_distance(c: &mut Criterion) {
 
     for size in [128, 384, 768, 1536].iter() {
         let a: Vec<f32> = (0..*size).map(|i| i as f32).collect();
-        let b: Vec<f32> = (0..*size).map(|i| (i + 1) as f32).collect();
+        let other: Vec<f32> = (0..*size).map(|i| (i + 1) as f32).collect();
 
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
-            b.iter(|| euclidean_distance(black_box(&a), black_box(&a)))
+            b.iter(|| euclidean_distance(black_box(&a), black_box(&other)))
         });
     }
 
@@ -63,9 +63,7 @@ fn benchmark_search(c: &mut Criterion) {
         group.bench_with_input(
             BenchmarkId::from_parameter(index_size),
             index_size,
-            |b, _| {
-                b.iter(|| index.search(black_box(&query), black_box(10)))
-            },
+            |b, _| b.iter(|| index.search(black_box(&query), black_box(10))),
         );
     }
 
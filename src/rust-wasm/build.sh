@@ -15,6 +15,11 @@ fi
 # Parse arguments
 TARGET="web"
 PROFILE="release"
+THREADS=""
+# Shared memory needs -Z build-std, so a nightly; it must be rustc 1.88 or
+# later, since the crate uses usize::is_multiple_of and slice::as_chunks.
+# CI builds the threads target with this toolchain (rust-wasm-ci.yml)
+THREADS_TOOLCHAIN="nightly-2026-05-19"
 
 while [[ $# -gt 0 ]]; do
     case $1 in
@@ -26,12 +31,17 @@ while [[ $# -gt 0 ]]; do
             TARGET="$2"
             shift 2
             ;;
+        --threads)
+            THREADS="1"
+            shift
+            ;;
         --help)
             echo "Usage: ./build.sh [OPTIONS]"
             echo ""
             echo "Options:"
             echo "  --dev              Build in development mode (faster, larger)"
             echo "  --target TARGET    Build target: web, nodejs, bundler (default: web)"
+            echo "  --threads          Enable the Web Worker thread pool (nightly, web target only)"
             echo "  --help             Show this help message"
             exit 0
             ;;
@@ -43,13 +53,27 @@ while [[ $# -gt 0 ]]; do
     esac
 done
 
+CARGO_ARGS=()
+if [ -n "$THREADS" ]; then
+    if [ "$TARGET" != "web" ]; then
+        echo "Error: --threads needs --target web (workers load the module by URL)"
+        exit 1
+    fi
+    echo "Enabling threads with $THREADS_TOOLCHAIN..."
+    rustup toolchain install "$THREADS_TOOLCHAIN" --component rust-src --target wasm32-unknown-unknown
+    export RUSTUP_TOOLCHAIN="$THREADS_TOOLCHAIN"
+    # Replaces the flags in .cargo/config.toml, so they are repeated here
+    export RUSTFLAGS="-C opt-level=3 -C lto=fat -C codegen-units=1 -C target-feature=+atomics,+bulk-memory,+mutable-globals"
+    CARGO_ARGS=(-- --features wasm-threads -Z build-std=panic_abort,std)
+fi
+
 # Build based on profile
 if [ "$PROFILE" = "dev" ]; then
     echo "Building for development..."
-    wasm-pack build --target $TARGET --dev
+    wasm-pack build --target $TARGET --dev "${CARGO_ARGS[@]}"
 else
     echo "Building for production..."
-    wasm-pack build --target $TARGET --release
+    wasm-pack build --target $TARGET --release "${CARGO_ARGS[@]}"
 fi
 
 # Copy TypeScript definitions
@@ -63,6 +87,7 @@ echo ""
 echo "Build complete!"
 echo "Target: $TARGET"
 echo "Profile: $PROFILE"
+[ -n "$THREADS" ] && echo "Threads: enabled"
 echo ""
 echo "Output files:"
 ls -lh pkg/ | grep -E '\.(wasm|js|ts)$' || true
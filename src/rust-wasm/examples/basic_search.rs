@@ -2,7 +2,7 @@
 //!
 //! Run with: cargo run --example basic_search
 
-use meta_media_search_wasm::vector_search::{VectorIndex, DistanceMetric};
+use meta_media_search_wasm::vector_search::{DistanceMetric, VectorIndex};
 
 fn main() {
     println!("Meta Media Search - Basic Vector Search Example\n");
@@ -13,11 +13,11 @@ fn main() {
     // Add some vectors
     println!("Adding vectors to index...");
     let vectors = vec![
-        vec![1.0, 0.0, 0.0],  // Vector 0
-        vec![0.0, 1.0, 0.0],  // Vector 1
-        vec![0.0, 0.0, 1.0],  // Vector 2
-        vec![0.9, 0.1, 0.0],  // Vector 3 (similar to 0)
-        vec![0.1, 0.9, 0.0],  // Vector 4 (similar to 1)
+        vec![1.0, 0.0, 0.0], // Vector 0
+        vec![0.0, 1.0, 0.0], // Vector 1
+        vec![0.0, 0.0, 1.0], // Vector 2
+        vec![0.9, 0.1, 0.0], // Vector 3 (similar to 0)
+        vec![0.1, 0.9, 0.0], // Vector 4 (similar to 1)
     ];
 
     for (i, vec) in vectors.iter().enumerate() {
@@ -0,0 +1,142 @@
+//! Minimal command-line parsing
+//!
+//! Arguments are positionals, `--name value` (or `--name=value`) options and
+//! bare `--flag`s. Each subcommand lists the names it accepts, with aliases
+//! such as `output|o`, so typos are reported instead of ignored.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Parsed arguments of one subcommand
+#[derive(Debug, Default)]
+pub struct Args {
+    positionals: Vec<String>,
+    values: HashMap<String, Vec<String>>,
+    flags: Vec<String>,
+}
+
+impl Args {
+    /// Parse `args` against the accepted `options` (which take a value) and `flags`
+    pub fn parse<I>(args: I, options: &[&str], flags: &[&str]) -> Result<Args, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let canonical = |specs: &[&str], name: &str| {
+            specs
+                .iter()
+                .find(|spec| spec.split('|').any(|alias| alias == name))
+                .map(|spec| spec.split('|').next().unwrap_or(spec).to_string())
+        };
+
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')) else {
+                parsed.positionals.push(arg);
+                continue;
+            };
+            if name.is_empty() {
+                // `-` on its own is a positional, conventionally stdin/stdout
+                parsed.positionals.push(arg);
+                continue;
+            }
+            let (name, inline) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (name, None),
+            };
+            if let Some(option) = canonical(options, name) {
+                let value = match inline {
+                    Some(value) => value,
+                    None => args
+                        .next()
+                        .ok_or_else(|| format!("option {} needs a value", arg))?,
+                };
+                parsed.values.entry(option).or_default().push(value);
+            } else if let Some(flag) = canonical(flags, name) {
+                if inline.is_some() {
+                    return Err(format!("flag --{} does not take a value", flag));
+                }
+                parsed.flags.push(flag);
+            } else {
+                return Err(format!("unknown option {}", arg));
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// The `index`-th positional argument, described as `what` in errors
+    pub fn positional(&self, index: usize, what: &str) -> Result<&str, String> {
+        self.positionals
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing {}", what))
+    }
+
+    /// Reject positionals beyond the first `count`
+    pub fn expect_positionals(&self, count: usize) -> Result<(), String> {
+        match self.positionals.get(count) {
+            Some(extra) => Err(format!("unexpected argument {}", extra)),
+            None => Ok(()),
+        }
+    }
+
+    /// The last value given for `name`
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).last().map(String::as_str)
+    }
+
+    /// Every value given for a repeatable option
+    pub fn values(&self, name: &str) -> &[String] {
+        self.values.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The value of `name` parsed as `T`, or `default` if it was not given
+    pub fn parse_or<T>(&self, name: &str, default: T) -> Result<T, String>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        match self.value(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|e| format!("invalid --{} {:?}: {}", name, value, e)),
+            None => Ok(default),
+        }
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        Args::parse(
+            line.split_whitespace().map(String::from),
+            &["output|o", "k", "vector"],
+            &["json"],
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse("in.npy -o out.mmsi --k=5 --vector -0.5,1 --vector 2,3 --json").unwrap();
+        assert_eq!(args.positional(0, "input").unwrap(), "in.npy");
+        assert_eq!(args.value("output"), Some("out.mmsi"));
+        assert_eq!(args.parse_or("k", 10).unwrap(), 5);
+        assert_eq!(args.values("vector"), ["-0.5,1", "2,3"]);
+        assert!(args.flag("json"));
+        assert!(args.expect_positionals(1).is_ok());
+
+        assert_eq!(
+            parse("--metric cosine").unwrap_err(),
+            "unknown option --metric"
+        );
+        assert_eq!(parse("-k").unwrap_err(), "option -k needs a value");
+        assert!(parse("--k ten").unwrap().parse_or("k", 10).is_err());
+        assert!(parse("a b").unwrap().expect_positionals(1).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Reading and writing embedding datasets
+//!
+//! Besides index files and the binary formats of `vector_io`, datasets can be
+//! JSON lines (`[0.1, ...]` or `{"id": ..., "vector": [...]}` per line) or CSV
+//! (one vector per row, an optional header on the first line, and an optional
+//! leading key column). Unless `--key-column` says otherwise, rows start with
+//! a key when the header names its first column `id` or `key`, or when the
+//! first cell of the first row is not a number.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use meta_media_search_wasm::index_file::IndexFile;
+use meta_media_search_wasm::vector_io::{read_vectors, write_vectors};
+use meta_media_search_wasm::VectorFormat;
+use serde_json::{json, Value};
+
+/// File format of a dataset or index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Index,
+    Jsonl,
+    Csv,
+    Vectors(VectorFormat),
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        Ok(match name.to_lowercase().as_str() {
+            "index" | "mmsi" => Format::Index,
+            "jsonl" | "ndjson" => Format::Jsonl,
+            "csv" => Format::Csv,
+            "npy" => Format::Vectors(VectorFormat::Npy),
+            "fvecs" => Format::Vectors(VectorFormat::Fvecs),
+            "bvecs" => Format::Vectors(VectorFormat::Bvecs),
+            "safetensors" => Format::Vectors(VectorFormat::Safetensors),
+            _ => {
+                return Err(format!(
+                    "unknown format {}; use index, jsonl, csv, npy, fvecs, bvecs or safetensors",
+                    name
+                ))
+            }
+        })
+    }
+
+    /// The format named by `explicit`, or else the one implied by the file extension
+    pub fn resolve(path: &str, explicit: Option<&str>) -> Result<Format, String> {
+        if let Some(name) = explicit {
+            return Format::parse(name);
+        }
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        Format::parse(extension).map_err(|_| {
+            format!(
+                "cannot tell the format of {} from its extension; pass --format",
+                path
+            )
+        })
+    }
+}
+
+/// Whether the rows of a CSV file start with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyColumn {
+    /// Decide from the header, or from the first row
+    Detect,
+    First,
+    None,
+}
+
+impl KeyColumn {
+    pub fn parse(name: Option<&str>) -> Result<KeyColumn, String> {
+        match name.map(str::to_lowercase).as_deref() {
+            None => Ok(KeyColumn::Detect),
+            Some("first") => Ok(KeyColumn::First),
+            Some("none") => Ok(KeyColumn::None),
+            Some(name) => Err(format!("unknown key column {}; use first or none", name)),
+        }
+    }
+}
+
+/// Flat vectors with optional per-vector keys
+#[derive(Debug, Default, PartialEq)]
+pub struct Dataset {
+    pub dimension: usize,
+    pub data: Vec<f32>,
+    pub keys: Option<Vec<String>>,
+}
+
+impl Dataset {
+    pub fn count(&self) -> usize {
+        self.data.len() / self.dimension.max(1)
+    }
+
+    pub fn vectors(&self) -> impl Iterator<Item = &[f32]> {
+        self.data.chunks_exact(self.dimension.max(1))
+    }
+
+    fn push(&mut self, key: Option<String>, vector: Vec<f32>, line: usize) -> Result<(), String> {
+        if vector.is_empty() {
+            return Err(format!("line {}: empty vector", line));
+        }
+        if self.data.is_empty() {
+            self.dimension = vector.len();
+            self.keys = key.is_some().then(Vec::new);
+        } else if vector.len() != self.dimension {
+            return Err(format!(
+                "line {}: expected {} values like the first vector, got {}",
+                line,
+                self.dimension,
+                vector.len()
+            ));
+        }
+        match (&mut self.keys, key) {
+            (Some(keys), Some(key)) => keys.push(key),
+            (None, None) => {}
+            _ => {
+                return Err(format!(
+                    "line {}: either every vector has a key or none does",
+                    line
+                ))
+            }
+        }
+        self.data.extend(vector);
+        Ok(())
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e).into())
+}
+
+/// Load an index file
+pub fn read_index(path: &str) -> Result<IndexFile, Box<dyn Error>> {
+    let bytes = read(path)?;
+    IndexFile::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e).into())
+}
+
+/// Load a dataset; an index file yields its stored vectors and keys
+///
+/// `key_column` only applies to CSV.
+pub fn read_dataset(
+    path: &str,
+    format: Format,
+    key_column: KeyColumn,
+) -> Result<Dataset, Box<dyn Error>> {
+    let bytes = read(path)?;
+    let context = |e: String| format!("{}: {}", path, e);
+    Ok(match format {
+        Format::Index => {
+            let file = IndexFile::from_bytes(&bytes).map_err(|e| context(e.to_string()))?;
+            let index = file.index();
+            Dataset {
+                dimension: file.stored_dimension(),
+                data: (0..index.size())
+                    .filter_map(|id| index.get_vector(id))
+                    .flatten()
+                    .collect(),
+                keys: file.keys().map(<[String]>::to_vec),
+            }
+        }
+        Format::Jsonl => parse_jsonl(&String::from_utf8_lossy(&bytes)).map_err(context)?,
+        Format::Csv => parse_csv(&String::from_utf8_lossy(&bytes), key_column).map_err(context)?,
+        Format::Vectors(format) => {
+            let (dimension, data) =
+                read_vectors(&bytes, format).map_err(|e| context(e.to_string()))?;
+            Dataset {
+                dimension,
+                data,
+                keys: None,
+            }
+        }
+    })
+}
+
+/// Write a dataset in any format except `Index`
+pub fn write_dataset(path: &str, dataset: &Dataset, format: Format) -> Result<(), Box<dyn Error>> {
+    let bytes = match format {
+        Format::Index => return Err("index files are written by `build`".into()),
+        Format::Jsonl => {
+            let mut out = String::new();
+            for (i, vector) in dataset.vectors().enumerate() {
+                let line = match &dataset.keys {
+                    Some(keys) => json!({ "id": keys[i], "vector": vector }),
+                    None => json!(vector),
+                };
+                out.push_str(&line.to_string());
+                out.push('\n');
+            }
+            out.into_bytes()
+        }
+        Format::Csv => format_csv(dataset)?.into_bytes(),
+        Format::Vectors(format) => {
+            let mut bytes = Vec::new();
+            write_vectors(&mut bytes, &dataset.data, dataset.dimension, format)?;
+            bytes
+        }
+    };
+    fs::write(path, bytes).map_err(|e| format!("cannot write {}: {}", path, e).into())
+}
+
+pub fn parse_jsonl(text: &str) -> Result<Dataset, String> {
+    let mut dataset = Dataset::default();
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", line_number, e))?;
+        let (key, vector) = match &value {
+            Value::Array(_) => (None, &value),
+            Value::Object(fields) => {
+                let vector = fields
+                    .get("vector")
+                    .or_else(|| fields.get("embedding"))
+                    .ok_or_else(|| {
+                        format!("line {}: no \"vector\" or \"embedding\" field", line_number)
+                    })?;
+                let key = match fields.get("id") {
+                    Some(Value::String(s)) => Some(s.clone()),
+                    Some(Value::Number(n)) => Some(n.to_string()),
+                    Some(_) => {
+                        return Err(format!(
+                            "line {}: \"id\" must be a string or number",
+                            line_number
+                        ))
+                    }
+                    None => None,
+                };
+                (key, vector)
+            }
+            _ => return Err(format!("line {}: expected an array or object", line_number)),
+        };
+        let vector = vector
+            .as_array()
+            .ok_or_else(|| format!("line {}: the vector must be an array", line_number))?
+            .iter()
+            .map(|v| v.as_f64().map(|v| v as f32))
+            .collect::<Option<Vec<f32>>>()
+            .ok_or_else(|| format!("line {}: the vector must contain only numbers", line_number))?;
+        dataset.push(key, vector, line_number)?;
+    }
+    Ok(dataset)
+}
+
+pub fn parse_csv(text: &str, key_column: KeyColumn) -> Result<Dataset, String> {
+    let mut dataset = Dataset::default();
+    let mut keyed = match key_column {
+        KeyColumn::Detect => None,
+        KeyColumn::First => Some(true),
+        KeyColumn::None => Some(false),
+    };
+    let numeric = |cells: &[String]| cells.iter().all(|c| c.parse::<f32>().is_ok());
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cells = split_csv_line(line).map_err(|e| format!("line {}: {}", line_number, e))?;
+        if line_number == 1 {
+            // Data rows hold numbers at least after their first cell, so
+            // anything else on the first line is a header
+            let values = match keyed {
+                Some(false) => &cells[..],
+                _ if cells.len() > 1 => &cells[1..],
+                _ => &cells[..],
+            };
+            if !numeric(values) {
+                if keyed.is_none() && ["id", "key"].contains(&cells[0].to_lowercase().as_str()) {
+                    keyed = Some(true);
+                }
+                continue;
+            }
+        }
+        let keyed = *keyed.get_or_insert_with(|| !numeric(&cells[..1]));
+        let (key, values) = match cells.split_first() {
+            Some((key, values)) if keyed => (Some(key.clone()), values),
+            _ => (None, &cells[..]),
+        };
+        let vector = values
+            .iter()
+            .map(|c| c.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|e| format!("line {}: {}", line_number, e))?;
+        dataset.push(key, vector, line_number)?;
+    }
+    Ok(dataset)
+}
+
+/// Split a CSV row into cells, unquoting `"..."` cells and their `""` escapes
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut cells = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+        let mut cell = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => cell.push('"'),
+                    Some('"') => break,
+                    Some(c) => cell.push(c),
+                    None => return Err("unterminated quoted cell".to_string()),
+                }
+            }
+            while chars.next_if(|c| *c == ' ' || *c == '\t').is_some() {}
+            if chars.peek().is_some_and(|c| *c != ',') {
+                return Err(format!("unexpected text after quoted cell \"{}\"", cell));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                cell.push(c);
+            }
+            cell.truncate(cell.trim_end().len());
+        }
+        cells.push(cell);
+        if chars.next().is_none() {
+            return Ok(cells);
+        }
+    }
+}
+
+/// CSV with one vector per row; keyed datasets get an `id` header so that
+/// numeric keys read back as keys
+fn format_csv(dataset: &Dataset) -> Result<String, String> {
+    let mut out = String::new();
+    if dataset.keys.is_some() {
+        let columns: Vec<String> = (1..=dataset.dimension).map(|i| format!("v{}", i)).collect();
+        out.push_str(&format!("id,{}\n", columns.join(",")));
+    }
+    for (i, vector) in dataset.vectors().enumerate() {
+        let mut cells: Vec<String> = vector.iter().map(f32::to_string).collect();
+        if let Some(keys) = &dataset.keys {
+            cells.insert(0, csv_escape(&keys[i])?);
+        }
+        out.push_str(&cells.join(","));
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn csv_escape(cell: &str) -> Result<String, String> {
+    if cell.contains(['\n', '\r']) {
+        Err(format!(
+            "cannot write key {:?} to CSV: it contains a line break",
+            cell
+        ))
+    } else if cell.contains([',', '"']) || cell.trim() != cell {
+        Ok(format!("\"{}\"", cell.replace('"', "\"\"")))
+    } else {
+        Ok(cell.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_formats() {
+        let plain = parse_jsonl("[1, 2]\n\n[3, 4.5]\n").unwrap();
+        assert_eq!((plain.dimension, plain.count(), plain.keys), (2, 2, None));
+
+        let keyed =
+            parse_jsonl("{\"id\": \"a\", \"vector\": [1, 2]}\n{\"id\": 7, \"embedding\": [3, 4]}")
+                .unwrap();
+        assert_eq!(keyed.keys, Some(vec!["a".to_string(), "7".to_string()]));
+        assert!(parse_jsonl("[1, 2]\n[1, 2, 3]")
+            .unwrap_err()
+            .starts_with("line 2:"));
+        assert!(parse_jsonl("{\"id\": 1, \"vector\": [1]}\n[2]").is_err());
+
+        let csv = parse_csv("id,x,y\ntt1,1,2\ntt2,3,4\n", KeyColumn::Detect).unwrap();
+        assert_eq!(csv, keyed_dataset());
+        let headerless = parse_csv("1,2\n3,4", KeyColumn::Detect).unwrap();
+        assert_eq!((headerless.count(), headerless.keys), (2, None));
+        assert!(parse_csv("1,2\n3,x", KeyColumn::Detect).is_err());
+        // Only the first line may be a header
+        assert!(parse_csv("x,y\n1,2\nx,y\n3,4", KeyColumn::Detect)
+            .unwrap_err()
+            .starts_with("line 3:"));
+
+        // Numeric keys need an `id` header or an explicit key column
+        let numeric_ids = parse_csv("id,x,y\n101,0.1,0.2\n102,0.3,0.4", KeyColumn::Detect).unwrap();
+        assert_eq!(
+            numeric_ids.keys,
+            Some(vec!["101".to_string(), "102".to_string()])
+        );
+        let numeric_ids = parse_csv("101,0.1,0.2\n102,0.3,0.4", KeyColumn::First).unwrap();
+        assert_eq!((numeric_ids.dimension, numeric_ids.count()), (2, 2));
+        let unkeyed = parse_csv("101,0.1,0.2", KeyColumn::Detect).unwrap();
+        assert_eq!((unkeyed.dimension, unkeyed.keys), (3, None));
+        assert!(parse_csv("1,1,2\ntt1,1,2", KeyColumn::None).is_err());
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mut dataset = keyed_dataset();
+        dataset.keys = Some(vec![
+            "Crouching Tiger, Hidden Dragon".to_string(),
+            "\"7\" ".to_string(),
+        ]);
+        let text = format_csv(&dataset).unwrap();
+        assert_eq!(parse_csv(&text, KeyColumn::Detect).unwrap(), dataset);
+
+        dataset.keys = Some(vec!["101".to_string(), "102".to_string()]);
+        let text = format_csv(&dataset).unwrap();
+        assert_eq!(parse_csv(&text, KeyColumn::Detect).unwrap(), dataset);
+
+        dataset.keys = Some(vec!["two\nlines".to_string(), "b".to_string()]);
+        assert!(format_csv(&dataset).is_err());
+        assert!(split_csv_line("\"open,1").is_err());
+        assert_eq!(
+            split_csv_line(" a , \"b\"\"c\" ,").unwrap(),
+            ["a", "b\"c", ""]
+        );
+    }
+
+    fn keyed_dataset() -> Dataset {
+        Dataset {
+            dimension: 2,
+            data: vec![1.0, 2.0, 3.0, 4.0],
+            keys: Some(vec!["tt1".to_string(), "tt2".to_string()]),
+        }
+    }
+}
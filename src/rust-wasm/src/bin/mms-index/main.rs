@@ -0,0 +1,364 @@
+//! `mms-index`: build, query and inspect index files from the command line
+//!
+//! Run `mms-index help` for usage. Index files are the safetensors-based
+//! format of `meta_media_search_wasm::index_file`, conventionally `.mmsi`.
+
+mod args;
+mod input;
+
+use std::error::Error;
+use std::fs;
+use std::process::ExitCode;
+use std::time::Instant;
+
+use meta_media_search_wasm::index_file::IndexFile;
+use meta_media_search_wasm::metrics::Histogram;
+use meta_media_search_wasm::utils::SeededRng;
+use meta_media_search_wasm::vector_search::{DistanceMetric, SearchResult, VectorIndex};
+use meta_media_search_wasm::{EmbeddingModelInfo, QueryBuilder};
+use serde_json::json;
+
+use args::Args;
+use input::{Dataset, Format, KeyColumn};
+
+const USAGE: &str = "\
+Usage: mms-index <command> [options]
+
+Commands:
+  build <input> -o <index>     Index embeddings from JSONL, CSV, npy, fvecs, bvecs or safetensors
+      --metric <name>            cosine (default), euclidean, manhattan or dotproduct
+      --model <name>             Tag the index with a built-in model (minilm, mpnet, ...)
+      --format <name>            Input format, if the extension does not say
+      --key-column <first|none>  Whether CSV rows start with a key (default: from the header)
+  query <index> [-k 10] [--json]
+      --vector <v1,v2,...>       Search for a vector (repeatable)
+      --id <id> / --key <key>    Find items like a stored one, excluding it (repeatable)
+      --queries <file>           Search for every vector in a dataset file
+  stats <index> [--json]       Size, dimension, metric, model and vector norms
+  verify <index>               Check the checksum and the stored vectors; exits 1 on problems
+  convert <input> <output>     Convert between dataset formats, or export an index's vectors
+      --from <name> / --to <name>  Formats, if the extensions do not say
+      --key-column <first|none>  Whether CSV rows start with a key (default: from the header)
+  bench <index>                Time searches with random queries
+      --queries <n>              Number of queries (default 1000)
+      -k <n>                     Results per query (default 10)
+      --seed <n>                 Random seed (default 42)
+";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().unwrap_or_default();
+    let result = match command.as_str() {
+        "build" => build(args),
+        "query" => query(args),
+        "stats" => stats(args),
+        "verify" => verify(args),
+        "convert" => convert(args),
+        "bench" => bench(args),
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("mms-index {}: {}", command, e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+type CommandResult = Result<ExitCode, Box<dyn Error>>;
+
+fn parse_metric(name: &str) -> Result<DistanceMetric, String> {
+    match name.to_lowercase().as_str() {
+        "cosine" => Ok(DistanceMetric::Cosine),
+        "euclidean" => Ok(DistanceMetric::Euclidean),
+        "manhattan" => Ok(DistanceMetric::Manhattan),
+        "dotproduct" => Ok(DistanceMetric::DotProduct),
+        _ => Err(format!(
+            "unknown metric {}; use cosine, euclidean, manhattan or dotproduct",
+            name
+        )),
+    }
+}
+
+fn build(args: impl Iterator<Item = String>) -> CommandResult {
+    let args = Args::parse(
+        args,
+        &["output|o", "metric", "model", "format", "key-column"],
+        &[],
+    )?;
+    args.expect_positionals(1)?;
+    let input = args.positional(0, "input file")?;
+    let output = args.value("output").ok_or("missing -o <index>")?;
+    let format = Format::resolve(input, args.value("format"))?;
+    let metric = parse_metric(args.value("metric").unwrap_or("cosine"))?;
+
+    let key_column = KeyColumn::parse(args.value("key-column"))?;
+    let dataset = input::read_dataset(input, format, key_column)?;
+    if dataset.count() == 0 {
+        return Err(format!("{} contains no vectors", input).into());
+    }
+    let mut index = VectorIndex::new(dataset.dimension, metric);
+    if let Some(name) = args.value("model") {
+        let model =
+            EmbeddingModelInfo::lookup(name).ok_or_else(|| format!("unknown model {}", name))?;
+        index.set_model(&model)?;
+    }
+    index.add_vectors_batch(&dataset.data, dataset.count())?;
+    let file = match dataset.keys {
+        Some(keys) => IndexFile::with_keys(index, keys)?,
+        None => IndexFile::new(index),
+    };
+    fs::write(output, file.to_bytes()).map_err(|e| format!("cannot write {}: {}", output, e))?;
+
+    println!(
+        "Indexed {} vectors of dimension {} ({:?}) into {}",
+        file.index().size(),
+        dataset.dimension,
+        metric,
+        output
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn query(args: impl Iterator<Item = String>) -> CommandResult {
+    let args = Args::parse(args, &["vector", "id", "key", "queries", "k"], &["json"])?;
+    args.expect_positionals(1)?;
+    let file = input::read_index(args.positional(0, "index file")?)?;
+    let index = file.index();
+    let k = args.parse_or("k", 10)?;
+
+    // (label, results) per query, in the order given
+    let mut queries: Vec<(String, Vec<SearchResult>)> = Vec::new();
+    for vector in args.values("vector") {
+        let vector = vector
+            .split(',')
+            .map(|v| v.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|e| format!("invalid --vector {:?}: {}", vector, e))?;
+        queries.push((
+            format!("vector {}", queries.len()),
+            index.search(&vector, k)?,
+        ));
+    }
+    let ids = args.values("id").iter().map(|id| {
+        id.parse::<usize>()
+            .ok()
+            .filter(|&id| id < index.size())
+            .ok_or_else(|| format!("no item with ID {}", id))
+    });
+    let keys = args.values("key").iter().map(|key| {
+        file.find(key)
+            .ok_or_else(|| format!("no item with key {}", key))
+    });
+    for id in ids.chain(keys) {
+        let id = id?;
+        let mut like = QueryBuilder::new();
+        like.like(id);
+        queries.push((
+            format!("like {}", file.key(id)),
+            like.search_index(index, k)?,
+        ));
+    }
+    if let Some(path) = args.value("queries") {
+        let dataset = input::read_dataset(path, Format::resolve(path, None)?, KeyColumn::Detect)?;
+        for (i, vector) in dataset.vectors().enumerate() {
+            let label = dataset
+                .keys
+                .as_ref()
+                .map_or_else(|| i.to_string(), |keys| keys[i].clone());
+            queries.push((label, index.search(vector, k)?));
+        }
+    }
+    if queries.is_empty() {
+        return Err("nothing to search for; pass --vector, --id, --key or --queries".into());
+    }
+
+    for (label, results) in &queries {
+        if args.flag("json") {
+            let results: Vec<_> = results
+                .iter()
+                .map(|r| json!({ "id": r.id, "key": file.key(r.id), "score": r.score }))
+                .collect();
+            println!("{}", json!({ "query": label, "results": results }));
+        } else {
+            for (rank, r) in results.iter().enumerate() {
+                println!(
+                    "{}\t{}\t{}\t{}\t{:.6}",
+                    label,
+                    rank + 1,
+                    r.id,
+                    file.key(r.id),
+                    r.score
+                );
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn stats(args: impl Iterator<Item = String>) -> CommandResult {
+    let args = Args::parse(args, &[], &["json"])?;
+    args.expect_positionals(1)?;
+    let path = args.positional(0, "index file")?;
+    let file_size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let file = input::read_index(path)?;
+    let index = file.index();
+
+    let norms: Vec<f32> = (0..index.size())
+        .filter_map(|id| index.get_vector(id))
+        .map(|v| v.iter().map(|x| x * x).sum::<f32>().sqrt())
+        .collect();
+    let norm_min = norms.iter().copied().fold(f32::INFINITY, f32::min);
+    let norm_max = norms.iter().copied().fold(0.0, f32::max);
+    let norm_mean = norms.iter().sum::<f32>() / norms.len().max(1) as f32;
+
+    let norm_min = if norms.is_empty() { 0.0 } else { norm_min };
+    let model = index.model().map(|m| m.to_string());
+    let vector_bytes = index.size() * file.stored_dimension() * 4;
+
+    if args.flag("json") {
+        let stats = json!({
+            "count": index.size(),
+            "dimension": index.dimension(),
+            "stored_dimension": file.stored_dimension(),
+            "metric": index.metric(),
+            "model": model,
+            "transform": index.has_transform(),
+            "keys": file.keys().is_some(),
+            "file_bytes": file_size,
+            "vector_bytes": vector_bytes,
+            "norm": { "min": norm_min, "mean": norm_mean, "max": norm_max },
+        });
+        println!("{}", stats);
+    } else {
+        println!("vectors:          {}", index.size());
+        println!(
+            "dimension:        {} (stored {})",
+            index.dimension(),
+            file.stored_dimension()
+        );
+        println!("metric:           {:?}", index.metric());
+        println!("model:            {}", model.as_deref().unwrap_or("-"));
+        println!(
+            "transform:        {}",
+            if index.has_transform() { "yes" } else { "no" }
+        );
+        println!(
+            "keys:             {}",
+            if file.keys().is_some() { "yes" } else { "no" }
+        );
+        println!(
+            "file size:        {} bytes ({} in vectors)",
+            file_size, vector_bytes
+        );
+        println!(
+            "norm min/mean/max: {:.4} / {:.4} / {:.4}",
+            norm_min, norm_mean, norm_max
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn verify(args: impl Iterator<Item = String>) -> CommandResult {
+    let args = Args::parse(args, &[], &[])?;
+    args.expect_positionals(1)?;
+    let path = args.positional(0, "index file")?;
+    let file = match input::read_index(path) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("FAILED {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let problems = file.problems();
+    if problems.is_empty() {
+        println!("OK {}: {} vectors", path, file.index().size());
+        return Ok(ExitCode::SUCCESS);
+    }
+    for problem in &problems {
+        println!("FAILED {}: {}", path, problem);
+    }
+    Ok(ExitCode::FAILURE)
+}
+
+fn convert(args: impl Iterator<Item = String>) -> CommandResult {
+    let args = Args::parse(args, &["from", "to", "key-column"], &[])?;
+    args.expect_positionals(2)?;
+    let (input, output) = (
+        args.positional(0, "input file")?,
+        args.positional(1, "output file")?,
+    );
+    let from = Format::resolve(input, args.value("from"))?;
+    let to = Format::resolve(output, args.value("to"))?;
+    if to == Format::Index {
+        return Err("use `build` to create an index file".into());
+    }
+
+    let key_column = KeyColumn::parse(args.value("key-column"))?;
+    let dataset: Dataset = input::read_dataset(input, from, key_column)?;
+    input::write_dataset(output, &dataset, to)?;
+    println!(
+        "Wrote {} vectors of dimension {} to {}",
+        dataset.count(),
+        dataset.dimension,
+        output
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn bench(args: impl Iterator<Item = String>) -> CommandResult {
+    let args = Args::parse(args, &["queries", "k", "seed"], &[])?;
+    args.expect_positionals(1)?;
+    let file = input::read_index(args.positional(0, "index file")?)?;
+    let index = file.index();
+    let (count, k) = (
+        args.parse_or("queries", 1000usize)?,
+        args.parse_or("k", 10usize)?,
+    );
+    let mut rng = SeededRng::new(args.parse_or("seed", 42)?);
+
+    let queries: Vec<Vec<f32>> = (0..count)
+        .map(|_| {
+            (0..index.dimension())
+                .map(|_| rng.normal() as f32)
+                .collect()
+        })
+        .collect();
+    let mut latency = Histogram::new();
+    let start = Instant::now();
+    for query in &queries {
+        let started = Instant::now();
+        index.search(query, k)?;
+        latency.record(started.elapsed().as_secs_f64() * 1000.0);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} queries over {} vectors (dimension {}, k = {})",
+        count,
+        index.size(),
+        index.dimension(),
+        k
+    );
+    println!(
+        "latency ms: mean {:.3}  p50 {:.3}  p95 {:.3}  p99 {:.3}  max {:.3}",
+        latency.mean_ms(),
+        latency.quantile(0.5),
+        latency.quantile(0.95),
+        latency.quantile(0.99),
+        latency.max_ms()
+    );
+    println!(
+        "throughput: {:.1} queries/s",
+        count as f64 / elapsed.max(f64::EPSILON)
+    );
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,675 @@
+//! A small blocking HTTP/1.1 front end for `IndexService`
+//!
+//! Each connection gets a thread and carries one request (`Connection:
+//! close`), which is plenty for a backend calling a local sidecar. At most
+//! `max_connections` are served at once; the rest are answered 503 without
+//! being read. Bodies must be JSON with a `Content-Length`; oversized headers
+//! and bodies are refused before they are read, and a request that has not
+//! fully arrived by `read_timeout` is answered 408. Routes:
+//!
+//! - `GET /health`
+//! - `POST /search` — `{"vector": [...]}` or `{"id": ...}`, optional `"k"`
+//! - `POST /search/batch` — `{"vectors": [[...], ...], "k": 10}`
+//! - `GET /items/{id}`
+//! - `POST /upsert` — `{"id": ..., "vector": [...]}` or `{"items": [...]}`
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use meta_media_search_wasm::Error;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::service::{BatchRequest, IndexService, ItemId, UpsertRequest};
+
+/// Largest request line plus headers
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// Unread input discarded after a response before the connection is dropped
+const MAX_DRAIN_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Largest accepted request body
+    pub max_body_bytes: usize,
+    /// How long a client may take to send its whole request
+    pub read_timeout: Duration,
+    /// Connections served at once; more are refused with 503
+    pub max_connections: usize,
+    /// How often to check the index file for changes; `None` disables reloading
+    pub reload_interval: Option<Duration>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            max_body_bytes: 8 * 1024 * 1024,
+            read_timeout: Duration::from_secs(10),
+            max_connections: 64,
+            reload_interval: Some(Duration::from_secs(2)),
+        }
+    }
+}
+
+/// Stops a running server from another thread
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    stopping: Arc<AtomicBool>,
+    addr: SocketAddr,
+}
+
+impl ShutdownHandle {
+    /// Stop accepting connections; `run` returns once in-flight requests finish
+    pub fn shutdown(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the blocking accept
+        let _ = TcpStream::connect_timeout(&self.addr, Duration::from_secs(1));
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    service: Arc<IndexService>,
+    config: ServerConfig,
+    handle: ShutdownHandle,
+}
+
+impl Server {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        service: Arc<IndexService>,
+        config: ServerConfig,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let mut wake = listener.local_addr()?;
+        if wake.ip().is_unspecified() {
+            wake.set_ip(match wake {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        Ok(Server {
+            listener,
+            service,
+            config,
+            handle: ShutdownHandle {
+                stopping: Arc::new(AtomicBool::new(false)),
+                addr: wake,
+            },
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.handle.clone()
+    }
+
+    /// Serve until shut down, then wait for in-flight requests
+    pub fn run(self) -> io::Result<()> {
+        let reloader = self.config.reload_interval.map(|interval| {
+            let (service, handle) = (Arc::clone(&self.service), self.handle.clone());
+            thread::spawn(move || watch(&service, &handle, interval))
+        });
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let mut workers: Vec<JoinHandle<()>> = Vec::new();
+        for stream in self.listener.incoming() {
+            if self.handle.is_stopping() {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("mms-server: accept failed: {}", e);
+                    continue;
+                }
+            };
+            // Only this thread takes slots, so the count cannot overshoot
+            if active.load(Ordering::SeqCst) >= self.config.max_connections {
+                refuse(stream);
+                continue;
+            }
+            active.fetch_add(1, Ordering::SeqCst);
+            let slot = Slot(Arc::clone(&active));
+            let (service, config) = (Arc::clone(&self.service), self.config.clone());
+            workers.retain(|worker| !worker.is_finished());
+            workers.push(thread::spawn(move || {
+                let _slot = slot;
+                if let Err(e) = serve(stream, &service, &config) {
+                    eprintln!("mms-server: {}", e);
+                }
+            }));
+        }
+
+        for worker in workers.into_iter().chain(reloader) {
+            let _ = worker.join();
+        }
+        Ok(())
+    }
+}
+
+/// A connection being served; frees its place in the limit when dropped
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Answer 503 from the accepting thread, without waiting for the request
+fn refuse(mut stream: TcpStream) {
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+    let busy = Response::error(503, "SERVER_BUSY", "too many connections; retry shortly");
+    if busy.write_to(&mut stream).is_ok() && stream.shutdown(Shutdown::Write).is_ok() {
+        // Discard input that has already arrived, so closing does not reset
+        // the connection before the client reads the response
+        if stream.set_nonblocking(true).is_ok() {
+            let _ = io::copy(
+                &mut (&stream).take(MAX_HEADER_BYTES as u64),
+                &mut io::sink(),
+            );
+        }
+    }
+}
+
+/// Poll the index file and reload it when it changes
+fn watch(service: &IndexService, handle: &ShutdownHandle, interval: Duration) {
+    let tick = Duration::from_millis(50).min(interval);
+    let mut waited = Duration::ZERO;
+    while !handle.is_stopping() {
+        thread::sleep(tick);
+        waited += tick;
+        if waited < interval {
+            continue;
+        }
+        waited = Duration::ZERO;
+        match service.reload_if_changed() {
+            Ok(true) => eprintln!("mms-server: reloaded {}", service.path().display()),
+            Ok(false) => {}
+            Err(e) => eprintln!("mms-server: keeping the loaded index: {}", e),
+        }
+    }
+}
+
+/// A response ready to be written
+struct Response {
+    status: u16,
+    body: Value,
+    allow: Option<&'static str>,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Response {
+            status: 200,
+            body,
+            allow: None,
+        }
+    }
+
+    fn error(status: u16, code: &str, message: impl Into<String>) -> Self {
+        Response {
+            status,
+            body: json!({ "error": { "code": code, "message": message.into(), "details": {} } }),
+            allow: None,
+        }
+    }
+
+    fn from_error(e: Error) -> Self {
+        let status = match e {
+            Error::NotFound { .. } | Error::IndexOutOfBounds { .. } => 404,
+            Error::InvalidState { .. } => 409,
+            Error::Io { .. }
+            | Error::Serialize { .. }
+            | Error::Unavailable { .. }
+            | Error::Aborted { .. } => 500,
+            _ => 400,
+        };
+        Response {
+            status,
+            body: json!({
+                "error": { "code": e.code(), "message": e.to_string(), "details": e.details() }
+            }),
+            allow: None,
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        let body = self.body.to_string();
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            reason(self.status),
+            body.len()
+        );
+        if let Some(allow) = self.allow {
+            head.push_str(&format!("Allow: {}\r\n", allow));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        stream.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// A parsed request line and the headers the server cares about
+struct Head {
+    method: String,
+    path: String,
+    content_length: Option<usize>,
+    chunked: bool,
+}
+
+/// Reads from a stream until a fixed deadline, however the bytes trickle in
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// The response for a failed read: 408 once the deadline passes, else 400
+fn read_error(e: io::Error) -> Response {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+            Response::error(408, "REQUEST_TIMEOUT", "the request did not arrive in time")
+        }
+        _ => Response::error(400, "BAD_REQUEST", e.to_string()),
+    }
+}
+
+fn serve(stream: TcpStream, service: &IndexService, config: &ServerConfig) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(DeadlineReader {
+        stream,
+        deadline: Instant::now() + config.read_timeout,
+    });
+    let response = match read_head(&mut reader) {
+        Ok(Some(head)) => respond(&head, &mut reader, service, config),
+        // The connection closed without a request, e.g. the shutdown wake-up
+        Ok(None) => return Ok(()),
+        Err(response) => response,
+    };
+    response.write_to(&mut writer)?;
+    // Closing with unread input would reset the connection and could discard
+    // the response, so let a refused request finish arriving (up to a point)
+    writer.shutdown(Shutdown::Write)?;
+    let _ = io::copy(&mut reader.take(MAX_DRAIN_BYTES), &mut io::sink());
+    Ok(())
+}
+
+fn read_head(reader: &mut impl BufRead) -> Result<Option<Head>, Response> {
+    let mut limited = reader.take(MAX_HEADER_BYTES as u64);
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let read = limited.read_line(&mut line).map_err(read_error)?;
+        if read == 0 || !line.ends_with('\n') {
+            if limited.limit() > 0 {
+                // The client hung up mid-request, or without sending one
+                return Ok(None);
+            }
+            return Err(Response::error(
+                431,
+                "HEADERS_TOO_LARGE",
+                format!("request headers exceed {} bytes", MAX_HEADER_BYTES),
+            ));
+        }
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let bad = |message: &str| Response::error(400, "BAD_REQUEST", message);
+    let mut request_line = lines
+        .first()
+        .ok_or_else(|| bad("missing request line"))?
+        .split(' ');
+    let (Some(method), Some(target), Some(version)) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return Err(bad("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(bad("only HTTP/1.x is supported"));
+    }
+    let mut head = Head {
+        method: method.to_string(),
+        path: target.split('?').next().unwrap_or_default().to_string(),
+        content_length: None,
+        chunked: false,
+    };
+    for line in &lines[1..] {
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(bad("malformed header"));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            head.content_length = Some(value.parse().map_err(|_| bad("invalid Content-Length"))?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            head.chunked = true;
+        }
+    }
+    Ok(Some(head))
+}
+
+fn respond(
+    head: &Head,
+    reader: &mut impl Read,
+    service: &IndexService,
+    config: &ServerConfig,
+) -> Response {
+    let segments: Vec<&str> = head.path.trim_matches('/').split('/').collect();
+    let (allow, route) = match segments.as_slice() {
+        ["health"] => ("GET", Route::Health),
+        ["search"] => ("POST", Route::Search),
+        ["search", "batch"] => ("POST", Route::SearchBatch),
+        ["items", id] => match percent_decode(id) {
+            Some(id) => ("GET", Route::Item(id)),
+            None => return Response::error(400, "BAD_REQUEST", "invalid percent-encoding"),
+        },
+        ["upsert"] => ("POST", Route::Upsert),
+        _ => {
+            return Response::error(
+                404,
+                "ROUTE_NOT_FOUND",
+                format!("no route for {}", head.path),
+            )
+        }
+    };
+    if head.method != allow {
+        let mut response = Response::error(
+            405,
+            "METHOD_NOT_ALLOWED",
+            format!("{} only accepts {}", head.path, allow),
+        );
+        response.allow = Some(allow);
+        return response;
+    }
+
+    let result = match route {
+        Route::Health => Ok(Ok(service.health())),
+        Route::Item(id) => Ok(service.item(&ItemId::Key(id))),
+        Route::Search => read_json(head, reader, config).map(|r| timed(1, || service.search(r))),
+        Route::SearchBatch => read_json(head, reader, config)
+            .map(|r: BatchRequest| timed(r.vectors.len(), || service.search_batch(r))),
+        Route::Upsert => {
+            read_json(head, reader, config).map(|r: UpsertRequest| service.upsert(r.into_items()))
+        }
+    };
+    match result {
+        Ok(Ok(body)) => Response::ok(body),
+        Ok(Err(e)) => Response::from_error(e),
+        Err(refused) => refused,
+    }
+}
+
+/// Add the mean time per query to a search response as `query_time_ms`
+fn timed(queries: usize, search: impl FnOnce() -> Result<Value, Error>) -> Result<Value, Error> {
+    let start = Instant::now();
+    let mut response = search()?;
+    response["query_time_ms"] =
+        json!(start.elapsed().as_secs_f64() * 1000.0 / queries.max(1) as f64);
+    Ok(response)
+}
+
+enum Route {
+    Health,
+    Search,
+    SearchBatch,
+    Item(String),
+    Upsert,
+}
+
+/// Read and parse a JSON body, refusing ones the transport limits rule out
+fn read_json<T: DeserializeOwned>(
+    head: &Head,
+    reader: &mut impl Read,
+    config: &ServerConfig,
+) -> Result<T, Response> {
+    let len = match (head.chunked, head.content_length) {
+        (true, _) => {
+            return Err(Response::error(
+                501,
+                "UNSUPPORTED_TRANSFER_ENCODING",
+                "chunked bodies are not supported; send Content-Length",
+            ))
+        }
+        (false, None) => {
+            return Err(Response::error(
+                411,
+                "LENGTH_REQUIRED",
+                "a Content-Length header is required",
+            ))
+        }
+        (false, Some(len)) if len > config.max_body_bytes => {
+            return Err(Response::error(
+                413,
+                "BODY_TOO_LARGE",
+                format!(
+                    "request bodies are limited to {} bytes",
+                    config.max_body_bytes
+                ),
+            ))
+        }
+        (false, Some(len)) => len,
+    };
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).map_err(read_error)?;
+    serde_json::from_slice(&body).map_err(|e| {
+        Response::from_error(Error::Parse {
+            what: "request body",
+            message: e.to_string(),
+        })
+    })
+}
+
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::tests::temp_index;
+
+    fn send(addr: SocketAddr, raw: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn post(addr: SocketAddr, path: &str, body: &str) -> (u16, Value) {
+        send(
+            addr,
+            &format!(
+                "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+                path,
+                body.len(),
+                body
+            ),
+        )
+    }
+
+    fn get(addr: SocketAddr, path: &str) -> (u16, Value) {
+        send(
+            addr,
+            &format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path),
+        )
+    }
+
+    #[test]
+    fn test_routes_limits_and_shutdown() {
+        let path = temp_index();
+        let service = Arc::new(IndexService::open(&path, false).unwrap());
+        let config = ServerConfig {
+            max_body_bytes: 256,
+            reload_interval: None,
+            ..ServerConfig::default()
+        };
+        let server = Server::bind("127.0.0.1:0", service, config).unwrap();
+        let (addr, handle) = (server.local_addr().unwrap(), server.shutdown_handle());
+        let running = thread::spawn(move || server.run());
+
+        let (status, health) = get(addr, "/health");
+        assert_eq!((status, &health["size"]), (200, &json!(3)));
+
+        let (status, found) = post(addr, "/search", r#"{"vector": [1, 0, 0], "k": 2}"#);
+        assert_eq!(status, 200);
+        assert_eq!(found["results"][0]["id"], "a");
+        assert_eq!(found["results"][1]["id"], "b");
+        assert!(found["query_time_ms"].is_number());
+        let (_, like) = post(addr, "/search", r#"{"id": "c", "k": 1}"#);
+        assert_ne!(like["results"][0]["id"], "c");
+        let (status, batch) = post(
+            addr,
+            "/search/batch",
+            r#"{"vectors": [[0, 0, 1], [1, 0, 0]], "k": 1}"#,
+        );
+        assert_eq!(status, 200);
+        assert_eq!(batch["results"][0][0]["id"], "c");
+
+        let (status, upserted) = post(addr, "/upsert", r#"{"id": "x y", "vector": [0, 1, 0]}"#);
+        assert_eq!((status, &upserted["size"]), (200, &json!(4)));
+        let (status, item) = get(addr, "/items/x%20y");
+        assert_eq!((status, &item["id"]), (200, &json!("x y")));
+
+        let (status, missing) = get(addr, "/items/zzz");
+        assert_eq!(
+            (status, &missing["error"]["code"]),
+            (404, &json!("NOT_FOUND"))
+        );
+        let (status, wrong) = post(addr, "/search", r#"{"vector": [1, 0]}"#);
+        assert_eq!(
+            (status, &wrong["error"]["code"]),
+            (400, &json!("DIMENSION_MISMATCH"))
+        );
+        let (status, _) = post(addr, "/search", "{not json");
+        assert_eq!(status, 400);
+        assert_eq!(get(addr, "/search").0, 405);
+        assert_eq!(get(addr, "/nowhere").0, 404);
+        let big = format!(r#"{{"vector": [{}]}}"#, vec!["0.5"; 100].join(","));
+        assert_eq!(post(addr, "/search", &big).0, 413);
+        let chunked = "POST /search HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        assert_eq!(send(addr, chunked).0, 501);
+        let huge_header = format!(
+            "GET /health HTTP/1.1\r\nX-Pad: {}\r\n\r\n",
+            "a".repeat(MAX_HEADER_BYTES)
+        );
+        assert_eq!(send(addr, &huge_header).0, 431);
+
+        handle.shutdown();
+        running.join().unwrap().unwrap();
+        assert!(TcpStream::connect(addr).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_connection_limit_and_deadline() {
+        let path = temp_index();
+        let service = Arc::new(IndexService::open(&path, false).unwrap());
+        let config = ServerConfig {
+            read_timeout: Duration::from_millis(300),
+            max_connections: 1,
+            reload_interval: None,
+            ..ServerConfig::default()
+        };
+        let server = Server::bind("127.0.0.1:0", service, config).unwrap();
+        let (addr, handle) = (server.local_addr().unwrap(), server.shutdown_handle());
+        let running = thread::spawn(move || server.run());
+
+        // A client trickling its headers holds the only slot, but only until
+        // the deadline for the whole request
+        let started = Instant::now();
+        let mut slow = TcpStream::connect(addr).unwrap();
+        slow.write_all(b"GET /health HTTP/1.1\r\n").unwrap();
+        let mut busy = TcpStream::connect(addr).unwrap();
+        let mut response = String::new();
+        busy.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 "), "{}", response);
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(100));
+            let _ = slow.write_all(b"X-Pad: a\r\n");
+        }
+        let mut response = String::new();
+        slow.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 "), "{}", response);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // The slot is free again once the slow connection is done
+        let mut healthy = None;
+        for _ in 0..50 {
+            match get(addr, "/health").0 {
+                503 => thread::sleep(Duration::from_millis(20)),
+                status => {
+                    healthy = Some(status);
+                    break;
+                }
+            }
+        }
+        assert_eq!(healthy, Some(200));
+
+        handle.shutdown();
+        running.join().unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
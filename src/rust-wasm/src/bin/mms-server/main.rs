@@ -0,0 +1,116 @@
+//! `mms-server`: serve an index file over local HTTP or stdin/stdout
+//!
+//! Built with the `server` feature. Over HTTP, the index is searched in memory
+//! and reloaded when its file changes; Ctrl-C or SIGTERM stops accepting
+//! connections and exits once in-flight requests are answered. With `--stdio`
+//! it answers line-delimited JSON requests instead, for a parent process that
+//! spawns it, and exits when its input ends.
+
+// Shared with mms-index, which uses more of it
+#[allow(dead_code)]
+#[path = "../mms-index/args.rs"]
+mod args;
+mod http;
+mod service;
+mod stdio;
+
+use std::error::Error;
+use std::io::{self, BufWriter};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use args::Args;
+use http::{Server, ServerConfig};
+use service::IndexService;
+
+const USAGE: &str = "\
+Usage: mms-server <index> [options]
+
+Serves /health, /search, /search/batch, /items/{id} and /upsert as JSON.
+
+Options:
+  --addr <host:port>         Listen address (default 127.0.0.1:8080)
+  --max-body <bytes>         Largest request body or line (default 8388608)
+  --max-connections <n>      Connections served at once; more get 503 (default 64)
+  --reload-interval <secs>   How often to check the index file for changes (default 2; 0 disables)
+  --persist                  Write changes back to the index file
+  --stdio                    Answer one JSON request per stdin line instead of HTTP:
+                             {\"id\": 1, \"method\": \"search\", \"params\": {\"vector\": [...], \"k\": 10}}
+                             Methods: search, search_batch, item, add, remove, stats, save
+";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        print!("{}", USAGE);
+        return if args.is_empty() {
+            ExitCode::from(2)
+        } else {
+            ExitCode::SUCCESS
+        };
+    }
+    match run(args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("mms-server: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<ExitCode, Box<dyn Error>> {
+    let args = Args::parse(
+        args,
+        &["addr", "max-body", "max-connections", "reload-interval"],
+        &["persist", "stdio"],
+    )?;
+    args.expect_positionals(1)?;
+    let path = args.positional(0, "index file")?;
+    let defaults = ServerConfig::default();
+    let reload_secs = args.parse_or("reload-interval", 2.0f64)?;
+    let config = ServerConfig {
+        max_body_bytes: args.parse_or("max-body", defaults.max_body_bytes)?,
+        max_connections: args
+            .parse_or("max-connections", defaults.max_connections)?
+            .max(1),
+        reload_interval: (reload_secs > 0.0).then(|| Duration::from_secs_f64(reload_secs)),
+        ..defaults
+    };
+
+    let service = Arc::new(IndexService::open(path, args.flag("persist"))?);
+    if args.flag("stdio") {
+        // Requests arrive in order on one stream, so the file is not watched;
+        // stdout carries only responses
+        let (stdin, stdout) = (io::stdin().lock(), io::stdout().lock());
+        match stdio::serve(
+            &service,
+            stdin,
+            BufWriter::new(stdout),
+            config.max_body_bytes,
+        ) {
+            // The parent stopped reading responses, which ends the session too
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let addr = args.value("addr").unwrap_or("127.0.0.1:8080");
+    let server = Server::bind(addr, Arc::clone(&service), config)
+        .map_err(|e| format!("cannot listen on {}: {}", addr, e))?;
+    let handle = server.shutdown_handle();
+    ctrlc::set_handler(move || handle.shutdown())?;
+
+    let health = service.health();
+    eprintln!(
+        "mms-server: serving {} ({} vectors of dimension {}) on http://{}",
+        path,
+        health["size"],
+        health["dimension"],
+        server.local_addr()?
+    );
+    server.run()?;
+    eprintln!("mms-server: stopped");
+    Ok(ExitCode::SUCCESS)
+}
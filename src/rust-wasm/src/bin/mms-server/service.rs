@@ -0,0 +1,520 @@
+//! Index state shared by the server front ends
+//!
+//! An `IndexService` owns one loaded index file. Searches take a shared lock
+//! and run concurrently; upserts and reloads take it exclusively. With
+//! `persist`, upserts and removals are written back to the file before they
+//! are applied and acknowledged, so a failed write changes nothing; without
+//! it they last until the file is reloaded.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
+
+use meta_media_search_wasm::index_file::IndexFile;
+use meta_media_search_wasm::vector_search::SearchResult;
+use meta_media_search_wasm::{Error, QueryBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Results per query when a request does not say
+pub const DEFAULT_K: usize = 10;
+
+/// An item key, or a numeric ID in indexes without keys
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ItemId {
+    Key(String),
+    Id(usize),
+}
+
+impl ItemId {
+    pub fn as_key(&self) -> String {
+        match self {
+            ItemId::Key(key) => key.clone(),
+            ItemId::Id(id) => id.to_string(),
+        }
+    }
+}
+
+/// Search by vector, or for items like a stored one (which is excluded)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SearchRequest {
+    #[serde(default)]
+    pub vector: Option<Vec<f32>>,
+    #[serde(default)]
+    pub id: Option<ItemId>,
+    #[serde(default = "default_k")]
+    pub k: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchRequest {
+    pub vectors: Vec<Vec<f32>>,
+    #[serde(default = "default_k")]
+    pub k: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Item {
+    pub id: ItemId,
+    pub vector: Vec<f32>,
+}
+
+/// One item, or `{"items": [...]}`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum UpsertRequest {
+    Many { items: Vec<Item> },
+    One(Item),
+}
+
+impl UpsertRequest {
+    pub fn into_items(self) -> Vec<Item> {
+        match self {
+            UpsertRequest::Many { items } => items,
+            UpsertRequest::One(item) => vec![item],
+        }
+    }
+}
+
+/// One item, or `{"ids": [...]}`
+#[derive(Debug, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum RemoveRequest {
+    Many { ids: Vec<ItemId> },
+    One { id: ItemId },
+}
+
+impl RemoveRequest {
+    pub fn into_ids(self) -> Vec<ItemId> {
+        match self {
+            RemoveRequest::Many { ids } => ids,
+            RemoveRequest::One { id } => vec![id],
+        }
+    }
+}
+
+fn default_k() -> usize {
+    DEFAULT_K
+}
+
+struct Loaded {
+    file: IndexFile,
+    modified: Option<SystemTime>,
+    /// Bumped on every reload, so clients can tell the index changed
+    generation: u64,
+}
+
+pub struct IndexService {
+    path: PathBuf,
+    persist: bool,
+    state: RwLock<Loaded>,
+}
+
+impl IndexService {
+    /// Load the index file at `path`
+    pub fn open(path: impl Into<PathBuf>, persist: bool) -> Result<Self, Error> {
+        let path = path.into();
+        let (file, modified) = load(&path)?;
+        Ok(IndexService {
+            path,
+            persist,
+            state: RwLock::new(Loaded {
+                file,
+                modified,
+                generation: 0,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // A panic while holding the lock cannot leave the index half-updated
+    // (every change is validated first), so poisoning is ignored
+    fn read(&self) -> RwLockReadGuard<'_, Loaded> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Loaded> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn search(&self, request: SearchRequest) -> Result<Value, Error> {
+        let state = self.read();
+        let file = &state.file;
+        let results = match (&request.vector, &request.id) {
+            (Some(vector), None) => file.index().search(vector, request.k)?,
+            (None, Some(id)) => {
+                let mut like = QueryBuilder::new();
+                like.like(find(file, id)?);
+                like.search_index(file.index(), request.k)?
+            }
+            _ => {
+                return Err(Error::InvalidArgument {
+                    operation: "search",
+                    message: "pass exactly one of \"vector\" and \"id\"".to_string(),
+                })
+            }
+        };
+        Ok(json!({ "results": hits(file, &results) }))
+    }
+
+    pub fn search_batch(&self, request: BatchRequest) -> Result<Value, Error> {
+        let state = self.read();
+        let file = &state.file;
+        let dimension = file.index().dimension();
+        if let Some(vector) = request.vectors.iter().find(|v| v.len() != dimension) {
+            return Err(Error::DimensionMismatch {
+                operation: "search_batch",
+                expected: dimension,
+                actual: vector.len(),
+            });
+        }
+        let results = file
+            .index()
+            .search_batch(&request.vectors.concat(), request.k)?;
+        let results: Vec<Value> = results.iter().map(|r| hits(file, r)).collect();
+        Ok(json!({ "results": results }))
+    }
+
+    /// The stored vector of one item
+    pub fn item(&self, id: &ItemId) -> Result<Value, Error> {
+        let state = self.read();
+        let id = find(&state.file, id)?;
+        Ok(json!({
+            "id": state.file.key(id),
+            "vector": state.file.index().get_vector(id),
+        }))
+    }
+
+    /// Replace or add items; either every item is applied or none is
+    pub fn upsert(&self, items: Vec<Item>) -> Result<Value, Error> {
+        let mut state = self.write();
+        let file = &mut state.file;
+        let dimension = file.index().dimension();
+        // Check every item up front so a bad one cannot leave a partial update
+        let mut next_id = file.index().size();
+        for item in &items {
+            if item.vector.len() != dimension {
+                return Err(Error::DimensionMismatch {
+                    operation: "upsert",
+                    expected: dimension,
+                    actual: item.vector.len(),
+                });
+            }
+            if file.keys().is_none() {
+                match item.id.as_key().parse::<usize>() {
+                    Ok(id) if id < next_id => {}
+                    Ok(id) if id == next_id => next_id += 1,
+                    _ => {
+                        return Err(Error::InvalidArgument {
+                            operation: "upsert",
+                            message: format!(
+                                "this index has no item keys, so item {} must be an ID below {} or equal to it",
+                                item.id.as_key(),
+                                next_id
+                            ),
+                        })
+                    }
+                }
+            }
+        }
+
+        let (inserted, size) = self.apply(&mut state, |file| {
+            let mut inserted = 0;
+            for item in &items {
+                let (_, added) = file.upsert(&item.id.as_key(), &item.vector)?;
+                inserted += added as usize;
+            }
+            Ok((inserted, file.index().size()))
+        })?;
+        Ok(json!({
+            "inserted": inserted,
+            "updated": items.len() - inserted,
+            "size": size,
+        }))
+    }
+
+    /// Remove items; either every item is removed or none is
+    ///
+    /// Only indexes with item keys support removal, since removing an item
+    /// renumbers the ones after it.
+    pub fn remove(&self, ids: Vec<ItemId>) -> Result<Value, Error> {
+        let mut state = self.write();
+        if state.file.keys().is_none() {
+            return Err(Error::InvalidState {
+                operation: "remove",
+                message: "this index has no item keys, so items cannot be removed".to_string(),
+            });
+        }
+        let mut keys: Vec<String> = ids.iter().map(ItemId::as_key).collect();
+        keys.sort();
+        keys.dedup();
+        if let Some(key) = keys.iter().find(|key| state.file.find(key).is_none()) {
+            return Err(Error::NotFound {
+                kind: "item",
+                name: key.clone(),
+            });
+        }
+        let size = self.apply(&mut state, |file| {
+            for key in &keys {
+                file.remove(key)?;
+            }
+            Ok(file.index().size())
+        })?;
+        Ok(json!({ "removed": keys.len(), "size": size }))
+    }
+
+    pub fn health(&self) -> Value {
+        let state = self.read();
+        let index = state.file.index();
+        json!({
+            "status": "ok",
+            "size": index.size(),
+            "dimension": index.dimension(),
+            "metric": index.metric(),
+            "model": index.model().map(|m| m.to_string()),
+            "keys": state.file.keys().is_some(),
+            "generation": state.generation,
+            "persist": self.persist,
+        })
+    }
+
+    /// Reload the index if its file changed on disk; returns whether it did
+    ///
+    /// A file that fails to load is reported and the current index is kept.
+    pub fn reload_if_changed(&self) -> Result<bool, Error> {
+        if modified(&self.path)? == self.read().modified {
+            return Ok(false);
+        }
+        // Check again under the write lock: a persisted change may have been
+        // written since, and loading the file read before it would undo it
+        let mut state = self.write();
+        if modified(&self.path)? == state.modified {
+            return Ok(false);
+        }
+        let (file, modified) = load(&self.path)?;
+        state.file = file;
+        state.modified = modified;
+        state.generation += 1;
+        Ok(true)
+    }
+
+    /// Write the index back to its file
+    pub fn save(&self) -> Result<Value, Error> {
+        let mut state = self.write();
+        state.modified = self.save_file(&state.file)?;
+        Ok(json!({
+            "path": self.path.display().to_string(),
+            "size": state.file.index().size(),
+        }))
+    }
+
+    /// Apply `change` to the index; with `persist`, it is made to a copy that
+    /// replaces the index only once the copy has been written
+    fn apply<T>(
+        &self,
+        state: &mut Loaded,
+        change: impl FnOnce(&mut IndexFile) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if !self.persist {
+            return change(&mut state.file);
+        }
+        let mut file = state.file.clone();
+        let result = change(&mut file)?;
+        state.modified = self.save_file(&file)?;
+        state.file = file;
+        Ok(result)
+    }
+
+    /// Write `file` to the index path; returns the new modification time
+    fn save_file(&self, file: &IndexFile) -> Result<Option<SystemTime>, Error> {
+        // Write beside the file and rename, so readers never see half a file
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let io = |e: std::io::Error| io_error(&self.path, e);
+        fs::write(&tmp, file.to_bytes()).map_err(io)?;
+        fs::rename(&tmp, &self.path).map_err(io)?;
+        // Our own write is not a change to reload
+        modified(&self.path)
+    }
+}
+
+fn load(path: &Path) -> Result<(IndexFile, Option<SystemTime>), Error> {
+    let modified = modified(path)?;
+    let bytes = fs::read(path).map_err(|e| io_error(path, e))?;
+    Ok((IndexFile::from_bytes(&bytes)?, modified))
+}
+
+fn modified(path: &Path) -> Result<Option<SystemTime>, Error> {
+    let metadata = fs::metadata(path).map_err(|e| io_error(path, e))?;
+    Ok(metadata.modified().ok())
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Io {
+        path: path.display().to_string(),
+        message: e.to_string(),
+    }
+}
+
+fn find(file: &IndexFile, id: &ItemId) -> Result<usize, Error> {
+    let key = id.as_key();
+    file.find(&key).ok_or(Error::NotFound {
+        kind: "item",
+        name: key,
+    })
+}
+
+fn hits(file: &IndexFile, results: &[SearchResult]) -> Value {
+    results
+        .iter()
+        .map(|r| json!({ "id": file.key(r.id), "score": r.score }))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use meta_media_search_wasm::vector_search::{DistanceMetric, VectorIndex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Write a three-item, keyed index to a fresh temporary file
+    pub(crate) fn temp_index() -> PathBuf {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "mms-server-{}-{}.mmsi",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file =
+            IndexFile::with_keys(VectorIndex::new(3, DistanceMetric::Cosine), Vec::new()).unwrap();
+        for (key, vector) in [
+            ("a", [1.0, 0.0, 0.0]),
+            ("b", [0.9, 0.1, 0.0]),
+            ("c", [0.0, 0.0, 1.0]),
+        ] {
+            file.push(Some(key.to_string()), &vector).unwrap();
+        }
+        fs::write(&path, file.to_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_upsert_persist_and_reload() {
+        let path = temp_index();
+        let service = IndexService::open(&path, true).unwrap();
+        let search = |id: &str| SearchRequest {
+            vector: None,
+            id: Some(ItemId::Key(id.to_string())),
+            k: 1,
+        };
+        assert_eq!(
+            service.search(search("a")).unwrap()["results"][0]["id"],
+            "b"
+        );
+
+        let bad = vec![
+            Item {
+                id: ItemId::Key("d".to_string()),
+                vector: vec![1.0, 0.0, 0.0],
+            },
+            Item {
+                id: ItemId::Key("e".to_string()),
+                vector: vec![1.0],
+            },
+        ];
+        let Err(err) = service.upsert(bad) else {
+            panic!("a short vector was accepted");
+        };
+        assert_eq!(err.code(), "DIMENSION_MISMATCH");
+        assert_eq!(service.health()["size"], 3);
+
+        let item = Item {
+            id: ItemId::Key("d".to_string()),
+            vector: vec![1.0, 0.0, 0.1],
+        };
+        assert_eq!(service.upsert(vec![item]).unwrap()["inserted"], 1);
+        assert!(!service.reload_if_changed().unwrap());
+
+        // Another writer replaces the file: the original three items come back
+        let reopened = IndexService::open(&path, false).unwrap();
+        assert_eq!(reopened.health()["size"], 4);
+        let original = temp_index();
+        fs::rename(&original, &path).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(service.reload_if_changed().unwrap());
+        assert_eq!(service.health()["size"], 3);
+        assert_eq!(service.health()["generation"], 1);
+        assert_eq!(
+            service
+                .item(&ItemId::Key("d".to_string()))
+                .unwrap_err()
+                .code(),
+            "NOT_FOUND"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_remove_needs_keys() {
+        let path = temp_index();
+        let mut keyless = IndexFile::new(VectorIndex::new(3, DistanceMetric::Cosine));
+        for vector in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            keyless.push(None, &vector).unwrap();
+        }
+        fs::write(&path, keyless.to_bytes()).unwrap();
+        let service = IndexService::open(&path, false).unwrap();
+        let Err(err) = service.remove(vec![ItemId::Id(0)]) else {
+            panic!("an item was removed from an index without keys");
+        };
+        assert_eq!(err.code(), "INVALID_STATE");
+        assert_eq!(
+            service.item(&ItemId::Id(1)).unwrap()["vector"],
+            json!([0.0, 1.0, 0.0])
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_failed_save_changes_nothing() {
+        let path = temp_index();
+        let service = IndexService::open(&path, true).unwrap();
+        // A directory in the way of the temporary file makes every save fail
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::create_dir(&tmp).unwrap();
+
+        let item = Item {
+            id: ItemId::Key("d".to_string()),
+            vector: vec![1.0, 0.0, 0.1],
+        };
+        let Err(err) = service.upsert(vec![item]) else {
+            panic!("the upsert was acknowledged without being saved");
+        };
+        assert_eq!(err.code(), "IO_ERROR");
+        let Err(err) = service.remove(vec![ItemId::Key("a".to_string())]) else {
+            panic!("the removal was acknowledged without being saved");
+        };
+        assert_eq!(err.code(), "IO_ERROR");
+        assert_eq!(service.health()["size"], 3);
+        assert!(service.item(&ItemId::Key("a".to_string())).is_ok());
+        assert!(service.item(&ItemId::Key("d".to_string())).is_err());
+
+        fs::remove_dir(&tmp).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+//! Line-delimited JSON RPC over stdin and stdout
+//!
+//! Each input line is one request, `{"id": ..., "method": ..., "params": {...}}`,
+//! and gets exactly one output line in the same order: `{"id": ..., "result":
+//! ...}` or `{"id": ..., "error": {"code", "message", "details"}}`. The request
+//! ID may be any JSON value and is echoed back under the same name; captured
+//! traffic that calls it `request_id` gets `request_id` back. Lines that are not
+//! valid requests get an error response rather than ending the session, and
+//! responses carry no timings, so replaying a file of requests against the same
+//! index always produces the same output.
+//!
+//! Methods: `search`, `search_batch`, `item`, `add` (or `upsert`), `remove`,
+//! `stats` and `save`. Params match the HTTP bodies; `item` and `remove` take
+//! `{"id": ...}`, and `remove` also `{"ids": [...]}`.
+
+use std::io::{self, BufRead, Read, Write};
+
+use meta_media_search_wasm::Error;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+
+use crate::service::{IndexService, ItemId, RemoveRequest, UpsertRequest};
+
+/// Names a request ID may go by, in order of preference
+const ID_FIELDS: [&str; 2] = ["id", "request_id"];
+
+/// Answer requests from `input` until it ends
+pub fn serve(
+    service: &IndexService,
+    input: impl BufRead,
+    mut output: impl Write,
+    max_line_bytes: usize,
+) -> io::Result<()> {
+    let mut lines = LineReader {
+        input,
+        max: max_line_bytes,
+    };
+    while let Some(line) = lines.next_line()? {
+        let response = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => respond(service, &line),
+            Err(e) => reply(None, Err(e)),
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        output.write_all(b"\n")?;
+        // The caller may be waiting on this response before sending the next
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Answer one request line
+pub fn respond(service: &IndexService, line: &str) -> Value {
+    let request: Map<String, Value> = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return reply(
+                None,
+                Err(Error::Parse {
+                    what: "request",
+                    message: e.to_string(),
+                }),
+            )
+        }
+    };
+    let id = ID_FIELDS
+        .iter()
+        .find_map(|&name| request.get(name).map(|id| (name, id.clone())));
+    let params = request.get("params").cloned().unwrap_or(json!({}));
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => call(service, method, params),
+        None => Err(Error::InvalidArgument {
+            operation: "request",
+            message: "missing \"method\"".to_string(),
+        }),
+    };
+    reply(id, result)
+}
+
+fn call(service: &IndexService, method: &str, params: Value) -> Result<Value, Error> {
+    match method {
+        "search" => service.search(parse(params)?),
+        "search_batch" => service.search_batch(parse(params)?),
+        "item" => service.item(&parse::<ItemParams>(params)?.id),
+        "add" | "upsert" => service.upsert(parse::<UpsertRequest>(params)?.into_items()),
+        "remove" => service.remove(parse::<RemoveRequest>(params)?.into_ids()),
+        "stats" => Ok(service.health()),
+        "save" => service.save(),
+        _ => Err(Error::NotFound {
+            kind: "method",
+            name: method.to_string(),
+        }),
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemParams {
+    id: ItemId,
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, Error> {
+    serde_json::from_value(params).map_err(|e| Error::Parse {
+        what: "params",
+        message: e.to_string(),
+    })
+}
+
+fn reply(id: Option<(&str, Value)>, result: Result<Value, Error>) -> Value {
+    let (name, id) = id.unwrap_or((ID_FIELDS[0], Value::Null));
+    let mut response = Map::new();
+    response.insert(name.to_string(), id);
+    match result {
+        Ok(result) => response.insert("result".to_string(), result),
+        Err(e) => response.insert(
+            "error".to_string(),
+            json!({ "code": e.code(), "message": e.to_string(), "details": e.details() }),
+        ),
+    };
+    Value::Object(response)
+}
+
+/// Splits input into lines of at most `max` bytes
+struct LineReader<R> {
+    input: R,
+    max: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    /// The next line; an over-long one is skipped and reported as an error
+    fn next_line(&mut self) -> io::Result<Option<Result<String, Error>>> {
+        let mut line = Vec::new();
+        let read = self
+            .input
+            .by_ref()
+            .take(self.max as u64 + 1)
+            .read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.last() != Some(&b'\n') && line.len() > self.max {
+            self.skip_line()?;
+            return Ok(Some(Err(Error::InvalidArgument {
+                operation: "request",
+                message: format!("request lines are limited to {} bytes", self.max),
+            })));
+        }
+        Ok(Some(Ok(String::from_utf8_lossy(&line).into_owned())))
+    }
+
+    fn skip_line(&mut self) -> io::Result<()> {
+        loop {
+            let buffer = self.input.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(());
+            }
+            match buffer.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    self.input.consume(end + 1);
+                    return Ok(());
+                }
+                None => {
+                    let len = buffer.len();
+                    self.input.consume(len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::tests::temp_index;
+
+    fn session(service: &IndexService, input: &str, max_line_bytes: usize) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve(service, input.as_bytes(), &mut output, max_line_bytes).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_session_and_replay() {
+        let path = temp_index();
+        let service = IndexService::open(&path, false).unwrap();
+        let input = [
+            r#"{"id": 1, "method": "search", "params": {"vector": [1, 0, 0], "k": 1}}"#,
+            r#"{"id": "two", "method": "add", "params": {"id": "d", "vector": [0, 1, 0]}}"#,
+            "",
+            r#"{"id": 3, "method": "remove", "params": {"ids": ["a", "b"]}}"#,
+            r#"{"id": 4, "method": "remove", "params": {"id": "a"}}"#,
+            r#"{"request_id": "user-001", "title": "not an RPC call"}"#,
+            r#"{"id": 5, "method": "stats"}"#,
+            "not json",
+            &format!(r#"{{"id": 6, "pad": "{}"}}"#, "x".repeat(300)),
+            r#"{"id": 7, "method": "search", "params": {"id": "d", "k": 1}}"#,
+        ]
+        .join("\n");
+        let responses = session(&service, &input, 256);
+        assert_eq!(responses.len(), 9);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["results"][0]["id"], "a");
+        assert_eq!(responses[1]["id"], "two");
+        assert_eq!(responses[1]["result"]["inserted"], 1);
+        assert_eq!(responses[2]["result"], json!({ "removed": 2, "size": 2 }));
+        assert_eq!(responses[3]["error"]["code"], "NOT_FOUND");
+        assert_eq!(responses[4]["request_id"], "user-001");
+        assert_eq!(responses[4]["error"]["code"], "INVALID_ARGUMENT");
+        assert_eq!(responses[5]["result"]["size"], 2);
+        assert_eq!(responses[6]["id"], Value::Null);
+        assert_eq!(responses[6]["error"]["code"], "PARSE_ERROR");
+        assert_eq!(responses[7]["error"]["code"], "INVALID_ARGUMENT");
+        assert_eq!(responses[8]["id"], 7);
+        assert_eq!(responses[8]["result"]["results"][0]["id"], "c");
+
+        // Replaying the same traffic against the same index is byte-for-byte stable
+        let replay = |service: &IndexService| {
+            let mut output = Vec::new();
+            serve(service, input.as_bytes(), &mut output, 256).unwrap();
+            output
+        };
+        let first = replay(&IndexService::open(&path, false).unwrap());
+        assert_eq!(replay(&IndexService::open(&path, false).unwrap()), first);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+//! Incremental index building
+//!
+//! Adding tens of thousands of vectors in one `add_batch` call blocks the
+//! JavaScript thread for seconds. [`IndexBuild`] splits the work into chunks
+//! and remembers where the index stood before it started, so a build that is
+//! cancelled or fails can be rolled back instead of leaving half the vectors
+//! behind. `VectorSearchEngine::build_index` drives it asynchronously and
+//! yields to the event loop once each time slice is used up.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use std::cell::RefCell;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::Error;
+use crate::vector_search::VectorIndex;
+
+/// Options for `VectorSearchEngine::build_index`
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    chunk_size: usize,
+    time_budget_ms: f64,
+    on_progress: Option<js_sys::Function>,
+    signal: Option<JsValue>,
+}
+
+#[wasm_bindgen]
+impl BuildOptions {
+    /// Chunks of 1024 vectors, yielding after every 8 ms of work
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        BuildOptions {
+            chunk_size: 1024,
+            time_budget_ms: 8.0,
+            on_progress: None,
+            signal: None,
+        }
+    }
+
+    /// Vectors added between cancellation checks and progress reports
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
+
+    /// Work done before yielding to the event loop; 0 yields after every chunk
+    pub fn set_time_budget_ms(&mut self, time_budget_ms: f64) {
+        self.time_budget_ms = time_budget_ms.max(0.0);
+    }
+
+    /// Called as `onProgress(done, total)` after every chunk
+    pub fn set_on_progress(&mut self, on_progress: js_sys::Function) {
+        self.on_progress = Some(on_progress);
+    }
+
+    /// An `AbortSignal`, or any object whose `aborted` property becomes truthy
+    pub fn set_signal(&mut self, signal: JsValue) {
+        self.signal = Some(signal);
+    }
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildOptions {
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    fn is_aborted(&self) -> bool {
+        self.signal.as_ref().is_some_and(|signal| {
+            js_sys::Reflect::get(signal, &JsValue::from_str("aborted"))
+                .map(|aborted| aborted.is_truthy())
+                .unwrap_or(false)
+        })
+    }
+
+    fn report(&self, done: usize, total: usize) {
+        if let Some(on_progress) = &self.on_progress {
+            // Progress reporting is best effort; a throwing callback does not abort the build
+            let _ = on_progress.call2(
+                &JsValue::NULL,
+                &JsValue::from_f64(done as f64),
+                &JsValue::from_f64(total as f64),
+            );
+        }
+    }
+}
+
+/// A batch of vectors being added to an index a chunk at a time
+#[derive(Debug)]
+pub struct IndexBuild {
+    vectors: Vec<f32>,
+    dimension: usize,
+    done: usize,
+    start_size: usize,
+}
+
+impl IndexBuild {
+    /// Prepare to add the flat buffer `vectors` to `index`
+    pub fn new(index: &VectorIndex, vectors: Vec<f32>) -> Result<Self, Error> {
+        Error::check_batch("build_index", &vectors, index.dimension())?;
+        Ok(IndexBuild {
+            dimension: index.dimension(),
+            vectors,
+            done: 0,
+            start_size: index.size(),
+        })
+    }
+
+    /// Vectors added so far
+    pub fn done(&self) -> usize {
+        self.done
+    }
+
+    /// Vectors in the batch
+    pub fn total(&self) -> usize {
+        self.vectors.len() / self.dimension
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done == self.total()
+    }
+
+    /// Add up to `chunk_size` more vectors, returning how many were added
+    pub fn step(&mut self, index: &mut VectorIndex, chunk_size: usize) -> Result<usize, Error> {
+        let end = (self.done + chunk_size).min(self.total());
+        let count = end - self.done;
+        let chunk = &self.vectors[self.done * self.dimension..end * self.dimension];
+        index.add_vectors_batch(chunk, count)?;
+        self.done = end;
+        Ok(count)
+    }
+
+    /// Remove every vector this build added, restoring the index's previous size
+    pub fn rollback(self, index: &mut VectorIndex) {
+        index.truncate(self.start_size);
+    }
+}
+
+/// Run `build` against `index` chunk by chunk, yielding between time slices
+///
+/// The index is only borrowed while a chunk is added, so searches issued while
+/// the build is suspended see the vectors added so far. Cancellation or an
+/// error rolls the index, and its `index_size` gauge, back to the size before
+/// the build.
+pub(crate) async fn run(
+    index: &RefCell<VectorIndex>,
+    mut build: IndexBuild,
+    options: &BuildOptions,
+    metrics_name: &str,
+) -> Result<usize, Error> {
+    let clock = SystemClock;
+    let mut slice_start = clock.now_ms();
+    while !build.is_finished() {
+        if options.is_aborted() {
+            build.rollback(&mut index.borrow_mut());
+            report_size(index, metrics_name);
+            return Err(Error::Aborted {
+                operation: "build_index",
+            });
+        }
+        let step = build.step(&mut index.borrow_mut(), options.chunk_size);
+        if let Err(e) = step {
+            build.rollback(&mut index.borrow_mut());
+            report_size(index, metrics_name);
+            return Err(e);
+        }
+        options.report(build.done(), build.total());
+        report_size(index, metrics_name);
+
+        if clock.now_ms() - slice_start >= options.time_budget_ms {
+            yield_to_event_loop().await;
+            slice_start = clock.now_ms();
+        }
+    }
+    Ok(build.total())
+}
+
+/// Update the engine's `index_size` gauge to the index's current size
+fn report_size(index: &RefCell<VectorIndex>, metrics_name: &str) {
+    let size = index.borrow().size() as f64;
+    crate::metrics::with_registry(|m| m.set_engine_gauge(metrics_name, "index_size", size));
+}
+
+/// Resolve on a later macrotask, letting the host render and handle input
+async fn yield_to_event_loop() {
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let set_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .ok()
+            .and_then(|f| f.dyn_into::<js_sys::Function>().ok());
+        let scheduled = set_timeout
+            .map(|set_timeout| {
+                set_timeout
+                    .call2(&JsValue::NULL, &resolve, &JsValue::from_f64(0.0))
+                    .is_ok()
+            })
+            .unwrap_or(false);
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::NULL);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_search::DistanceMetric;
+
+    #[test]
+    fn test_chunked_build_and_rollback() {
+        let data: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let mut expected = VectorIndex::new(2, DistanceMetric::Euclidean);
+        expected.add_vector(&[9.0, 9.0]).unwrap();
+        expected.add_vectors_batch(&data, 10).unwrap();
+
+        let mut index = VectorIndex::new(2, DistanceMetric::Euclidean);
+        index.add_vector(&[9.0, 9.0]).unwrap();
+        let mut build = IndexBuild::new(&index, data.clone()).unwrap();
+        assert_eq!(build.step(&mut index, 4).unwrap(), 4);
+        assert_eq!((build.done(), build.total(), index.size()), (4, 10, 5));
+        build.rollback(&mut index);
+        assert_eq!(index.size(), 1);
+
+        let mut build = IndexBuild::new(&index, data).unwrap();
+        while !build.is_finished() {
+            build.step(&mut index, 3).unwrap();
+        }
+        assert_eq!(index.size(), 11);
+        for id in 0..11 {
+            assert_eq!(index.get_vector(id), expected.get_vector(id));
+        }
+
+        let err = IndexBuild::new(&index, vec![0.0; 3]).unwrap_err();
+        assert_eq!(err.code(), "INVALID_BATCH");
+    }
+}
@@ -0,0 +1,381 @@
+//! Long-text chunking for documents that exceed the encoder's sequence length
+//!
+//! Splits text into overlapping token windows that keep their byte offsets, and
+//! indexes the pooled document embeddings alongside the individual chunks so a
+//! search can point at the passage that matched.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::tokenizer::{Encoding, Tokenizer, DEFAULT_MAX_SEQUENCE_LENGTH};
+use crate::vector_search::{DistanceMetric, VectorIndex};
+
+/// Window size and overlap used to split long texts
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ChunkConfig {
+    max_tokens: usize,
+    overlap: usize,
+}
+
+#[wasm_bindgen]
+impl ChunkConfig {
+    /// Windows of at most `max_tokens` tokens (special tokens included) sharing `overlap` tokens
+    #[wasm_bindgen(constructor)]
+    pub fn new(max_tokens: usize, overlap: usize) -> Self {
+        ChunkConfig {
+            max_tokens,
+            overlap,
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn overlap(&self) -> usize {
+        self.overlap
+    }
+}
+
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SEQUENCE_LENGTH, 64)
+    }
+}
+
+/// A window of the original text
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextChunk {
+    index: usize,
+    start: usize,
+    end: usize,
+    token_count: usize,
+    text: String,
+}
+
+#[wasm_bindgen]
+impl TextChunk {
+    /// Position of the chunk within its document
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Byte offset where the chunk starts in the original text
+    #[wasm_bindgen(getter)]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Byte offset where the chunk ends in the original text
+    #[wasm_bindgen(getter)]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Number of model tokens in the chunk, excluding special tokens
+    #[wasm_bindgen(getter)]
+    pub fn token_count(&self) -> usize {
+        self.token_count
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn text(&self) -> String {
+        self.text.clone()
+    }
+}
+
+/// Split `text` into overlapping token windows
+#[wasm_bindgen]
+pub fn chunk_text(tokenizer: &Tokenizer, text: &str, config: &ChunkConfig) -> Vec<TextChunk> {
+    encode_chunks(tokenizer, text, config)
+        .into_iter()
+        .map(|(chunk, _)| chunk)
+        .collect()
+}
+
+/// Split `text` into windows, returning each chunk with its encoding
+pub(crate) fn encode_chunks(
+    tokenizer: &Tokenizer,
+    text: &str,
+    config: &ChunkConfig,
+) -> Vec<(TextChunk, Encoding)> {
+    tokenizer
+        .encode_windows(text, config.max_tokens, config.overlap)
+        .into_iter()
+        .enumerate()
+        .map(|(index, encoding)| {
+            let spans: Vec<(usize, usize)> = encoding
+                .offset_pairs()
+                .iter()
+                .zip(encoding.special_tokens_mask())
+                .filter(|&(_, special)| special == 0)
+                .map(|(&span, _)| span)
+                .collect();
+            let start = spans.first().map_or(0, |span| span.0);
+            let end = spans.last().map_or(0, |span| span.1);
+            let chunk = TextChunk {
+                index,
+                start,
+                end,
+                token_count: spans.len(),
+                text: text[start..end].to_string(),
+            };
+            (chunk, encoding)
+        })
+        .collect()
+}
+
+/// Pooled embedding of a long text together with its chunk embeddings
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentEmbedding {
+    embedding: Vec<f32>,
+    chunks: Vec<TextChunk>,
+    chunk_embeddings: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl DocumentEmbedding {
+    /// Document vector pooled from the chunk embeddings
+    pub fn embedding(&self) -> Vec<f32> {
+        self.embedding.clone()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn chunk(&self, index: usize) -> Option<TextChunk> {
+        self.chunks.get(index).cloned()
+    }
+
+    /// Embedding of one chunk
+    pub fn chunk_embedding(&self, index: usize) -> Option<Vec<f32>> {
+        let dimension = self.embedding.len();
+        self.chunk_embeddings
+            .get(index * dimension..(index + 1) * dimension)
+            .map(<[f32]>::to_vec)
+    }
+}
+
+impl DocumentEmbedding {
+    pub(crate) fn new(
+        embedding: Vec<f32>,
+        chunks: Vec<TextChunk>,
+        chunk_embeddings: Vec<f32>,
+    ) -> Self {
+        DocumentEmbedding {
+            embedding,
+            chunks,
+            chunk_embeddings,
+        }
+    }
+
+    pub fn chunks(&self) -> &[TextChunk] {
+        &self.chunks
+    }
+}
+
+/// A document (and optionally the passage within it) matching a query
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentMatch {
+    document_id: String,
+    passage: Option<TextChunk>,
+    score: f32,
+}
+
+#[wasm_bindgen]
+impl DocumentMatch {
+    #[wasm_bindgen(getter)]
+    pub fn document_id(&self) -> String {
+        self.document_id.clone()
+    }
+
+    /// Best matching chunk, for passage searches
+    #[wasm_bindgen(getter)]
+    pub fn passage(&self) -> Option<TextChunk> {
+        self.passage.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+}
+
+/// Index of pooled document embeddings, optionally with one entry per chunk
+#[wasm_bindgen]
+pub struct DocumentIndex {
+    documents: VectorIndex,
+    document_ids: Vec<String>,
+    chunks: Option<VectorIndex>,
+    /// Parent document position and span of every indexed chunk
+    chunk_parents: Vec<(usize, TextChunk)>,
+}
+
+#[wasm_bindgen]
+impl DocumentIndex {
+    /// Create an index; with `index_chunks` every chunk is searchable as a passage
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimension: usize, metric: DistanceMetric, index_chunks: bool) -> Self {
+        DocumentIndex {
+            documents: VectorIndex::new(dimension, metric),
+            document_ids: Vec::new(),
+            chunks: index_chunks.then(|| VectorIndex::new(dimension, metric)),
+            chunk_parents: Vec::new(),
+        }
+    }
+
+    /// Add a document under `id`
+    ///
+    /// A document whose vectors do not fit the index leaves it unchanged.
+    pub fn add_document(&mut self, id: &str, document: &DocumentEmbedding) -> Result<usize, Error> {
+        // The chunk batch is checked as a whole before any chunk is added, so
+        // checking the document vector first makes the whole add all-or-nothing
+        Error::check_dimension(
+            "add_document",
+            self.documents.dimension(),
+            document.embedding.len(),
+        )?;
+        let position = self.documents.size();
+        if let Some(chunks) = &mut self.chunks {
+            chunks.add_vectors_batch(&document.chunk_embeddings, document.chunks.len())?;
+            self.chunk_parents.extend(
+                document
+                    .chunks
+                    .iter()
+                    .map(|chunk| (position, chunk.clone())),
+            );
+        }
+
+        self.documents.add_vector(&document.embedding)?;
+        self.document_ids.push(id.to_string());
+        Ok(position)
+    }
+
+    /// Number of documents
+    pub fn size(&self) -> usize {
+        self.documents.size()
+    }
+
+    /// Number of indexed chunks (zero unless chunk indexing is enabled)
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_parents.len()
+    }
+
+    /// Find the `k` documents whose pooled embedding best matches `query`
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<DocumentMatch>, Error> {
+        Ok(self
+            .documents
+            .search(query, k)?
+            .into_iter()
+            .map(|result| DocumentMatch {
+                document_id: self.document_ids[result.id].clone(),
+                passage: None,
+                score: result.score,
+            })
+            .collect())
+    }
+
+    /// Find the `k` documents with the best matching chunk, returning that chunk
+    ///
+    /// Falls back to [`search`](Self::search) when chunks are not indexed.
+    pub fn search_passages(&self, query: &[f32], k: usize) -> Result<Vec<DocumentMatch>, Error> {
+        let Some(chunks) = &self.chunks else {
+            return self.search(query, k);
+        };
+
+        let mut seen = vec![false; self.document_ids.len()];
+        let mut matches = Vec::with_capacity(k);
+        for result in chunks.search(query, chunks.size())? {
+            let (document, chunk) = &self.chunk_parents[result.id];
+            if std::mem::replace(&mut seen[*document], true) {
+                continue;
+            }
+            matches.push(DocumentMatch {
+                document_id: self.document_ids[*document].clone(),
+                passage: Some(chunk.clone()),
+                score: result.score,
+            });
+            if matches.len() == k {
+                break;
+            }
+        }
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(chunks: &[(&str, [f32; 2])]) -> DocumentEmbedding {
+        let chunks_meta = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, (text, _))| TextChunk {
+                index,
+                start: 0,
+                end: text.len(),
+                token_count: 1,
+                text: text.to_string(),
+            })
+            .collect();
+        let embeddings: Vec<f32> = chunks.iter().flat_map(|(_, e)| *e).collect();
+        let mut pooled = crate::embeddings::compute_centroid(&embeddings, 2).unwrap();
+        crate::vector_search::normalize_vector(&mut pooled);
+        DocumentEmbedding::new(pooled, chunks_meta, embeddings)
+    }
+
+    #[test]
+    fn test_passage_search_returns_best_chunk_per_document() {
+        let mut index = DocumentIndex::new(2, DistanceMetric::Cosine, true);
+        index
+            .add_document(
+                "heist",
+                &document(&[("vault", [1.0, 0.0]), ("escape", [0.0, 1.0])]),
+            )
+            .unwrap();
+        index
+            .add_document(
+                "drama",
+                &document(&[("family", [0.6, 0.8]), ("dinner", [0.8, 0.6])]),
+            )
+            .unwrap();
+        assert_eq!(index.chunk_count(), 4);
+
+        let passages = index.search_passages(&[0.0, 1.0], 2).unwrap();
+        assert_eq!(passages.len(), 2);
+        assert_eq!(passages[0].document_id(), "heist");
+        assert_eq!(passages[0].passage().unwrap().text(), "escape");
+        assert_eq!(passages[1].passage().unwrap().text(), "family");
+
+        let documents = index.search(&[0.0, 1.0], 1).unwrap();
+        assert!(documents[0].passage().is_none());
+    }
+
+    #[test]
+    fn test_invalid_document_leaves_index_unchanged() {
+        let mut index = DocumentIndex::new(2, DistanceMetric::Cosine, true);
+        let mut bad_chunks = document(&[("vault", [1.0, 0.0])]);
+        bad_chunks.chunk_embeddings.push(0.5);
+        let mut bad_pooled = document(&[("vault", [1.0, 0.0])]);
+        bad_pooled.embedding.push(0.5);
+
+        for bad in [&bad_chunks, &bad_pooled] {
+            assert!(index.add_document("heist", bad).is_err());
+            assert_eq!((index.size(), index.chunk_count()), (0, 0));
+        }
+        index
+            .add_document("heist", &document(&[("vault", [1.0, 0.0])]))
+            .unwrap();
+        assert_eq!((index.size(), index.chunk_count()), (1, 1));
+    }
+}
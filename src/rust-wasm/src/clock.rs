@@ -0,0 +1,104 @@
+//! Monotonic clocks for timing searches
+//!
+//! [`SystemClock`] reads `globalThis.performance` under WASM, which exists on
+//! the browser main thread, in Web Workers and in Node, and falls back to
+//! `Date.now()` elsewhere. Native builds use `std::time::Instant`. Tests inject
+//! a [`FakeClock`] to get deterministic timings.
+
+use std::cell::Cell;
+
+/// A source of monotonic time in milliseconds
+pub trait Clock {
+    /// Milliseconds since an arbitrary, fixed origin
+    fn now_ms(&self) -> f64;
+}
+
+/// The best clock available on the current platform
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        use wasm_bindgen::{JsCast, JsValue};
+
+        let performance =
+            js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
+                .ok()
+                .filter(|p| p.is_object());
+        let now = performance.and_then(|performance| {
+            let now = js_sys::Reflect::get(&performance, &JsValue::from_str("now")).ok()?;
+            now.dyn_into::<js_sys::Function>()
+                .ok()?
+                .call0(&performance)
+                .ok()?
+                .as_f64()
+        });
+        now.unwrap_or_else(js_sys::Date::now)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        use std::sync::OnceLock;
+        use std::time::Instant;
+
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+/// Manually driven clock for tests
+///
+/// Every reading advances the time by `step` milliseconds, so an operation
+/// timed with two readings always takes exactly `step`.
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    now: Cell<f64>,
+    step: f64,
+}
+
+impl FakeClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A clock that advances by `step` milliseconds on each reading
+    pub fn with_step(step: f64) -> Self {
+        FakeClock {
+            now: Cell::new(0.0),
+            step,
+        }
+    }
+
+    /// Move the clock forward by `ms` milliseconds
+    pub fn advance(&self, ms: f64) {
+        self.now.set(self.now.get() + ms);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now_ms(&self) -> f64 {
+        let now = self.now.get();
+        self.now.set(now + self.step);
+        now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clocks() {
+        let fake = FakeClock::with_step(2.5);
+        assert_eq!(fake.now_ms(), 0.0);
+        fake.advance(10.0);
+        assert_eq!(fake.now_ms(), 12.5);
+
+        let system = SystemClock;
+        let start = system.now_ms();
+        assert!(system.now_ms() >= start);
+    }
+}
@@ -0,0 +1,633 @@
+//! K-means clustering of embeddings
+//!
+//! k-means++ seeding followed by either full Lloyd iterations or mini-batch
+//! updates, under any [`DistanceMetric`]. Used to build genre "shelves" and to
+//! train coarse quantizers for partitioned indexes.
+//!
+//! Cosine and dot product clustering keep unit-length centroids (spherical
+//! k-means): with unnormalized means, the longest centroid would have the
+//! largest dot product with almost every vector and absorb the other clusters.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::utils::SeededRng;
+use crate::vector_search::{
+    dot_product, euclidean_distance, manhattan_distance, normalize_vector, DistanceMetric,
+};
+
+/// Consecutive small-shift batches needed to stop mini-batch training early
+const STABLE_BATCHES: usize = 5;
+
+/// Parameters of a k-means run
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KMeansConfig {
+    k: usize,
+    metric: DistanceMetric,
+    max_iterations: usize,
+    tolerance: f32,
+    batch_size: Option<usize>,
+    silhouette_sample: usize,
+    seed: u64,
+}
+
+#[wasm_bindgen]
+impl KMeansConfig {
+    /// `k` clusters under `metric`, using full Lloyd iterations
+    #[wasm_bindgen(constructor)]
+    pub fn new(k: usize, metric: DistanceMetric) -> Self {
+        KMeansConfig {
+            k,
+            metric,
+            max_iterations: 100,
+            tolerance: 1e-4,
+            batch_size: None,
+            silhouette_sample: 1000,
+            seed: 42,
+        }
+    }
+
+    /// Maximum number of Lloyd iterations, or of mini-batches
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations.max(1);
+    }
+
+    /// Stop once no centroid moves further than this
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance.max(0.0);
+    }
+
+    /// Switch to mini-batch k-means with batches of `batch_size` vectors
+    pub fn set_mini_batch(&mut self, batch_size: usize) {
+        self.batch_size = Some(batch_size.max(1));
+    }
+
+    /// Number of vectors sampled for the silhouette estimate (0 disables it)
+    pub fn set_silhouette_sample(&mut self, sample: usize) {
+        self.silhouette_sample = sample;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+}
+
+/// A fitted clustering: centroids plus the assignment of every input vector
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KMeans {
+    metric: DistanceMetric,
+    dimension: usize,
+    centroids: Vec<f32>,
+    assignments: Vec<u32>,
+    inertia: f32,
+    silhouette: f32,
+    iterations: usize,
+}
+
+#[wasm_bindgen]
+impl KMeans {
+    /// Cluster a flat buffer of embeddings
+    pub fn fit(
+        embeddings: &[f32],
+        dimension: usize,
+        config: &KMeansConfig,
+    ) -> Result<KMeans, Error> {
+        Error::check_batch("KMeans::fit", embeddings, dimension)?;
+        let count = embeddings.len() / dimension;
+        if config.k == 0 || config.k > count {
+            return Err(Error::InvalidArgument {
+                operation: "KMeans::fit",
+                message: format!("cannot form {} clusters from {} vectors", config.k, count),
+            });
+        }
+
+        let data = if config.metric == DistanceMetric::Cosine {
+            let mut data = embeddings.to_vec();
+            crate::vector_search::normalize_vectors_batch(&mut data, dimension);
+            data
+        } else {
+            embeddings.to_vec()
+        };
+
+        let mut rng = SeededRng::new(config.seed);
+        let mut centroids = seed_plus_plus(&data, dimension, config.k, config.metric, &mut rng);
+        if unit_centroids(config.metric) {
+            crate::vector_search::normalize_vectors_batch(&mut centroids, dimension);
+        }
+        let mut model = KMeans {
+            metric: config.metric,
+            dimension,
+            centroids,
+            assignments: vec![0; count],
+            inertia: 0.0,
+            silhouette: 0.0,
+            iterations: 0,
+        };
+
+        match config.batch_size {
+            Some(batch_size) => model.mini_batch(&data, config, batch_size, &mut rng),
+            None => model.lloyd(&data, config),
+        }
+        model.inertia = model.assign_all(&data);
+        model.silhouette = model.silhouette_estimate(&data, config.silhouette_sample, &mut rng);
+        Ok(model)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn k(&self) -> usize {
+        self.centroids.len() / self.dimension
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Flat buffer of `k * dimension` centroid values
+    pub fn centroids(&self) -> Vec<f32> {
+        self.centroids.clone()
+    }
+
+    /// Cluster index of every input vector, in input order
+    pub fn assignments(&self) -> Vec<u32> {
+        self.assignments.clone()
+    }
+
+    /// Sum of each vector's distance to its centroid
+    ///
+    /// Squared L2 for Euclidean, `1 - cos` for Cosine, L1 for Manhattan and the
+    /// negated dot product with the unit centroid for DotProduct, so lower is
+    /// always tighter.
+    #[wasm_bindgen(getter)]
+    pub fn inertia(&self) -> f32 {
+        self.inertia
+    }
+
+    /// Mean silhouette coefficient over a sample of the input, in [-1, 1]
+    #[wasm_bindgen(getter)]
+    pub fn silhouette(&self) -> f32 {
+        self.silhouette
+    }
+
+    /// Number of Lloyd iterations or mini-batches that ran
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Cluster index of the centroid nearest to `vector`
+    pub fn predict(&self, vector: &[f32]) -> Result<usize, Error> {
+        Error::check_dimension("KMeans::predict", self.dimension, vector.len())?;
+        let mut vector = vector.to_vec();
+        if self.metric == DistanceMetric::Cosine {
+            normalize_vector(&mut vector);
+        }
+        Ok(self.nearest(&vector).0)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("k-means model", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<KMeans, Error> {
+        let model: KMeans = serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        model
+            .validate()
+            .map_err(|e| Error::parse("k-means model", e))?;
+        Ok(model)
+    }
+}
+
+impl KMeans {
+    /// Check that a deserialized model is consistent
+    fn validate(&self) -> Result<(), String> {
+        if self.dimension == 0 {
+            return Err("dimension must be positive".to_string());
+        }
+        if self.centroids.is_empty() || !self.centroids.len().is_multiple_of(self.dimension) {
+            return Err(format!(
+                "{} centroid values do not form whole centroids of dimension {}",
+                self.centroids.len(),
+                self.dimension
+            ));
+        }
+        match self.assignments.iter().find(|&&a| a as usize >= self.k()) {
+            Some(a) => Err(format!(
+                "assignment {} is not one of {} clusters",
+                a,
+                self.k()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub fn centroid(&self, cluster: usize) -> &[f32] {
+        &self.centroids[cluster * self.dimension..(cluster + 1) * self.dimension]
+    }
+
+    /// Nearest centroid to an already prepared vector, with its distance
+    fn nearest(&self, vector: &[f32]) -> (usize, f32) {
+        let mut best = (0, f32::INFINITY);
+        for (cluster, centroid) in self.centroids.chunks_exact(self.dimension).enumerate() {
+            let d = distance(self.metric, vector, centroid);
+            if d < best.1 {
+                best = (cluster, d);
+            }
+        }
+        best
+    }
+
+    /// Assign every vector to its nearest centroid, returning the inertia
+    fn assign_all(&mut self, data: &[f32]) -> f32 {
+        let mut inertia = 0.0;
+        for (i, vector) in data.chunks_exact(self.dimension).enumerate() {
+            let (cluster, d) = self.nearest(vector);
+            self.assignments[i] = cluster as u32;
+            inertia += d;
+        }
+        inertia
+    }
+
+    fn lloyd(&mut self, data: &[f32], config: &KMeansConfig) {
+        let dimension = self.dimension;
+        let k = self.k();
+        for _ in 0..config.max_iterations {
+            self.iterations += 1;
+            self.assign_all(data);
+
+            let mut members: Vec<Vec<&[f32]>> = vec![Vec::new(); k];
+            for (vector, &cluster) in data.chunks_exact(dimension).zip(&self.assignments) {
+                members[cluster as usize].push(vector);
+            }
+            // Each empty cluster is re-seeded with a different badly served vector
+            let empty = members.iter().filter(|m| m.is_empty()).count();
+            let mut worst = self.worst_served(data, empty).into_iter();
+
+            let mut updated = vec![0.0f32; k * dimension];
+            for (cluster, members) in members.iter().enumerate() {
+                let target = &mut updated[cluster * dimension..(cluster + 1) * dimension];
+                if members.is_empty() {
+                    let i = worst.next().unwrap_or(0);
+                    target.copy_from_slice(&data[i * dimension..(i + 1) * dimension]);
+                    if unit_centroids(self.metric) {
+                        normalize_vector(target);
+                    }
+                } else {
+                    self.update_centroid(target, members);
+                }
+            }
+
+            let shift = self
+                .centroids
+                .chunks_exact(dimension)
+                .zip(updated.chunks_exact(dimension))
+                .map(|(old, new)| euclidean_distance(old, new))
+                .fold(0.0f32, f32::max);
+            self.centroids = updated;
+            if shift <= config.tolerance {
+                break;
+            }
+        }
+    }
+
+    /// Mini-batch k-means with per-centroid learning rates (Sculley, 2010)
+    ///
+    /// A single batch can move little by chance, so training stops only after
+    /// [`STABLE_BATCHES`] consecutive batches shift no centroid beyond the tolerance.
+    fn mini_batch(
+        &mut self,
+        data: &[f32],
+        config: &KMeansConfig,
+        batch_size: usize,
+        rng: &mut SeededRng,
+    ) {
+        let dimension = self.dimension;
+        let count = data.len() / dimension;
+        let mut seen = vec![0usize; self.k()];
+        let mut stable = 0;
+
+        for _ in 0..config.max_iterations {
+            self.iterations += 1;
+            let batch = rng.sample_indices(count, batch_size);
+            let nearest: Vec<usize> = batch
+                .iter()
+                .map(|&i| self.nearest(&data[i * dimension..(i + 1) * dimension]).0)
+                .collect();
+
+            let mut shift = 0.0f32;
+            for (&i, &cluster) in batch.iter().zip(&nearest) {
+                seen[cluster] += 1;
+                let rate = 1.0 / seen[cluster] as f32;
+                let vector = &data[i * dimension..(i + 1) * dimension];
+                let centroid = &mut self.centroids[cluster * dimension..(cluster + 1) * dimension];
+                let before = centroid.to_vec();
+                for (c, x) in centroid.iter_mut().zip(vector) {
+                    *c += rate * (x - *c);
+                }
+                if unit_centroids(self.metric) {
+                    normalize_vector(centroid);
+                }
+                shift = shift.max(euclidean_distance(&before, centroid));
+            }
+            stable = if shift <= config.tolerance {
+                stable + 1
+            } else {
+                0
+            };
+            if stable >= STABLE_BATCHES {
+                break;
+            }
+        }
+    }
+
+    /// Mean for L2, normalized mean for cosine and dot product, median for L1
+    fn update_centroid(&self, target: &mut [f32], members: &[&[f32]]) {
+        match self.metric {
+            DistanceMetric::Manhattan => {
+                let mut column = Vec::with_capacity(members.len());
+                for (j, value) in target.iter_mut().enumerate() {
+                    column.clear();
+                    column.extend(members.iter().map(|m| m[j]));
+                    column.sort_by(|a, b| a.total_cmp(b));
+                    *value = column[column.len() / 2];
+                }
+            }
+            _ => {
+                for member in members {
+                    for (c, x) in target.iter_mut().zip(member.iter()) {
+                        *c += x;
+                    }
+                }
+                let n = members.len() as f32;
+                target.iter_mut().for_each(|c| *c /= n);
+                if unit_centroids(self.metric) {
+                    normalize_vector(target);
+                }
+            }
+        }
+    }
+
+    /// The `count` vectors farthest from their assigned centroids, farthest first
+    fn worst_served(&self, data: &[f32], count: usize) -> Vec<usize> {
+        if count == 0 {
+            return Vec::new();
+        }
+        let mut served: Vec<(usize, f32)> = data
+            .chunks_exact(self.dimension)
+            .zip(&self.assignments)
+            .map(|(v, &a)| distance(self.metric, v, self.centroid(a as usize)))
+            .enumerate()
+            .collect();
+        served.sort_by(|a, b| b.1.total_cmp(&a.1));
+        served.into_iter().take(count).map(|(i, _)| i).collect()
+    }
+
+    /// Silhouette over a seeded sample, with distances taken within the sample
+    fn silhouette_estimate(&self, data: &[f32], sample: usize, rng: &mut SeededRng) -> f32 {
+        let k = self.k();
+        if k < 2 || sample == 0 {
+            return 0.0;
+        }
+        let dimension = self.dimension;
+        let indices = rng.sample_indices(data.len() / dimension, sample);
+        let vector = |i: usize| &data[i * dimension..(i + 1) * dimension];
+
+        let mut total = 0.0f32;
+        for &i in &indices {
+            let mut sums = vec![0.0f32; k];
+            let mut counts = vec![0usize; k];
+            for &j in &indices {
+                if i != j {
+                    let cluster = self.assignments[j] as usize;
+                    sums[cluster] += silhouette_distance(self.metric, vector(i), vector(j));
+                    counts[cluster] += 1;
+                }
+            }
+
+            let own = self.assignments[i] as usize;
+            if counts[own] == 0 {
+                continue; // singleton clusters score 0
+            }
+            let a = sums[own] / counts[own] as f32;
+            let b = (0..k)
+                .filter(|&c| c != own && counts[c] > 0)
+                .map(|c| sums[c] / counts[c] as f32)
+                .fold(f32::INFINITY, f32::min);
+            if b.is_finite() && a.max(b) > 0.0 {
+                total += (b - a) / a.max(b);
+            }
+        }
+        total / indices.len() as f32
+    }
+}
+
+/// Whether centroids are kept at unit length (spherical k-means)
+fn unit_centroids(metric: DistanceMetric) -> bool {
+    matches!(metric, DistanceMetric::Cosine | DistanceMetric::DotProduct)
+}
+
+/// Dissimilarity minimized by the clustering (lower is closer)
+fn distance(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => 1.0 - dot_product(a, b),
+        DistanceMetric::Euclidean => {
+            let d = euclidean_distance(a, b);
+            d * d
+        }
+        DistanceMetric::Manhattan => manhattan_distance(a, b),
+        DistanceMetric::DotProduct => -dot_product(a, b),
+    }
+}
+
+/// Non-negative distance for the silhouette; dot product falls back to L2
+fn silhouette_distance(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    match metric {
+        DistanceMetric::Cosine => (1.0 - dot_product(a, b)).max(0.0),
+        DistanceMetric::Manhattan => manhattan_distance(a, b),
+        DistanceMetric::Euclidean | DistanceMetric::DotProduct => euclidean_distance(a, b),
+    }
+}
+
+/// k-means++ seeding: each new centroid is drawn with probability
+/// proportional to its squared distance from the nearest chosen one
+fn seed_plus_plus(
+    data: &[f32],
+    dimension: usize,
+    k: usize,
+    metric: DistanceMetric,
+    rng: &mut SeededRng,
+) -> Vec<f32> {
+    let count = data.len() / dimension;
+    let vector = |i: usize| &data[i * dimension..(i + 1) * dimension];
+    let weight = |a: &[f32], b: &[f32]| -> f64 {
+        let d = match metric {
+            DistanceMetric::DotProduct => euclidean_distance(a, b),
+            _ => silhouette_distance(metric, a, b),
+        } as f64;
+        d * d
+    };
+
+    let first = rng.below(count);
+    let mut centroids = vector(first).to_vec();
+    let mut closest: Vec<f64> = (0..count)
+        .map(|i| weight(vector(i), vector(first)))
+        .collect();
+
+    while centroids.len() < k * dimension {
+        let total: f64 = closest.iter().sum();
+        let chosen = if total > 0.0 {
+            let mut target = rng.next_f64() * total;
+            closest
+                .iter()
+                .position(|&w| {
+                    target -= w;
+                    target < 0.0
+                })
+                .unwrap_or(count - 1)
+        } else {
+            // All remaining vectors coincide with a centroid
+            rng.below(count)
+        };
+        centroids.extend_from_slice(vector(chosen));
+        for (i, best) in closest.iter_mut().enumerate() {
+            *best = best.min(weight(vector(i), vector(chosen)));
+        }
+    }
+    centroids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three tight blobs in 2D, separated both in distance and in angle
+    fn blobs() -> Vec<f32> {
+        let mut rng = SeededRng::new(7);
+        let mut data = Vec::new();
+        for center in [[10.0f32, 0.0], [7.0, 7.0], [0.0, 10.0]] {
+            for _ in 0..30 {
+                data.push(center[0] + rng.normal() as f32 * 0.3);
+                data.push(center[1] + rng.normal() as f32 * 0.3);
+            }
+        }
+        data
+    }
+
+    fn is_recovered(model: &KMeans) {
+        let assignments = model.assignments();
+        for blob in assignments.chunks(30) {
+            assert!(blob.iter().all(|&a| a == blob[0]));
+        }
+        let mut firsts: Vec<u32> = assignments.chunks(30).map(|b| b[0]).collect();
+        firsts.sort();
+        firsts.dedup();
+        assert_eq!(firsts.len(), 3);
+    }
+
+    #[test]
+    fn test_lloyd_recovers_blobs() {
+        let data = blobs();
+        for metric in [
+            DistanceMetric::Euclidean,
+            DistanceMetric::Manhattan,
+            DistanceMetric::Cosine,
+            DistanceMetric::DotProduct,
+        ] {
+            let model = KMeans::fit(&data, 2, &KMeansConfig::new(3, metric)).unwrap();
+            is_recovered(&model);
+            assert!(
+                model.silhouette() > 0.5,
+                "{:?}: {}",
+                metric,
+                model.silhouette()
+            );
+            assert_eq!(
+                model.predict(&[9.5, 0.5]).unwrap(),
+                model.assignments()[0] as usize
+            );
+        }
+
+        let model =
+            KMeans::fit(&data, 2, &KMeansConfig::new(3, DistanceMetric::Euclidean)).unwrap();
+        // 90 points with per-axis variance 0.09
+        assert!(model.inertia() < 90.0 * 2.0 * 0.09 * 2.0);
+        let restored = KMeans::from_json(&model.to_json().unwrap()).unwrap();
+        assert_eq!(restored.centroids(), model.centroids());
+
+        for broken in [
+            KMeans {
+                dimension: 0,
+                ..model.clone()
+            },
+            KMeans {
+                centroids: model.centroids[..5].to_vec(),
+                ..model.clone()
+            },
+            KMeans {
+                assignments: vec![3],
+                ..model.clone()
+            },
+        ] {
+            let Err(err) = KMeans::from_json(&broken.to_json().unwrap()) else {
+                panic!("an inconsistent model was restored");
+            };
+            assert_eq!(err.code(), "PARSE_ERROR");
+        }
+    }
+
+    #[test]
+    fn test_empty_clusters_get_distinct_seeds() {
+        // Both far centroids lose every vector to the first one
+        let data = [0.0, 0.0, 1.0, 0.0, 10.0, 0.0, 11.0, 0.0];
+        let mut model = KMeans {
+            metric: DistanceMetric::Euclidean,
+            dimension: 2,
+            centroids: vec![0.5, 0.0, 100.0, 100.0, 200.0, 200.0],
+            assignments: vec![0; 4],
+            inertia: 0.0,
+            silhouette: 0.0,
+            iterations: 0,
+        };
+        let mut config = KMeansConfig::new(3, DistanceMetric::Euclidean);
+        config.set_max_iterations(1);
+        model.lloyd(&data, &config);
+        assert_eq!(model.centroid(1), &[11.0, 0.0]);
+        assert_eq!(model.centroid(2), &[10.0, 0.0]);
+    }
+
+    #[test]
+    fn test_mini_batch_is_seeded() {
+        let data = blobs();
+        let mut config = KMeansConfig::new(3, DistanceMetric::Euclidean);
+        config.set_mini_batch(16);
+        config.set_max_iterations(50);
+
+        let a = KMeans::fit(&data, 2, &config).unwrap();
+        let b = KMeans::fit(&data, 2, &config).unwrap();
+        is_recovered(&a);
+        assert_eq!(a.centroids(), b.centroids());
+
+        // One quiet batch is not enough to stop
+        config.set_tolerance(f32::INFINITY);
+        let quiet = KMeans::fit(&data, 2, &config).unwrap();
+        assert_eq!(quiet.iterations(), STABLE_BATCHES);
+
+        // Dot product centroids stay unit length, so no cluster absorbs the rest
+        let mut config = KMeansConfig::new(3, DistanceMetric::DotProduct);
+        config.set_mini_batch(16);
+        let dot = KMeans::fit(&data, 2, &config).unwrap();
+        is_recovered(&dot);
+        for cluster in 0..3 {
+            let norm = dot_product(dot.centroid(cluster), dot.centroid(cluster)).sqrt();
+            assert!((norm - 1.0).abs() < 1e-5, "{}", norm);
+        }
+    }
+}
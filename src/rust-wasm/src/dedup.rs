@@ -0,0 +1,362 @@
+//! Near-duplicate detection for items aggregated from several catalog sources
+//!
+//! Pairs of items whose similarity clears a threshold are linked, and the
+//! connected components of those links become duplicate groups. Similarity is
+//! either the vector score alone or a weighted blend with normalized-title and
+//! release-year agreement.
+
+use serde::{Deserialize, Serialize};
+use unicode_categories::UnicodeCategories;
+use unicode_normalization::UnicodeNormalization;
+use wasm_bindgen::prelude::*;
+
+use crate::vector_search::{
+    dot_product, euclidean_distance, manhattan_distance, DistanceMetric, VectorIndex,
+};
+
+/// Leading (or comma-separated trailing) articles dropped when normalizing titles
+const TITLE_ARTICLES: &[&str] = &["the", "a", "an"];
+
+/// Title and release year of indexed items, keyed by index ID
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogMetadata {
+    titles: Vec<Option<String>>,
+    years: Vec<Option<u32>>,
+}
+
+#[wasm_bindgen]
+impl CatalogMetadata {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the metadata of the item with index ID `id`
+    pub fn set(&mut self, id: usize, title: Option<String>, year: Option<u32>) {
+        if id >= self.titles.len() {
+            self.titles.resize(id + 1, None);
+            self.years.resize(id + 1, None);
+        }
+        self.titles[id] = title.map(|t| normalize_title(&t));
+        self.years[id] = year;
+    }
+}
+
+impl CatalogMetadata {
+    fn title(&self, id: usize) -> Option<&str> {
+        self.titles.get(id).and_then(|t| t.as_deref())
+    }
+
+    fn year(&self, id: usize) -> Option<u32> {
+        self.years.get(id).copied().flatten()
+    }
+
+    /// Number of metadata signals known for an item
+    fn completeness(&self, id: usize) -> usize {
+        self.title(id).is_some() as usize + self.year(id).is_some() as usize
+    }
+}
+
+/// How vector, title and year similarity are blended
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateConfig {
+    threshold: f32,
+    vector_weight: f32,
+    title_weight: f32,
+    year_weight: f32,
+    year_tolerance: u32,
+}
+
+#[wasm_bindgen]
+impl DuplicateConfig {
+    /// Link items whose blended similarity is at least `threshold`
+    #[wasm_bindgen(constructor)]
+    pub fn new(threshold: f32) -> Self {
+        DuplicateConfig {
+            threshold,
+            vector_weight: 0.6,
+            title_weight: 0.3,
+            year_weight: 0.1,
+            year_tolerance: 1,
+        }
+    }
+
+    /// Relative weights of the three signals; signals missing on either item are left out
+    pub fn set_weights(&mut self, vector: f32, title: f32, year: f32) {
+        self.vector_weight = vector.max(0.0);
+        self.title_weight = title.max(0.0);
+        self.year_weight = year.max(0.0);
+    }
+
+    /// Release years this far apart still agree (providers disagree on festival vs release dates)
+    pub fn set_year_tolerance(&mut self, years: u32) {
+        self.year_tolerance = years;
+    }
+}
+
+/// A set of items judged to be the same title
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    members: Vec<usize>,
+    canonical: usize,
+    min_similarity: f32,
+}
+
+#[wasm_bindgen]
+impl DuplicateGroup {
+    /// Index IDs of every member, ascending
+    pub fn members(&self) -> Vec<usize> {
+        self.members.clone()
+    }
+
+    /// Suggested member to keep when merging
+    ///
+    /// The member with the most complete metadata, then the highest total
+    /// similarity to the rest of the group, then the lowest ID.
+    #[wasm_bindgen(getter)]
+    pub fn canonical(&self) -> usize {
+        self.canonical
+    }
+
+    /// Weakest link that joined this group
+    #[wasm_bindgen(getter)]
+    pub fn min_similarity(&self) -> f32 {
+        self.min_similarity
+    }
+}
+
+/// Group the items of `index` linked by a similarity of at least the threshold
+pub(crate) fn find_groups(
+    index: &VectorIndex,
+    metadata: Option<&CatalogMetadata>,
+    config: &DuplicateConfig,
+) -> Vec<DuplicateGroup> {
+    let count = index.size();
+    let vector = |id: usize| index.stored(id).unwrap_or(&[]);
+
+    // Links are merged as they are found, so memory stays linear in the item
+    // count however many pairs clear the threshold
+    let mut sets = DisjointSets::new(count);
+    let mut affinity = vec![0.0f32; count];
+    for i in 0..count {
+        for j in i + 1..count {
+            let similarity = vector_similarity(index.metric(), vector(i), vector(j));
+            let score = match metadata {
+                Some(metadata) => blend(similarity, metadata, i, j, config),
+                None => similarity,
+            };
+            if score >= config.threshold {
+                sets.union(i, j, score);
+                affinity[i] += score;
+                affinity[j] += score;
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..count).map(|id| sets.find(id)).collect();
+    let mut components: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for (id, &root) in roots.iter().enumerate() {
+        components[root].push(id);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = components
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let completeness = |id: usize| metadata.map_or(0, |m| m.completeness(id));
+            let canonical = members
+                .iter()
+                .copied()
+                .max_by(|&a, &b| {
+                    completeness(a)
+                        .cmp(&completeness(b))
+                        .then(affinity[a].total_cmp(&affinity[b]))
+                        .then(b.cmp(&a))
+                })
+                .unwrap_or(members[0]);
+            DuplicateGroup {
+                min_similarity: sets.weakest[roots[members[0]]],
+                members,
+                canonical,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|group| group.members[0]);
+    groups
+}
+
+/// Vector score mapped so that higher is always more similar
+fn vector_similarity(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    match metric {
+        DistanceMetric::Cosine | DistanceMetric::DotProduct => dot_product(a, b),
+        DistanceMetric::Euclidean => 1.0 / (1.0 + euclidean_distance(a, b)),
+        DistanceMetric::Manhattan => 1.0 / (1.0 + manhattan_distance(a, b)),
+    }
+}
+
+/// Weighted mean of the signals known for both items
+fn blend(
+    similarity: f32,
+    metadata: &CatalogMetadata,
+    i: usize,
+    j: usize,
+    config: &DuplicateConfig,
+) -> f32 {
+    let mut total = config.vector_weight * similarity;
+    let mut weight = config.vector_weight;
+    if let (Some(a), Some(b)) = (metadata.title(i), metadata.title(j)) {
+        total += config.title_weight * title_similarity(a, b);
+        weight += config.title_weight;
+    }
+    if let (Some(a), Some(b)) = (metadata.year(i), metadata.year(j)) {
+        let agrees = a.abs_diff(b) <= config.year_tolerance;
+        total += config.year_weight * agrees as u8 as f32;
+        weight += config.year_weight;
+    }
+    if weight > 0.0 {
+        total / weight
+    } else {
+        similarity
+    }
+}
+
+/// Lowercase, fold accents, strip punctuation and a leading or trailing
+/// article ("The Matrix", "Matrix, The"), collapse whitespace
+pub fn normalize_title(title: &str) -> String {
+    let cleaned: String = strip_trailing_article(title)
+        .nfd()
+        .filter(|c| !c.is_mark_nonspacing())
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    let mut words: Vec<&str> = cleaned.split_whitespace().collect();
+    if words.len() > 1 && TITLE_ARTICLES.contains(&words[0]) {
+        words.remove(0);
+    }
+    words.join(" ")
+}
+
+/// Drop an article moved to the end after a comma, as catalogs list "Matrix, The"
+fn strip_trailing_article(title: &str) -> &str {
+    match title.rsplit_once(',') {
+        Some((head, tail))
+            if !head.trim().is_empty()
+                && TITLE_ARTICLES.contains(&tail.trim().to_lowercase().as_str()) =>
+        {
+            head
+        }
+        _ => title,
+    }
+}
+
+/// Jaccard overlap of the word sets of two normalized titles
+fn title_similarity(a: &str, b: &str) -> f32 {
+    if a == b {
+        return 1.0;
+    }
+    let a: std::collections::HashSet<&str> = a.split_whitespace().collect();
+    let b: std::collections::HashSet<&str> = b.split_whitespace().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Union-find with path halving and union by size
+struct DisjointSets {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+    /// Weakest link score within each set, kept at its root
+    weakest: Vec<f32>,
+}
+
+impl DisjointSets {
+    fn new(count: usize) -> Self {
+        DisjointSets {
+            parent: (0..count).collect(),
+            size: vec![1; count],
+            weakest: vec![f32::INFINITY; count],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Join the sets of `a` and `b` by a link scoring `score`
+    fn union(&mut self, a: usize, b: usize, score: f32) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            self.weakest[a] = self.weakest[a].min(score);
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        self.weakest[a] = self.weakest[a].min(self.weakest[b]).min(score);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> VectorIndex {
+        let mut index = VectorIndex::new(2, DistanceMetric::Cosine);
+        index.add_vector(&[1.0, 0.0]).unwrap(); // 0: The Matrix (provider A)
+        index.add_vector(&[0.99, 0.1]).unwrap(); // 1: Matrix, The (provider B)
+        index.add_vector(&[0.98, 0.2]).unwrap(); // 2: The Matrix Reloaded
+        index.add_vector(&[0.0, 1.0]).unwrap(); // 3: Amélie
+        index.add_vector(&[0.05, 1.0]).unwrap(); // 4: Amelie
+        index
+    }
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("The Matrix (1999)"), "matrix 1999");
+        assert_eq!(normalize_title("  Amélie!  "), "amelie");
+        assert_eq!(normalize_title("A"), "a");
+        assert_eq!(normalize_title("Matrix, The"), "matrix");
+        assert_eq!(normalize_title("Good, the Bad"), "good the bad");
+        assert_eq!(normalize_title(", An"), "an");
+    }
+
+    #[test]
+    fn test_vector_only_groups() {
+        let index = catalog();
+        let groups = index.find_near_duplicates(0.97);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].members(), vec![0, 1, 2]);
+        assert_eq!(groups[1].members(), vec![3, 4]);
+        assert!(groups[0].min_similarity() >= 0.97);
+    }
+
+    #[test]
+    fn test_metadata_splits_sequel_and_picks_canonical() {
+        let index = catalog();
+        let mut metadata = CatalogMetadata::new();
+        metadata.set(0, Some("The Matrix".into()), Some(1999));
+        metadata.set(1, Some("Matrix, The".into()), None);
+        metadata.set(2, Some("The Matrix Reloaded".into()), Some(2003));
+        metadata.set(3, Some("Amélie".into()), Some(2001));
+        metadata.set(4, Some("Amelie".into()), Some(2001));
+
+        let groups =
+            index.find_near_duplicates_with_metadata(&metadata, &DuplicateConfig::new(0.9));
+        // The sequel is close in vector space but disagrees on title and year
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].members(), vec![0, 1]);
+        assert_eq!(groups[0].canonical(), 0);
+        assert_eq!(groups[1].members(), vec![3, 4]);
+    }
+}
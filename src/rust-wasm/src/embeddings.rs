@@ -3,8 +3,12 @@
 //! Provides tools for working with vector embeddings efficiently.
 
 use serde::{Deserialize, Serialize};
-use wasm_bindgen::prelude::*;
 use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::Error;
+use crate::utils::check_len;
 
 /// Configuration for embedding generation
 #[wasm_bindgen]
@@ -13,6 +17,7 @@ pub struct EmbeddingConfig {
     dimension: usize,
     normalize: bool,
     pooling_strategy: PoolingStrategy,
+    model: Option<EmbeddingModelInfo>,
 }
 
 #[wasm_bindgen]
@@ -23,9 +28,34 @@ impl EmbeddingConfig {
             dimension,
             normalize: true,
             pooling_strategy: PoolingStrategy::Mean,
+            model: None,
         }
     }
 
+    /// Create a configuration matching a model from [`known_models`](Self::known_models)
+    pub fn for_model(name: &str) -> Result<EmbeddingConfig, Error> {
+        let info = EmbeddingModelInfo::lookup(name).ok_or_else(|| Error::NotFound {
+            kind: "embedding model",
+            name: name.to_string(),
+        })?;
+        Ok(EmbeddingConfig {
+            dimension: info.dimension,
+            normalize: info.normalize,
+            pooling_strategy: info.pooling,
+            model: Some(info),
+        })
+    }
+
+    /// Models with built-in configurations
+    pub fn known_models() -> Vec<EmbeddingModelInfo> {
+        MODEL_REGISTRY
+            .iter()
+            .map(|&(name, version, dimension)| {
+                EmbeddingModelInfo::new(name, version, dimension, true, PoolingStrategy::Mean)
+            })
+            .collect()
+    }
+
     #[wasm_bindgen(getter)]
     pub fn dimension(&self) -> usize {
         self.dimension
@@ -33,12 +63,174 @@ impl EmbeddingConfig {
 
     pub fn set_normalize(&mut self, normalize: bool) {
         self.normalize = normalize;
+        if let Some(model) = &mut self.model {
+            model.normalize = normalize;
+        }
+    }
+
+    /// The model this configuration was created for, if any
+    pub fn model_info(&self) -> Option<EmbeddingModelInfo> {
+        self.model.clone()
+    }
+}
+
+/// Built-in models as (name, version, dimension)
+const MODEL_REGISTRY: &[(&str, &str, usize)] = &[
+    ("minilm", "1", 384),
+    ("sentence_bert", "1", 768),
+    ("mpnet", "1", 768),
+    ("e5_large", "1", 1024),
+];
+
+/// Identity of the model that produced a set of embeddings
+///
+/// Two embeddings are only comparable when every field matches: models with the
+/// same dimension still produce incompatible spaces.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModelInfo {
+    name: String,
+    version: String,
+    dimension: usize,
+    normalize: bool,
+    pooling: PoolingStrategy,
+}
+
+#[wasm_bindgen]
+impl EmbeddingModelInfo {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        name: &str,
+        version: &str,
+        dimension: usize,
+        normalize: bool,
+        pooling: PoolingStrategy,
+    ) -> Self {
+        EmbeddingModelInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+            dimension,
+            normalize,
+            pooling,
+        }
+    }
+
+    /// Look up a built-in model by name
+    pub fn lookup(name: &str) -> Option<EmbeddingModelInfo> {
+        EmbeddingConfig::known_models()
+            .into_iter()
+            .find(|model| model.name == name)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn normalize(&self) -> bool {
+        self.normalize
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pooling(&self) -> PoolingStrategy {
+        self.pooling
+    }
+
+    /// Compare against the model an index was built with
+    pub fn check_compatible(
+        &self,
+        expected: &EmbeddingModelInfo,
+    ) -> Result<(), ModelMismatchError> {
+        let field = if self.name != expected.name {
+            "name"
+        } else if self.version != expected.version {
+            "version"
+        } else if self.dimension != expected.dimension {
+            "dimension"
+        } else if self.normalize != expected.normalize {
+            "normalize"
+        } else if self.pooling != expected.pooling {
+            "pooling"
+        } else {
+            return Ok(());
+        };
+
+        Err(ModelMismatchError {
+            field,
+            expected: Box::new(expected.clone()),
+            actual: Box::new(self.clone()),
+        })
+    }
+}
+
+impl std::fmt::Display for EmbeddingModelInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{} ({}d)", self.name, self.version, self.dimension)
+    }
+}
+
+/// Raised when vectors from one embedding model meet an index built with another
+///
+/// Reaches JavaScript as a `MetaMediaError` with code `MODEL_MISMATCH` whose
+/// details name the differing field and both models.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ModelMismatchError {
+    field: &'static str,
+    expected: Box<EmbeddingModelInfo>,
+    actual: Box<EmbeddingModelInfo>,
+}
+
+#[wasm_bindgen]
+impl ModelMismatchError {
+    /// First model field that differs
+    #[wasm_bindgen(getter)]
+    pub fn field(&self) -> String {
+        self.field.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn expected(&self) -> EmbeddingModelInfo {
+        (*self.expected).clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn actual(&self) -> EmbeddingModelInfo {
+        (*self.actual).clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl std::fmt::Display for ModelMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Embedding model mismatch on {}: index uses {}, got {}",
+            self.field, self.expected, self.actual
+        )
     }
 }
 
+impl std::error::Error for ModelMismatchError {}
+
 /// Pooling strategies for combining embeddings
 #[wasm_bindgen]
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PoolingStrategy {
     /// Average pooling
     Mean,
@@ -56,6 +248,7 @@ pub struct EmbeddingGenerator {
     config: EmbeddingConfig,
     cache: HashMap<String, Vec<f32>>,
     cache_enabled: bool,
+    clock: Box<dyn Clock>,
 }
 
 #[wasm_bindgen]
@@ -66,6 +259,7 @@ impl EmbeddingGenerator {
             config,
             cache: HashMap::new(),
             cache_enabled: true,
+            clock: Box::new(SystemClock),
         }
     }
 
@@ -87,16 +281,60 @@ impl EmbeddingGenerator {
         self.cache.len()
     }
 
+    /// Enable or disable normalization of pooled embeddings
+    pub fn set_normalize(&mut self, normalize: bool) {
+        self.config.set_normalize(normalize);
+    }
+
     /// Pool multiple embeddings into one
-    pub fn pool_embeddings(&self, embeddings: &[f32], count: usize) -> Result<Vec<f32>, JsValue> {
+    pub fn pool_embeddings(&self, embeddings: &[f32], count: usize) -> Result<Vec<f32>, Error> {
         if embeddings.len() != count * self.config.dimension {
-            return Err(JsValue::from_str(&format!(
-                "Invalid embeddings batch: expected {} floats, got {}",
-                count * self.config.dimension,
-                embeddings.len()
-            )));
+            return Err(Error::InvalidBatch {
+                operation: "pool_embeddings",
+                len: embeddings.len(),
+                dimension: self.config.dimension,
+            });
+        }
+
+        let start = self.clock.now_ms();
+        let pooled = self.pool(embeddings, count);
+        let elapsed = self.clock.now_ms() - start;
+        crate::metrics::with_registry(|m| m.record_latency("pool", elapsed));
+        Ok(pooled)
+    }
+}
+
+impl EmbeddingGenerator {
+    /// Replace the clock used to time pooling (e.g. with a `FakeClock` in tests)
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Look up a cached embedding
+    pub(crate) fn cached(&self, key: &str) -> Option<Vec<f32>> {
+        if !self.cache_enabled {
+            return None;
+        }
+        let hit = self.cache.get(key).cloned();
+        let counter = if hit.is_some() {
+            "cache_hits"
+        } else {
+            "cache_misses"
+        };
+        crate::metrics::with_registry(|m| m.increment(counter, 1));
+        hit
+    }
+
+    /// Store an embedding in the cache if caching is enabled
+    pub(crate) fn cache_insert(&mut self, key: &str, embedding: &[f32]) {
+        if self.cache_enabled {
+            self.cache.insert(key.to_string(), embedding.to_vec());
         }
+    }
 
+    /// Pool `count` embeddings; the caller guarantees `embeddings.len() == count * dimension`
+    pub(crate) fn pool(&self, embeddings: &[f32], count: usize) -> Vec<f32> {
         let dim = self.config.dimension;
         let mut result = vec![0.0; dim];
 
@@ -147,13 +385,15 @@ impl EmbeddingGenerator {
             crate::vector_search::normalize_vector(&mut result);
         }
 
-        Ok(result)
+        result
     }
 }
 
 /// Compute embedding statistics for analysis
 #[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingStats {
+    count: usize,
     mean: Vec<f32>,
     std_dev: Vec<f32>,
     min: Vec<f32>,
@@ -163,51 +403,27 @@ pub struct EmbeddingStats {
 #[wasm_bindgen]
 impl EmbeddingStats {
     /// Compute statistics from a batch of embeddings
-    pub fn from_batch(embeddings: &[f32], dimension: usize) -> Result<EmbeddingStats, JsValue> {
-        let count = embeddings.len() / dimension;
-        if embeddings.len() != count * dimension {
-            return Err(JsValue::from_str("Invalid embedding batch size"));
-        }
-
-        let mut mean = vec![0.0; dimension];
-        let mut min = vec![f32::INFINITY; dimension];
-        let mut max = vec![f32::NEG_INFINITY; dimension];
-
-        // Compute mean, min, max
-        for i in 0..count {
-            let start = i * dimension;
-            for j in 0..dimension {
-                let val = embeddings[start + j];
-                mean[j] += val;
-                min[j] = min[j].min(val);
-                max[j] = max[j].max(val);
-            }
-        }
-
-        for val in mean.iter_mut() {
-            *val /= count as f32;
-        }
+    ///
+    /// Fails for a zero dimension, a buffer that is not a whole number of
+    /// vectors, or an empty batch.
+    pub fn from_batch(embeddings: &[f32], dimension: usize) -> Result<EmbeddingStats, Error> {
+        Error::check_batch("EmbeddingStats::from_batch", embeddings, dimension)?;
 
-        // Compute standard deviation
-        let mut std_dev = vec![0.0; dimension];
-        for i in 0..count {
-            let start = i * dimension;
-            for j in 0..dimension {
-                let diff = embeddings[start + j] - mean[j];
-                std_dev[j] += diff * diff;
-            }
-        }
+        let mut accumulator = EmbeddingStatsAccumulator::new(dimension);
+        accumulator.update(embeddings)?;
+        accumulator.stats().ok_or(Error::EmptyInput {
+            operation: "EmbeddingStats::from_batch",
+        })
+    }
 
-        for val in std_dev.iter_mut() {
-            *val = (*val / count as f32).sqrt();
-        }
+    /// Number of embeddings the statistics were computed from
+    pub fn count(&self) -> usize {
+        self.count
+    }
 
-        Ok(EmbeddingStats {
-            mean,
-            std_dev,
-            min,
-            max,
-        })
+    /// Per-dimension population variance
+    pub fn variance(&self) -> Vec<f32> {
+        self.std_dev.iter().map(|s| s * s).collect()
     }
 
     pub fn mean(&self) -> Vec<f32> {
@@ -227,105 +443,1848 @@ impl EmbeddingStats {
     }
 }
 
-/// Reduce embedding dimensionality using PCA-like projection
+/// Streaming, mergeable accumulator for embedding statistics
+///
+/// Uses Welford's update for each vector and Chan's parallel formula to merge
+/// accumulators built by different workers, so chunks never need to be held in
+/// memory together. Co-moments are tracked for an optional subset of dimensions,
+/// and vector norms go into a bounded sketch for quantile estimates.
 #[wasm_bindgen]
-pub fn reduce_dimensions(
-    embeddings: &[f32],
-    original_dim: usize,
-    target_dim: usize,
-) -> Result<Vec<f32>, JsValue> {
-    if target_dim > original_dim {
-        return Err(JsValue::from_str("Target dimension must be less than original"));
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingStatsAccumulator {
+    dimension: usize,
+    count: usize,
+    mean: Vec<f64>,
+    m2: Vec<f64>,
+    min: Vec<f32>,
+    max: Vec<f32>,
+    covariance_dims: Vec<usize>,
+    comoment: Vec<f64>,
+    norms: NormSketch,
+}
+
+#[wasm_bindgen]
+impl EmbeddingStatsAccumulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimension: usize) -> Self {
+        EmbeddingStatsAccumulator {
+            dimension,
+            count: 0,
+            mean: vec![0.0; dimension],
+            m2: vec![0.0; dimension],
+            min: vec![f32::INFINITY; dimension],
+            max: vec![f32::NEG_INFINITY; dimension],
+            covariance_dims: Vec::new(),
+            comoment: Vec::new(),
+            norms: NormSketch::default(),
+        }
     }
 
-    let count = embeddings.len() / original_dim;
-    if embeddings.len() != count * original_dim {
-        return Err(JsValue::from_str("Invalid embeddings batch size"));
+    /// Create an accumulator that also tracks covariance between `dims`
+    pub fn with_covariance_dims(
+        dimension: usize,
+        dims: Vec<usize>,
+    ) -> Result<EmbeddingStatsAccumulator, Error> {
+        if let Some(&bad) = dims.iter().find(|&&d| d >= dimension) {
+            return Err(Error::IndexOutOfBounds {
+                operation: "with_covariance_dims",
+                index: bad,
+                len: dimension,
+            });
+        }
+
+        let mut accumulator = Self::new(dimension);
+        accumulator.comoment = vec![0.0; dims.len() * dims.len()];
+        accumulator.covariance_dims = dims;
+        Ok(accumulator)
     }
 
-    // Simple dimension reduction: take first N dimensions
-    // In production, this would use PCA or other methods
-    let mut result = Vec::with_capacity(count * target_dim);
+    /// Create an accumulator tracking covariance for `sample_size` dimensions chosen with `seed`
+    pub fn with_sampled_covariance(
+        dimension: usize,
+        sample_size: usize,
+        seed: u64,
+    ) -> EmbeddingStatsAccumulator {
+        let mut dims = crate::utils::SeededRng::new(seed).sample_indices(dimension, sample_size);
+        dims.sort_unstable();
+        let mut accumulator = Self::new(dimension);
+        accumulator.comoment = vec![0.0; dims.len() * dims.len()];
+        accumulator.covariance_dims = dims;
+        accumulator
+    }
 
-    for i in 0..count {
-        let start = i * original_dim;
-        for j in 0..target_dim {
-            result.push(embeddings[start + j]);
+    /// Add a chunk of embeddings (flat buffer of whole vectors)
+    pub fn update(&mut self, chunk: &[f32]) -> Result<(), Error> {
+        Error::check_batch("EmbeddingStatsAccumulator::update", chunk, self.dimension)?;
+
+        let k = self.covariance_dims.len();
+        let mut delta_before = vec![0.0f64; k];
+        for vector in chunk.chunks_exact(self.dimension) {
+            self.count += 1;
+            let n = self.count as f64;
+
+            for (a, &dim) in self.covariance_dims.iter().enumerate() {
+                delta_before[a] = vector[dim] as f64 - self.mean[dim];
+            }
+
+            let mut norm = 0.0f64;
+            for (j, &value) in vector.iter().enumerate() {
+                let x = value as f64;
+                let delta = x - self.mean[j];
+                self.mean[j] += delta / n;
+                self.m2[j] += delta * (x - self.mean[j]);
+                self.min[j] = self.min[j].min(value);
+                self.max[j] = self.max[j].max(value);
+                norm += x * x;
+            }
+            self.norms.record(norm.sqrt() as f32);
+
+            for (a, &delta_a) in delta_before.iter().enumerate() {
+                for (b, &dim_b) in self.covariance_dims.iter().enumerate() {
+                    let delta_after = vector[dim_b] as f64 - self.mean[dim_b];
+                    self.comoment[a * k + b] += delta_a * delta_after;
+                }
+            }
         }
+
+        Ok(())
     }
 
-    Ok(result)
+    /// Merge statistics accumulated elsewhere into this accumulator
+    pub fn merge(&mut self, other: &EmbeddingStatsAccumulator) -> Result<(), Error> {
+        if other.dimension != self.dimension || other.covariance_dims != self.covariance_dims {
+            return Err(Error::InvalidArgument {
+                operation: "EmbeddingStatsAccumulator::merge",
+                message: "accumulators have different dimensions or covariance subsets".to_string(),
+            });
+        }
+        if other.count == 0 {
+            return Ok(());
+        }
+
+        let (n_a, n_b) = (self.count as f64, other.count as f64);
+        let n = n_a + n_b;
+        let delta: Vec<f64> = self
+            .mean
+            .iter()
+            .zip(&other.mean)
+            .map(|(a, b)| b - a)
+            .collect();
+
+        for (j, &d) in delta.iter().enumerate() {
+            self.mean[j] += d * n_b / n;
+            self.m2[j] += other.m2[j] + d * d * n_a * n_b / n;
+            self.min[j] = self.min[j].min(other.min[j]);
+            self.max[j] = self.max[j].max(other.max[j]);
+        }
+
+        let k = self.covariance_dims.len();
+        for (a, &dim_a) in self.covariance_dims.iter().enumerate() {
+            for (b, &dim_b) in self.covariance_dims.iter().enumerate() {
+                self.comoment[a * k + b] +=
+                    other.comoment[a * k + b] + delta[dim_a] * delta[dim_b] * n_a * n_b / n;
+            }
+        }
+
+        self.count += other.count;
+        self.norms.merge(&other.norms);
+        Ok(())
+    }
+
+    /// Number of embeddings seen so far
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Per-dimension mean
+    pub fn mean(&self) -> Vec<f32> {
+        self.mean.iter().map(|&m| m as f32).collect()
+    }
+
+    /// Per-dimension population variance (zeros before any update)
+    pub fn variance(&self) -> Vec<f32> {
+        let n = self.count.max(1) as f64;
+        self.m2.iter().map(|&m2| (m2 / n) as f32).collect()
+    }
+
+    /// Dimensions covered by [`covariance`](Self::covariance)
+    pub fn covariance_dims(&self) -> Vec<usize> {
+        self.covariance_dims.clone()
+    }
+
+    /// Population covariance between the tracked dimensions as a row-major k×k matrix
+    pub fn covariance(&self) -> Vec<f32> {
+        let n = self.count.max(1) as f64;
+        self.comoment.iter().map(|&c| (c / n) as f32).collect()
+    }
+
+    /// Quantiles of the vector L2 norms, linearly interpolated
+    ///
+    /// Estimated from a bucketed sketch to within about 3% (0 and 1 are exact).
+    /// Each requested quantile is clamped to [0, 1]; an empty accumulator yields NaN.
+    pub fn norm_quantiles(&self, quantiles: &[f64]) -> Vec<f32> {
+        quantiles.iter().map(|&q| self.norms.quantile(q)).collect()
+    }
+
+    /// Snapshot the accumulated statistics, or `None` if no embeddings were seen
+    pub fn stats(&self) -> Option<EmbeddingStats> {
+        if self.count == 0 {
+            return None;
+        }
+
+        Some(EmbeddingStats {
+            count: self.count,
+            mean: self.mean(),
+            std_dev: self.variance().iter().map(|v| v.sqrt()).collect(),
+            min: self.min.clone(),
+            max: self.max.clone(),
+        })
+    }
+
+    /// Serialize the accumulator so it can be sent between workers
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("stats accumulator", e))
+    }
+
+    /// Restore an accumulator produced by [`to_json`](Self::to_json)
+    pub fn from_json(json: &str) -> Result<EmbeddingStatsAccumulator, Error> {
+        let accumulator: EmbeddingStatsAccumulator =
+            serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        accumulator
+            .validate()
+            .map_err(|e| Error::parse("stats accumulator", e))?;
+        Ok(accumulator)
+    }
 }
 
-/// Compute embedding centroid (mean vector)
-#[wasm_bindgen]
-pub fn compute_centroid(embeddings: &[f32], dimension: usize) -> Result<Vec<f32>, JsValue> {
-    let count = embeddings.len() / dimension;
-    if embeddings.len() != count * dimension {
-        return Err(JsValue::from_str("Invalid embeddings batch size"));
+impl EmbeddingStatsAccumulator {
+    fn validate(&self) -> Result<(), String> {
+        let dimension = self.dimension;
+        check_len("mean", self.mean.len(), dimension)?;
+        check_len("m2", self.m2.len(), dimension)?;
+        check_len("min", self.min.len(), dimension)?;
+        check_len("max", self.max.len(), dimension)?;
+        if let Some(&bad) = self.covariance_dims.iter().find(|&&d| d >= dimension) {
+            return Err(format!(
+                "covariance dimension {} is out of range for dimension {}",
+                bad, dimension
+            ));
+        }
+        let k = self.covariance_dims.len();
+        let len = k.checked_mul(k).ok_or("covariance dimensions overflow")?;
+        check_len("comoment", self.comoment.len(), len)
     }
+}
 
-    let mut centroid = vec![0.0; dimension];
+/// Norms are bucketed in millionths, so smaller norms are indistinguishable
+const NORM_SCALE: f64 = 1e6;
 
-    for i in 0..count {
-        let start = i * dimension;
-        for j in 0..dimension {
-            centroid[j] += embeddings[start + j];
+/// Bounded quantile sketch of vector norms
+///
+/// Uses the log-linear buckets of the latency
+/// [`Histogram`](crate::metrics::Histogram): memory depends on the largest
+/// norm rather than the number of vectors, and merging adds bucket counts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct NormSketch {
+    counts: Vec<u64>,
+    count: u64,
+    min: f32,
+    max: f32,
+}
+
+impl NormSketch {
+    fn record(&mut self, norm: f32) {
+        let bucket = crate::metrics::bucket_index((norm as f64 * NORM_SCALE).round() as u64);
+        if bucket >= self.counts.len() {
+            self.counts.resize(bucket + 1, 0);
         }
+        self.counts[bucket] += 1;
+        (self.min, self.max) = if self.count == 0 {
+            (norm, norm)
+        } else {
+            (self.min.min(norm), self.max.max(norm))
+        };
+        self.count += 1;
     }
 
-    for val in centroid.iter_mut() {
-        *val /= count as f32;
+    fn merge(&mut self, other: &NormSketch) {
+        if other.count == 0 {
+            return;
+        }
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (mine, theirs) in self.counts.iter_mut().zip(&other.counts) {
+            *mine += theirs;
+        }
+        (self.min, self.max) = if self.count == 0 {
+            (other.min, other.max)
+        } else {
+            (self.min.min(other.min), self.max.max(other.max))
+        };
+        self.count += other.count;
     }
 
-    Ok(centroid)
+    /// Estimate of the `rank`-th smallest norm: the middle of its bucket,
+    /// except for the exactly known extremes
+    fn value_at(&self, rank: u64) -> f32 {
+        if rank == 0 {
+            return self.min;
+        }
+        if rank + 1 >= self.count {
+            return self.max;
+        }
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen > rank {
+                let lower = crate::metrics::bucket_lower_bound(bucket) as f64;
+                let upper = crate::metrics::bucket_lower_bound(bucket + 1) as f64;
+                let middle = ((lower + upper - 1.0) / 2.0 / NORM_SCALE) as f32;
+                return middle.clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    fn quantile(&self, q: f64) -> f32 {
+        if self.count == 0 {
+            return f32::NAN;
+        }
+        let position = q.clamp(0.0, 1.0) * (self.count - 1) as f64;
+        let (lower, upper) = (position.floor() as u64, position.ceil() as u64);
+        let (low, high) = (self.value_at(lower), self.value_at(upper));
+        low + (high - low) * (position - lower as f64) as f32
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Floor applied to standard deviations and eigenvalues before inverting them
+const TRANSFORM_EPSILON: f64 = 1e-6;
 
-    #[test]
-    fn test_pool_embeddings() {
-        let config = EmbeddingConfig::new(3);
-        let generator = EmbeddingGenerator::new(config);
+/// Per-dimension z-score standardization
+///
+/// Subtracts the mean and divides by the standard deviation reported by
+/// [`EmbeddingStats`], so every dimension contributes on the same scale.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Standardizer {
+    mean: Vec<f32>,
+    std_dev: Vec<f32>,
+}
 
-        let embeddings = vec![
-            1.0, 0.0, 0.0,
-            0.0, 1.0, 0.0,
-        ];
+#[wasm_bindgen]
+impl Standardizer {
+    /// Fit a standardizer to a batch of embeddings
+    pub fn fit(embeddings: &[f32], dimension: usize) -> Result<Standardizer, Error> {
+        Ok(Self::from_stats(&EmbeddingStats::from_batch(
+            embeddings, dimension,
+        )?))
+    }
 
-        let pooled = generator.pool_embeddings(&embeddings, 2).unwrap();
-        assert_eq!(pooled.len(), 3);
+    /// Build a standardizer from precomputed statistics
+    pub fn from_stats(stats: &EmbeddingStats) -> Standardizer {
+        Standardizer {
+            mean: stats.mean.clone(),
+            std_dev: stats.std_dev.clone(),
+        }
     }
 
-    #[test]
-    fn test_embedding_stats() {
-        let embeddings = vec![
-            1.0, 2.0, 3.0,
-            2.0, 3.0, 4.0,
-            3.0, 4.0, 5.0,
-        ];
+    pub fn dimension(&self) -> usize {
+        self.mean.len()
+    }
 
-        let stats = EmbeddingStats::from_batch(&embeddings, 3).unwrap();
-        let mean = stats.mean();
+    /// Standardize a single embedding
+    pub fn transform(&self, vector: &[f32]) -> Result<Vec<f32>, Error> {
+        Error::check_dimension("transform", self.dimension(), vector.len())?;
+        Ok(self.apply(vector))
+    }
 
-        assert!((mean[0] - 2.0).abs() < 1e-6);
-        assert!((mean[1] - 3.0).abs() < 1e-6);
-        assert!((mean[2] - 4.0).abs() < 1e-6);
+    /// Standardize a flat batch of embeddings
+    pub fn transform_batch(&self, embeddings: &[f32]) -> Result<Vec<f32>, Error> {
+        Error::check_batch("transform_batch", embeddings, self.dimension())?;
+        Ok(embeddings
+            .chunks_exact(self.dimension())
+            .flat_map(|vector| self.apply(vector))
+            .collect())
     }
 
-    #[test]
-    fn test_compute_centroid() {
-        let embeddings = vec![
-            1.0, 0.0, 0.0,
-            0.0, 1.0, 0.0,
-            0.0, 0.0, 1.0,
-        ];
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("standardizer", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Standardizer, Error> {
+        let standardizer: Standardizer =
+            serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        standardizer
+            .validate()
+            .map_err(|e| Error::parse("standardizer", e))?;
+        Ok(standardizer)
+    }
+}
+
+impl Standardizer {
+    /// Check that a deserialized standardizer is consistent
+    fn validate(&self) -> Result<(), String> {
+        if self.mean.is_empty() {
+            return Err("dimension must be positive".to_string());
+        }
+        check_len("std_dev", self.std_dev.len(), self.mean.len())
+    }
+
+    /// Standardize a vector whose length is already known to match
+    pub(crate) fn apply(&self, vector: &[f32]) -> Vec<f32> {
+        vector
+            .iter()
+            .zip(self.mean.iter().zip(&self.std_dev))
+            .map(|(&x, (&mean, &std_dev))| (x - mean) / std_dev.max(TRANSFORM_EPSILON as f32))
+            .collect()
+    }
+}
+
+/// Whitening variants supported by [`Whitener`]
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WhiteningMode {
+    /// Zero-phase whitening: decorrelates while staying close to the original axes
+    Zca,
+    /// PCA whitening: rotates onto principal components, optionally truncated
+    Pca,
+}
+
+/// Whitening transform that maps embeddings to an isotropic distribution
+///
+/// Centers embeddings on the fitted mean and multiplies by `Λ^-1/2 Uᵀ` (PCA) or
+/// `U Λ^-1/2 Uᵀ` (ZCA), where `U Λ Uᵀ` is the eigendecomposition of the covariance.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Whitener {
+    mode: WhiteningMode,
+    input_dimension: usize,
+    output_dimension: usize,
+    mean: Vec<f32>,
+    /// Row-major `output_dimension × input_dimension` projection
+    matrix: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl Whitener {
+    /// Fit a whitener to a batch of embeddings
+    ///
+    /// `components` truncates PCA whitening to the leading principal components;
+    /// it is ignored for ZCA, which always keeps the full dimension.
+    pub fn fit(
+        embeddings: &[f32],
+        dimension: usize,
+        mode: WhiteningMode,
+        components: Option<usize>,
+    ) -> Result<Whitener, Error> {
+        let mut accumulator =
+            EmbeddingStatsAccumulator::with_covariance_dims(dimension, (0..dimension).collect())?;
+        accumulator.update(embeddings)?;
+        Self::from_accumulator(&accumulator, mode, components)
+    }
+
+    /// Build a whitener from an accumulator that tracked covariance for every dimension
+    pub fn from_accumulator(
+        accumulator: &EmbeddingStatsAccumulator,
+        mode: WhiteningMode,
+        components: Option<usize>,
+    ) -> Result<Whitener, Error> {
+        let dimension = accumulator.dimension();
+        if accumulator.count() == 0 {
+            return Err(Error::EmptyInput {
+                operation: "Whitener::from_accumulator",
+            });
+        }
+        if !accumulator.covariance_dims.iter().copied().eq(0..dimension) {
+            return Err(Error::InvalidArgument {
+                operation: "Whitener::from_accumulator",
+                message: "whitening requires covariance for every dimension".to_string(),
+            });
+        }
+
+        let n = accumulator.count() as f64;
+        let covariance: Vec<f64> = accumulator.comoment.iter().map(|c| c / n).collect();
+        let (eigenvalues, eigenvectors) = symmetric_eigen(covariance, dimension);
+
+        let output_dimension = match mode {
+            WhiteningMode::Zca => dimension,
+            WhiteningMode::Pca => components.unwrap_or(dimension).clamp(1, dimension),
+        };
+
+        // Rows of the PCA projection: eigenvector / sqrt(eigenvalue), largest first
+        let pca: Vec<f64> = (0..output_dimension)
+            .flat_map(|c| {
+                let scale = 1.0 / eigenvalues[c].max(TRANSFORM_EPSILON).sqrt();
+                let eigenvectors = &eigenvectors;
+                (0..dimension).map(move |j| eigenvectors[j * dimension + c] * scale)
+            })
+            .collect();
+
+        let matrix = match mode {
+            WhiteningMode::Pca => pca.iter().map(|&v| v as f32).collect(),
+            WhiteningMode::Zca => {
+                // Rotate back onto the original axes: U · (Λ^-1/2 Uᵀ)
+                let mut zca = vec![0.0f32; dimension * dimension];
+                for i in 0..dimension {
+                    for j in 0..dimension {
+                        let value: f64 = (0..dimension)
+                            .map(|c| eigenvectors[i * dimension + c] * pca[c * dimension + j])
+                            .sum();
+                        zca[i * dimension + j] = value as f32;
+                    }
+                }
+                zca
+            }
+        };
+
+        Ok(Whitener {
+            mode,
+            input_dimension: dimension,
+            output_dimension,
+            mean: accumulator.mean(),
+            matrix,
+        })
+    }
+
+    pub fn mode(&self) -> WhiteningMode {
+        self.mode
+    }
+
+    pub fn input_dimension(&self) -> usize {
+        self.input_dimension
+    }
+
+    pub fn output_dimension(&self) -> usize {
+        self.output_dimension
+    }
+
+    /// Whiten a single embedding
+    pub fn transform(&self, vector: &[f32]) -> Result<Vec<f32>, Error> {
+        Error::check_dimension("transform", self.input_dimension, vector.len())?;
+        Ok(self.apply(vector))
+    }
+
+    /// Whiten a flat batch of embeddings
+    pub fn transform_batch(&self, embeddings: &[f32]) -> Result<Vec<f32>, Error> {
+        Error::check_batch("transform_batch", embeddings, self.input_dimension)?;
+        Ok(embeddings
+            .chunks_exact(self.input_dimension)
+            .flat_map(|vector| self.apply(vector))
+            .collect())
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("whitener", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Whitener, Error> {
+        let whitener: Whitener = serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        whitener
+            .validate()
+            .map_err(|e| Error::parse("whitener", e))?;
+        Ok(whitener)
+    }
+}
+
+impl Whitener {
+    /// Check that a deserialized whitener is consistent
+    fn validate(&self) -> Result<(), String> {
+        let (input, output) = (self.input_dimension, self.output_dimension);
+        if input == 0 || output == 0 {
+            return Err("dimensions must be positive".to_string());
+        }
+        let fits = match self.mode {
+            WhiteningMode::Zca => output == input,
+            WhiteningMode::Pca => output <= input,
+        };
+        if !fits {
+            return Err(format!(
+                "{:?} whitening cannot map {} dimensions to {}",
+                self.mode, input, output
+            ));
+        }
+        check_len("mean", self.mean.len(), input)?;
+        let len = input.checked_mul(output).ok_or("dimensions overflow")?;
+        check_len("matrix", self.matrix.len(), len)
+    }
+
+    /// Whiten a vector whose length is already known to match
+    pub(crate) fn apply(&self, vector: &[f32]) -> Vec<f32> {
+        let centered: Vec<f32> = vector.iter().zip(&self.mean).map(|(x, m)| x - m).collect();
+        self.matrix
+            .chunks_exact(self.input_dimension)
+            .map(|row| row.iter().zip(&centered).map(|(w, x)| w * x).sum())
+            .collect()
+    }
+}
+
+/// A fitted transform applied to embeddings before they are indexed or queried
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EmbeddingTransform {
+    Standardize(Standardizer),
+    Whiten(Whitener),
+}
+
+impl EmbeddingTransform {
+    pub fn input_dimension(&self) -> usize {
+        match self {
+            EmbeddingTransform::Standardize(s) => s.dimension(),
+            EmbeddingTransform::Whiten(w) => w.input_dimension,
+        }
+    }
+
+    pub fn output_dimension(&self) -> usize {
+        match self {
+            EmbeddingTransform::Standardize(s) => s.dimension(),
+            EmbeddingTransform::Whiten(w) => w.output_dimension,
+        }
+    }
+
+    /// Apply the transform to a vector of `input_dimension` floats
+    pub fn apply(&self, vector: &[f32]) -> Vec<f32> {
+        match self {
+            EmbeddingTransform::Standardize(s) => s.apply(vector),
+            EmbeddingTransform::Whiten(w) => w.apply(vector),
+        }
+    }
+}
+
+/// Eigendecomposition of a symmetric row-major matrix using cyclic Jacobi rotations
+///
+/// Returns eigenvalues in descending order and the matching eigenvectors as the
+/// columns of a row-major matrix.
+pub(crate) fn symmetric_eigen(mut a: Vec<f64>, n: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut v = vec![0.0; n * n];
+    for i in 0..n {
+        v[i * n + i] = 1.0;
+    }
+
+    for _sweep in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i * n + j] * a[i * n + j])
+            .sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = a[p * n + q];
+                if apq.abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (a[q * n + q] - a[p * n + p]) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+
+                // A ← Jᵀ A J, applied as column then row rotations
+                for k in 0..n {
+                    let akp = a[k * n + p];
+                    let akq = a[k * n + q];
+                    a[k * n + p] = c * akp - s * akq;
+                    a[k * n + q] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let apk = a[p * n + k];
+                    let aqk = a[q * n + k];
+                    a[p * n + k] = c * apk - s * aqk;
+                    a[q * n + k] = s * apk + c * aqk;
+                }
+                for k in 0..n {
+                    let vkp = v[k * n + p];
+                    let vkq = v[k * n + q];
+                    v[k * n + p] = c * vkp - s * vkq;
+                    v[k * n + q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[j * n + j].total_cmp(&a[i * n + i]));
+    let eigenvalues = order.iter().map(|&i| a[i * n + i]).collect();
+    let mut eigenvectors = vec![0.0; n * n];
+    for (c, &i) in order.iter().enumerate() {
+        for k in 0..n {
+            eigenvectors[k * n + c] = v[k * n + i];
+        }
+    }
+    (eigenvalues, eigenvectors)
+}
+
+/// Embeddings captured from a catalog at one point in time, keyed by item ID
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingSnapshot {
+    ids: Vec<String>,
+    embeddings: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl EmbeddingSnapshot {
+    /// Create a snapshot from item IDs and a flat buffer with one vector per ID
+    #[wasm_bindgen(constructor)]
+    pub fn new(ids: Vec<String>, embeddings: Vec<f32>) -> Self {
+        EmbeddingSnapshot { ids, embeddings }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl EmbeddingSnapshot {
+    fn validate(&self, dimension: usize) -> Result<(), Error> {
+        if dimension == 0 || self.embeddings.len() != self.ids.len() * dimension {
+            return Err(Error::InvalidBatch {
+                operation: "drift_report",
+                len: self.embeddings.len(),
+                dimension,
+            });
+        }
+        if self.ids.is_empty() {
+            return Err(Error::EmptyInput {
+                operation: "drift_report",
+            });
+        }
+        // NaN has no place in the empirical CDFs the KS statistic compares
+        if let Some(at) = self.embeddings.iter().position(|x| !x.is_finite()) {
+            return Err(Error::InvalidArgument {
+                operation: "drift_report",
+                message: format!("embedding of {} is not finite", self.ids[at / dimension]),
+            });
+        }
+        Ok(())
+    }
+
+    fn vector(&self, row: usize, dimension: usize) -> &[f32] {
+        &self.embeddings[row * dimension..(row + 1) * dimension]
+    }
+}
+
+/// Sampling parameters and pass/fail thresholds for [`drift_report_with_config`]
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftConfig {
+    k: usize,
+    sample_size: usize,
+    seed: u64,
+    max_mean_shift: f32,
+    ks_threshold: f32,
+    max_ks_drift_fraction: f32,
+    max_mmd: f32,
+    min_neighbor_overlap: f32,
+}
+
+#[wasm_bindgen]
+impl DriftConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        DriftConfig {
+            k: 10,
+            sample_size: 500,
+            seed: 42,
+            max_mean_shift: 0.1,
+            ks_threshold: 0.1,
+            max_ks_drift_fraction: 0.05,
+            max_mmd: 0.01,
+            min_neighbor_overlap: 0.7,
+        }
+    }
+
+    /// Number of neighbors compared for neighbor overlap
+    pub fn set_k(&mut self, k: usize) {
+        self.k = k.max(1);
+    }
+
+    /// Maximum number of vectors sampled for MMD and neighbor overlap
+    pub fn set_sample_size(&mut self, sample_size: usize) {
+        self.sample_size = sample_size.max(2);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Maximum RMS mean shift, measured in baseline standard deviations
+    pub fn set_max_mean_shift(&mut self, value: f32) {
+        self.max_mean_shift = value;
+    }
+
+    /// KS statistic above which a dimension counts as drifted
+    pub fn set_ks_threshold(&mut self, value: f32) {
+        self.ks_threshold = value;
+    }
+
+    /// Maximum fraction of dimensions allowed to exceed the KS threshold
+    pub fn set_max_ks_drift_fraction(&mut self, value: f32) {
+        self.max_ks_drift_fraction = value;
+    }
+
+    /// Maximum squared MMD between the sampled distributions
+    pub fn set_max_mmd(&mut self, value: f32) {
+        self.max_mmd = value;
+    }
+
+    /// Minimum mean neighbor overlap@k on shared IDs
+    pub fn set_min_neighbor_overlap(&mut self, value: f32) {
+        self.min_neighbor_overlap = value;
+    }
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of comparing two embedding snapshots
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftReport {
+    mean_shift: f32,
+    ks_statistics: Vec<f32>,
+    ks_drift_fraction: f32,
+    mmd: f32,
+    neighbor_overlap: Option<f32>,
+    shared_ids: usize,
+    failures: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl DriftReport {
+    /// RMS difference of the means, in baseline standard deviations
+    pub fn mean_shift(&self) -> f32 {
+        self.mean_shift
+    }
+
+    /// Two-sample Kolmogorov-Smirnov statistic for each dimension
+    pub fn ks_statistics(&self) -> Vec<f32> {
+        self.ks_statistics.clone()
+    }
+
+    pub fn max_ks(&self) -> f32 {
+        self.ks_statistics.iter().copied().fold(0.0, f32::max)
+    }
+
+    /// Fraction of dimensions whose KS statistic exceeds the threshold
+    pub fn ks_drift_fraction(&self) -> f32 {
+        self.ks_drift_fraction
+    }
+
+    /// Squared maximum mean discrepancy with an RBF kernel (median bandwidth)
+    pub fn mmd(&self) -> f32 {
+        self.mmd
+    }
+
+    /// Mean neighbor overlap@k on shared IDs, or `None` if too few IDs are shared
+    pub fn neighbor_overlap(&self) -> Option<f32> {
+        self.neighbor_overlap
+    }
+
+    /// Number of IDs present in both snapshots
+    pub fn shared_ids(&self) -> usize {
+        self.shared_ids
+    }
+
+    /// Whether every check stayed within its threshold
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Human-readable description of each failed check
+    pub fn failures(&self) -> Vec<String> {
+        self.failures.clone()
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("drift report", e))
+    }
+}
+
+/// Compare two snapshots with the default [`DriftConfig`]
+#[wasm_bindgen]
+pub fn drift_report(
+    baseline: &EmbeddingSnapshot,
+    current: &EmbeddingSnapshot,
+    dimension: usize,
+) -> Result<DriftReport, Error> {
+    drift_report_with_config(baseline, current, dimension, &DriftConfig::new())
+}
+
+/// Compare two snapshots and check the results against `config`'s thresholds
+#[wasm_bindgen]
+pub fn drift_report_with_config(
+    baseline: &EmbeddingSnapshot,
+    current: &EmbeddingSnapshot,
+    dimension: usize,
+    config: &DriftConfig,
+) -> Result<DriftReport, Error> {
+    baseline.validate(dimension)?;
+    current.validate(dimension)?;
+
+    let baseline_stats = EmbeddingStats::from_batch(&baseline.embeddings, dimension)?;
+    let current_stats = EmbeddingStats::from_batch(&current.embeddings, dimension)?;
+    let mean_shift = (baseline_stats
+        .mean
+        .iter()
+        .zip(&current_stats.mean)
+        .zip(&baseline_stats.std_dev)
+        .map(|((b, c), s)| {
+            let z = (c - b) / s.max(TRANSFORM_EPSILON as f32);
+            z * z
+        })
+        .sum::<f32>()
+        / dimension as f32)
+        .sqrt();
+
+    let ks_statistics: Vec<f32> = (0..dimension)
+        .map(|j| {
+            ks_statistic(
+                baseline
+                    .embeddings
+                    .iter()
+                    .skip(j)
+                    .step_by(dimension)
+                    .copied()
+                    .collect(),
+                current
+                    .embeddings
+                    .iter()
+                    .skip(j)
+                    .step_by(dimension)
+                    .copied()
+                    .collect(),
+            )
+        })
+        .collect();
+    let drifted = ks_statistics
+        .iter()
+        .filter(|&&ks| ks > config.ks_threshold)
+        .count();
+    let ks_drift_fraction = drifted as f32 / dimension as f32;
+
+    let mut rng = crate::utils::SeededRng::new(config.seed);
+    let baseline_sample = rng.sample_indices(baseline.len(), config.sample_size);
+    let current_sample = rng.sample_indices(current.len(), config.sample_size);
+    let mmd = rbf_mmd(
+        &baseline_sample
+            .iter()
+            .map(|&row| baseline.vector(row, dimension))
+            .collect::<Vec<_>>(),
+        &current_sample
+            .iter()
+            .map(|&row| current.vector(row, dimension))
+            .collect::<Vec<_>>(),
+    );
+
+    let current_rows: HashMap<&str, usize> = current
+        .ids
+        .iter()
+        .enumerate()
+        .map(|(row, id)| (id.as_str(), row))
+        .collect();
+    let shared: Vec<(usize, usize)> = baseline
+        .ids
+        .iter()
+        .enumerate()
+        .filter_map(|(row, id)| current_rows.get(id.as_str()).map(|&other| (row, other)))
+        .collect();
+    let mut shared_sample: Vec<(usize, usize)> = rng
+        .sample_indices(shared.len(), config.sample_size)
+        .into_iter()
+        .map(|i| shared[i])
+        .collect();
+    shared_sample.sort_unstable();
+    let neighbor_overlap = (shared_sample.len() > config.k).then(|| {
+        let baseline_vectors: Vec<&[f32]> = shared_sample
+            .iter()
+            .map(|&(row, _)| baseline.vector(row, dimension))
+            .collect();
+        let current_vectors: Vec<&[f32]> = shared_sample
+            .iter()
+            .map(|&(_, row)| current.vector(row, dimension))
+            .collect();
+        neighbor_overlap_at_k(&baseline_vectors, &current_vectors, config.k)
+    });
+
+    let mut failures = Vec::new();
+    if mean_shift > config.max_mean_shift {
+        failures.push(format!(
+            "mean shift {:.4} exceeds {:.4}",
+            mean_shift, config.max_mean_shift
+        ));
+    }
+    if ks_drift_fraction > config.max_ks_drift_fraction {
+        failures.push(format!(
+            "{} of {} dimensions have KS statistic above {:.4}",
+            drifted, dimension, config.ks_threshold
+        ));
+    }
+    if mmd > config.max_mmd {
+        failures.push(format!("MMD {:.4} exceeds {:.4}", mmd, config.max_mmd));
+    }
+    if let Some(overlap) = neighbor_overlap.filter(|&o| o < config.min_neighbor_overlap) {
+        failures.push(format!(
+            "neighbor overlap@{} {:.4} is below {:.4}",
+            config.k, overlap, config.min_neighbor_overlap
+        ));
+    }
+
+    Ok(DriftReport {
+        mean_shift,
+        ks_statistics,
+        ks_drift_fraction,
+        mmd,
+        neighbor_overlap,
+        shared_ids: shared.len(),
+        failures,
+    })
+}
+
+/// Two-sample Kolmogorov-Smirnov statistic: the largest gap between the empirical CDFs
+fn ks_statistic(mut a: Vec<f32>, mut b: Vec<f32>) -> f32 {
+    a.sort_by(|x, y| x.total_cmp(y));
+    b.sort_by(|x, y| x.total_cmp(y));
+
+    let (n, m) = (a.len() as f32, b.len() as f32);
+    let (mut i, mut j) = (0, 0);
+    let mut max_gap = 0.0f32;
+    while i < a.len() && j < b.len() {
+        let value = a[i].min(b[j]);
+        while i < a.len() && a[i] <= value {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= value {
+            j += 1;
+        }
+        max_gap = max_gap.max((i as f32 / n - j as f32 / m).abs());
+    }
+    max_gap
+}
+
+/// Unbiased squared MMD with an RBF kernel whose bandwidth is the median pairwise distance
+fn rbf_mmd(x: &[&[f32]], y: &[&[f32]]) -> f32 {
+    if x.len() < 2 || y.len() < 2 {
+        return 0.0;
+    }
+
+    let squared_distance = |a: &[f32], b: &[f32]| -> f64 {
+        a.iter().zip(b).map(|(p, q)| ((p - q) as f64).powi(2)).sum()
+    };
+
+    let pooled: Vec<&[f32]> = x.iter().chain(y).copied().collect();
+    let mut distances: Vec<f64> = Vec::with_capacity(pooled.len() * (pooled.len() - 1) / 2);
+    for i in 0..pooled.len() {
+        for j in (i + 1)..pooled.len() {
+            distances.push(squared_distance(pooled[i], pooled[j]));
+        }
+    }
+    let middle = distances.len() / 2;
+    let (_, median, _) = distances.select_nth_unstable_by(middle, |a, b| a.total_cmp(b));
+    let gamma = 1.0 / median.max(f64::MIN_POSITIVE);
+    let kernel = |a: &[f32], b: &[f32]| (-gamma * squared_distance(a, b)).exp();
+
+    let within = |set: &[&[f32]]| -> f64 {
+        let mut sum = 0.0;
+        for i in 0..set.len() {
+            for j in (i + 1)..set.len() {
+                sum += 2.0 * kernel(set[i], set[j]);
+            }
+        }
+        sum / (set.len() * (set.len() - 1)) as f64
+    };
+    let cross: f64 = x
+        .iter()
+        .flat_map(|a| y.iter().map(|b| kernel(a, b)))
+        .sum::<f64>()
+        / (x.len() * y.len()) as f64;
+
+    (within(x) + within(y) - 2.0 * cross).max(0.0) as f32
+}
+
+/// Mean fraction of each item's cosine k-nearest neighbors that agree between two spaces
+///
+/// `a[i]` and `b[i]` must describe the same item.
+fn neighbor_overlap_at_k(a: &[&[f32]], b: &[&[f32]], k: usize) -> f32 {
+    let neighbors = |vectors: &[&[f32]], i: usize| -> Vec<usize> {
+        let query = vectors[i];
+        let query_norm = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let mut scored: Vec<(usize, f32)> = vectors
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(j, v)| {
+                let dot: f32 = query.iter().zip(v.iter()).map(|(p, q)| p * q).sum();
+                let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                (j, dot / (query_norm * norm).max(f32::MIN_POSITIVE))
+            })
+            .collect();
+        scored.sort_by(|x, y| y.1.total_cmp(&x.1).then(x.0.cmp(&y.0)));
+        scored.into_iter().take(k).map(|(j, _)| j).collect()
+    };
+
+    let total: f32 = (0..a.len())
+        .map(|i| {
+            let in_a = neighbors(a, i);
+            let in_b = neighbors(b, i);
+            in_a.iter().filter(|j| in_b.contains(j)).count() as f32 / k as f32
+        })
+        .sum();
+    total / a.len() as f32
+}
+
+/// How [`FusionModel`] combines per-modality embeddings
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FusionStrategy {
+    /// Concatenate normalized blocks scaled by the square root of their weight
+    Concatenate,
+    /// Weighted concatenation followed by a learned linear projection
+    Projection,
+    /// Store unweighted blocks and combine per-modality cosine scores at query time
+    LateFusion,
+}
+
+/// One input modality of a [`FusionModel`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Modality {
+    name: String,
+    dimension: usize,
+    weight: f32,
+}
+
+/// Per-modality vectors for one catalog item; absent modalities are simply not set
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct FusionInput {
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+#[wasm_bindgen]
+impl FusionInput {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the embedding for a modality such as "text", "poster" or "audio"
+    pub fn set(&mut self, modality: &str, vector: &[f32]) {
+        self.vectors.insert(modality.to_string(), vector.to_vec());
+    }
+
+    pub fn has(&self, modality: &str) -> bool {
+        self.vectors.contains_key(modality)
+    }
+}
+
+/// Combines text, image and audio embeddings of a title into one index vector
+///
+/// Each modality vector is L2-normalized and weighted; when a modality is
+/// missing, the weights of the remaining ones are renormalized to sum to one so
+/// fused vectors stay comparable.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FusionModel {
+    strategy: FusionStrategy,
+    modalities: Vec<Modality>,
+    /// Row-major `projection_dimension × concatenated dimension` matrix
+    projection: Vec<f32>,
+    projection_dimension: usize,
+}
+
+#[wasm_bindgen]
+impl FusionModel {
+    #[wasm_bindgen(constructor)]
+    pub fn new(strategy: FusionStrategy) -> Self {
+        FusionModel {
+            strategy,
+            modalities: Vec::new(),
+            projection: Vec::new(),
+            projection_dimension: 0,
+        }
+    }
+
+    /// Register a modality; blocks are laid out in registration order
+    pub fn add_modality(&mut self, name: &str, dimension: usize, weight: f32) -> Result<(), Error> {
+        if dimension == 0 || !(weight > 0.0 && weight.is_finite()) {
+            return Err(Error::InvalidArgument {
+                operation: "add_modality",
+                message: format!(
+                    "modality {} needs a non-zero dimension and a positive weight",
+                    name
+                ),
+            });
+        }
+        if self.modalities.iter().any(|m| m.name == name) {
+            return Err(Error::InvalidArgument {
+                operation: "add_modality",
+                message: format!("duplicate modality {}", name),
+            });
+        }
+        if !self.projection.is_empty() {
+            return Err(Error::InvalidState {
+                operation: "add a modality",
+                message: "modalities must be registered before the projection is set".to_string(),
+            });
+        }
+
+        self.modalities.push(Modality {
+            name: name.to_string(),
+            dimension,
+            weight,
+        });
+        Ok(())
+    }
+
+    /// Set the learned projection used by [`FusionStrategy::Projection`]
+    ///
+    /// `weights` is row-major with `output_dimension` rows, one column per
+    /// concatenated input dimension.
+    pub fn set_projection(
+        &mut self,
+        weights: Vec<f32>,
+        output_dimension: usize,
+    ) -> Result<(), Error> {
+        let input_dimension = self.concatenated_dimension();
+        if output_dimension == 0 || weights.len() != output_dimension * input_dimension {
+            return Err(Error::InvalidArgument {
+                operation: "set_projection",
+                message: format!(
+                    "projection must be {}x{}, got {} weights",
+                    output_dimension,
+                    input_dimension,
+                    weights.len()
+                ),
+            });
+        }
+
+        self.projection = weights;
+        self.projection_dimension = output_dimension;
+        Ok(())
+    }
+
+    pub fn strategy(&self) -> FusionStrategy {
+        self.strategy
+    }
+
+    /// Names of the registered modalities in block order
+    pub fn modalities(&self) -> Vec<String> {
+        self.modalities.iter().map(|m| m.name.clone()).collect()
+    }
+
+    /// Dimension of the fused vectors produced by [`fuse`](Self::fuse)
+    pub fn output_dimension(&self) -> usize {
+        match self.strategy {
+            FusionStrategy::Projection => self.projection_dimension,
+            FusionStrategy::Concatenate | FusionStrategy::LateFusion => {
+                self.concatenated_dimension()
+            }
+        }
+    }
+
+    /// Fuse one item's modality vectors into an index vector
+    pub fn fuse(&self, input: &FusionInput) -> Result<Vec<f32>, Error> {
+        if let Some(unknown) = input
+            .vectors
+            .keys()
+            .find(|name| !self.modalities.iter().any(|m| &m.name == *name))
+        {
+            return Err(Error::NotFound {
+                kind: "modality",
+                name: unknown.clone(),
+            });
+        }
+        if self.strategy == FusionStrategy::Projection && self.projection.is_empty() {
+            return Err(Error::InvalidState {
+                operation: "fuse",
+                message: "projection fusion requires set_projection".to_string(),
+            });
+        }
+
+        let mut blocks = Vec::with_capacity(self.modalities.len());
+        for modality in &self.modalities {
+            let block = match input.vectors.get(&modality.name) {
+                Some(vector) if vector.len() != modality.dimension => {
+                    return Err(Error::DimensionMismatch {
+                        operation: "fuse",
+                        expected: modality.dimension,
+                        actual: vector.len(),
+                    });
+                }
+                Some(vector) => {
+                    let mut block = vector.clone();
+                    crate::vector_search::normalize_vector(&mut block);
+                    Some(block)
+                }
+                None => None,
+            };
+            blocks.push(block);
+        }
+
+        let total_weight: f32 = self
+            .modalities
+            .iter()
+            .zip(&blocks)
+            .filter(|(_, block)| block.is_some())
+            .map(|(m, _)| m.weight)
+            .sum();
+        if total_weight == 0.0 {
+            return Err(Error::EmptyInput { operation: "fuse" });
+        }
+
+        let mut fused = Vec::with_capacity(self.concatenated_dimension());
+        for (modality, block) in self.modalities.iter().zip(&blocks) {
+            match block {
+                Some(block) if self.strategy == FusionStrategy::LateFusion => {
+                    fused.extend_from_slice(block)
+                }
+                Some(block) => {
+                    let scale = (modality.weight / total_weight).sqrt();
+                    fused.extend(block.iter().map(|x| x * scale));
+                }
+                None => fused.extend(std::iter::repeat_n(0.0, modality.dimension)),
+            }
+        }
+
+        if self.strategy == FusionStrategy::Projection {
+            let mut projected: Vec<f32> = self
+                .projection
+                .chunks_exact(fused.len())
+                .map(|row| row.iter().zip(&fused).map(|(w, x)| w * x).sum())
+                .collect();
+            crate::vector_search::normalize_vector(&mut projected);
+            return Ok(projected);
+        }
+        Ok(fused)
+    }
+
+    /// Similarity between two fused vectors
+    ///
+    /// For late fusion this is the weighted mean of per-modality cosine
+    /// similarities over the modalities both vectors have; otherwise it is the
+    /// dot product of the fused vectors.
+    pub fn score(&self, query: &[f32], candidate: &[f32]) -> Result<f32, Error> {
+        let dimension = self.output_dimension();
+        Error::check_dimension("FusionModel::score", dimension, query.len())?;
+        Error::check_dimension("FusionModel::score", dimension, candidate.len())?;
+
+        if self.strategy != FusionStrategy::LateFusion {
+            return Ok(crate::vector_search::dot_product(query, candidate));
+        }
+
+        let mut offset = 0;
+        let mut weighted = 0.0;
+        let mut total_weight = 0.0;
+        for modality in &self.modalities {
+            let range = offset..offset + modality.dimension;
+            offset += modality.dimension;
+            let (q, c) = (&query[range.clone()], &candidate[range]);
+            let present = |v: &[f32]| v.iter().any(|&x| x != 0.0);
+            if present(q) && present(c) {
+                weighted += modality.weight * crate::vector_search::dot_product(q, c);
+                total_weight += modality.weight;
+            }
+        }
+
+        Ok(if total_weight > 0.0 {
+            weighted / total_weight
+        } else {
+            0.0
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("fusion model", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<FusionModel, Error> {
+        let model: FusionModel = serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        model
+            .validate()
+            .map_err(|e| Error::parse("fusion model", e))?;
+        Ok(model)
+    }
+}
+
+impl FusionModel {
+    /// Check what `add_modality` and `set_projection` would have enforced
+    fn validate(&self) -> Result<(), String> {
+        for (i, modality) in self.modalities.iter().enumerate() {
+            if modality.dimension == 0 || !(modality.weight > 0.0 && modality.weight.is_finite()) {
+                return Err(format!(
+                    "modality {} needs a non-zero dimension and a positive weight",
+                    modality.name
+                ));
+            }
+            if self.modalities[..i].iter().any(|m| m.name == modality.name) {
+                return Err(format!("duplicate modality {}", modality.name));
+            }
+        }
+        if self.projection.is_empty() && self.projection_dimension == 0 {
+            return Ok(());
+        }
+        let expected = self
+            .projection_dimension
+            .checked_mul(self.concatenated_dimension())
+            .ok_or("projection dimensions overflow")?;
+        if self.projection_dimension == 0 || self.projection.len() != expected {
+            return Err(format!(
+                "projection must be {}x{}, got {} weights",
+                self.projection_dimension,
+                self.concatenated_dimension(),
+                self.projection.len()
+            ));
+        }
+        Ok(())
+    }
+
+    fn concatenated_dimension(&self) -> usize {
+        self.modalities.iter().map(|m| m.dimension).sum()
+    }
+}
+
+/// Reduce embedding dimensionality using PCA-like projection
+#[wasm_bindgen]
+pub fn reduce_dimensions(
+    embeddings: &[f32],
+    original_dim: usize,
+    target_dim: usize,
+) -> Result<Vec<f32>, Error> {
+    if target_dim > original_dim {
+        return Err(Error::InvalidArgument {
+            operation: "reduce_dimensions",
+            message: "target dimension must be less than original".to_string(),
+        });
+    }
+
+    Error::check_batch("reduce_dimensions", embeddings, original_dim)?;
+    let count = embeddings.len() / original_dim;
+
+    // Simple dimension reduction: take first N dimensions
+    // In production, this would use PCA or other methods
+    let mut result = Vec::with_capacity(count * target_dim);
+
+    for i in 0..count {
+        let start = i * original_dim;
+        for j in 0..target_dim {
+            result.push(embeddings[start + j]);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Compute embedding centroid (mean vector)
+#[wasm_bindgen]
+pub fn compute_centroid(embeddings: &[f32], dimension: usize) -> Result<Vec<f32>, Error> {
+    Error::check_batch("compute_centroid", embeddings, dimension)?;
+    let count = embeddings.len() / dimension;
+
+    let mut centroid = vec![0.0; dimension];
+
+    for i in 0..count {
+        let start = i * dimension;
+        for j in 0..dimension {
+            centroid[j] += embeddings[start + j];
+        }
+    }
+
+    for val in centroid.iter_mut() {
+        *val /= count as f32;
+    }
+
+    Ok(centroid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_embeddings() {
+        let config = EmbeddingConfig::new(3);
+        let generator =
+            EmbeddingGenerator::new(config).with_clock(crate::clock::FakeClock::with_step(2.5));
+
+        let embeddings = vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+        let pooled = generator.pool_embeddings(&embeddings, 2).unwrap();
+        assert_eq!(pooled.len(), 3);
+        let registry = crate::metrics::metrics();
+        assert_eq!(registry.latency("pool").map(|h| h.max_ms()), Some(2.5));
+    }
+
+    #[test]
+    fn test_embedding_stats() {
+        let embeddings = vec![1.0, 2.0, 3.0, 2.0, 3.0, 4.0, 3.0, 4.0, 5.0];
+
+        let stats = EmbeddingStats::from_batch(&embeddings, 3).unwrap();
+        let mean = stats.mean();
+
+        assert!((mean[0] - 2.0).abs() < 1e-6);
+        assert!((mean[1] - 3.0).abs() < 1e-6);
+        assert!((mean[2] - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_streaming_stats_match_batch() {
+        let embeddings: Vec<f32> = (0..40).map(|i| ((i * 7) % 11) as f32 - 5.0).collect();
+        let batch = EmbeddingStats::from_batch(&embeddings, 4).unwrap();
+
+        let mut left = EmbeddingStatsAccumulator::new(4);
+        left.update(&embeddings[..12]).unwrap();
+        left.update(&embeddings[12..20]).unwrap();
+        let mut right = EmbeddingStatsAccumulator::new(4);
+        right.update(&embeddings[20..]).unwrap();
+        left.merge(&right).unwrap();
+
+        let merged = left.stats().unwrap();
+        assert_eq!(merged.count(), 10);
+        for j in 0..4 {
+            assert!((merged.mean()[j] - batch.mean()[j]).abs() < 1e-5);
+            assert!((merged.std_dev()[j] - batch.std_dev()[j]).abs() < 1e-5);
+            assert_eq!(merged.min()[j], batch.min()[j]);
+            assert_eq!(merged.max()[j], batch.max()[j]);
+        }
+        assert!(EmbeddingStatsAccumulator::new(4).stats().is_none());
+    }
+
+    #[test]
+    fn test_covariance_subset() {
+        #[rustfmt::skip]
+        let embeddings = [
+            1.0, 2.0, 0.0,
+            2.0, 4.0, 1.0,
+            3.0, 6.0, 0.0,
+            4.0, 8.0, 1.0,
+        ];
+        let mut first = EmbeddingStatsAccumulator::with_covariance_dims(3, vec![0, 1]).unwrap();
+        first.update(&embeddings[..3]).unwrap();
+        let mut second = EmbeddingStatsAccumulator::with_covariance_dims(3, vec![0, 1]).unwrap();
+        second.update(&embeddings[3..]).unwrap();
+        first.merge(&second).unwrap();
+
+        // var(x) = 1.25, cov(x, 2x) = 2.5, var(2x) = 5.0
+        let cov = first.covariance();
+        assert!((cov[0] - 1.25).abs() < 1e-6);
+        assert!((cov[1] - 2.5).abs() < 1e-6);
+        assert!((cov[2] - 2.5).abs() < 1e-6);
+        assert!((cov[3] - 5.0).abs() < 1e-6);
+        assert!((first.variance()[2] - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_inconsistent_accumulators_are_rejected() {
+        let mut accumulator =
+            EmbeddingStatsAccumulator::with_covariance_dims(3, vec![0, 2]).unwrap();
+        accumulator.update(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        let restored = EmbeddingStatsAccumulator::from_json(&accumulator.to_json().unwrap());
+        assert_eq!(restored.unwrap().count(), 2);
+
+        let mut short_m2 = accumulator.clone();
+        short_m2.m2.pop();
+        let mut bad_comoment = accumulator.clone();
+        bad_comoment.comoment.push(0.0);
+        let mut bad_dim = accumulator.clone();
+        bad_dim.covariance_dims[1] = 3;
+        for broken in [short_m2, bad_comoment, bad_dim] {
+            let Err(err) = EmbeddingStatsAccumulator::from_json(&broken.to_json().unwrap()) else {
+                panic!("an inconsistent accumulator was restored");
+            };
+            assert_eq!(err.code(), "PARSE_ERROR");
+        }
+    }
+
+    #[test]
+    fn test_norm_quantiles() {
+        let mut accumulator = EmbeddingStatsAccumulator::new(2);
+        accumulator
+            .update(&[3.0, 4.0, 0.0, 1.0, 0.0, 2.0, 6.0, 8.0])
+            .unwrap();
+
+        let quantiles = accumulator.norm_quantiles(&[0.0, 0.5, 1.0]);
+        assert_eq!((quantiles[0], quantiles[2]), (1.0, 10.0));
+        assert!((quantiles[1] - 3.5).abs() < 0.1, "{}", quantiles[1]);
+
+        // The sketch stays small however many vectors it sees, and merges exactly
+        let norms: Vec<f32> = (1..=20_000).map(|i| i as f32 / 100.0).collect();
+        let (mut left, mut right) = (
+            EmbeddingStatsAccumulator::new(1),
+            EmbeddingStatsAccumulator::new(1),
+        );
+        left.update(&norms[..5_000]).unwrap();
+        right.update(&norms[5_000..]).unwrap();
+        left.merge(&right).unwrap();
+        assert!(left.norms.counts.len() < 1_000);
+        for (q, expected) in [(0.1, 20.0), (0.5, 100.0), (0.99, 198.0)] {
+            let estimate = left.norm_quantiles(&[q])[0];
+            assert!(
+                (estimate - expected).abs() / expected < 0.03,
+                "{} {}",
+                q,
+                estimate
+            );
+        }
+    }
+
+    fn correlated_batch() -> Vec<f32> {
+        let mut rng = crate::utils::SeededRng::new(7);
+        (0..500)
+            .flat_map(|_| {
+                let a = rng.normal() as f32;
+                let b = rng.normal() as f32;
+                let c = rng.normal() as f32;
+                vec![0.8 + a, 0.5 + 0.9 * a + 0.2 * b, 0.7 + 0.3 * c - 0.5 * a]
+            })
+            .collect()
+    }
+
+    fn covariance_of(embeddings: &[f32], dimension: usize) -> Vec<f32> {
+        let mut accumulator =
+            EmbeddingStatsAccumulator::with_covariance_dims(dimension, (0..dimension).collect())
+                .unwrap();
+        accumulator.update(embeddings).unwrap();
+        accumulator.covariance()
+    }
+
+    #[test]
+    fn test_standardizer() {
+        let embeddings = correlated_batch();
+        let standardizer = Standardizer::fit(&embeddings, 3).unwrap();
+        let transformed = standardizer.transform_batch(&embeddings).unwrap();
+
+        let stats = EmbeddingStats::from_batch(&transformed, 3).unwrap();
+        for j in 0..3 {
+            assert!(stats.mean()[j].abs() < 1e-4);
+            assert!((stats.std_dev()[j] - 1.0).abs() < 1e-4);
+        }
+
+        let restored = Standardizer::from_json(&standardizer.to_json().unwrap()).unwrap();
+        assert_eq!(
+            restored.transform(&embeddings[..3]).unwrap(),
+            standardizer.transform(&embeddings[..3]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_whitening_decorrelates() {
+        let embeddings = correlated_batch();
+
+        let zca = Whitener::fit(&embeddings, 3, WhiteningMode::Zca, None).unwrap();
+        let covariance = covariance_of(&zca.transform_batch(&embeddings).unwrap(), 3);
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((covariance[i * 3 + j] - expected).abs() < 1e-3);
+            }
+        }
+
+        let pca = Whitener::fit(&embeddings, 3, WhiteningMode::Pca, Some(2)).unwrap();
+        assert_eq!(pca.output_dimension(), 2);
+        let projected = pca.transform_batch(&embeddings).unwrap();
+        assert_eq!(projected.len(), 500 * 2);
+        let covariance = covariance_of(&projected, 2);
+        assert!((covariance[0] - 1.0).abs() < 1e-3);
+        assert!(covariance[1].abs() < 1e-3);
+        assert!((covariance[3] - 1.0).abs() < 1e-3);
+
+        let restored = Whitener::from_json(&pca.to_json().unwrap()).unwrap();
+        assert_eq!(restored.transform_batch(&embeddings).unwrap(), projected);
+    }
+
+    #[test]
+    fn test_truncated_transforms_are_rejected() {
+        let embeddings = correlated_batch();
+        let mut standardizer = Standardizer::fit(&embeddings, 3).unwrap();
+        standardizer.std_dev.pop();
+        let mut whitener = Whitener::fit(&embeddings, 3, WhiteningMode::Pca, Some(2)).unwrap();
+        whitener.matrix.truncate(5);
+        let mut empty = Standardizer::fit(&embeddings, 3).unwrap();
+        (empty.mean, empty.std_dev) = (Vec::new(), Vec::new());
+
+        for result in [
+            Standardizer::from_json(&standardizer.to_json().unwrap()).map(|_| ()),
+            Standardizer::from_json(&empty.to_json().unwrap()).map(|_| ()),
+            Whitener::from_json(&whitener.to_json().unwrap()).map(|_| ()),
+        ] {
+            let Err(err) = result else {
+                panic!("an inconsistent transform was restored");
+            };
+            assert_eq!(err.code(), "PARSE_ERROR");
+        }
+    }
+
+    fn snapshot(prefix: &str, count: usize, seed: u64, offset: f32) -> EmbeddingSnapshot {
+        let mut rng = crate::utils::SeededRng::new(seed);
+        let embeddings = (0..count * 4)
+            .map(|_| rng.normal() as f32 + offset)
+            .collect();
+        let ids = (0..count).map(|i| format!("{}{}", prefix, i)).collect();
+        EmbeddingSnapshot::new(ids, embeddings)
+    }
+
+    #[test]
+    fn test_drift_report_same_distribution() {
+        let baseline = snapshot("item-", 300, 1, 0.0);
+        let identical = drift_report(&baseline, &baseline, 4).unwrap();
+        assert!(identical.passed(), "{:?}", identical.failures());
+        assert_eq!(identical.mean_shift(), 0.0);
+        assert_eq!(identical.max_ks(), 0.0);
+        assert_eq!(identical.neighbor_overlap(), Some(1.0));
+        assert_eq!(identical.shared_ids(), 300);
+
+        // A fresh sample from the same distribution with disjoint IDs also passes
+        let resampled = drift_report(&baseline, &snapshot("new-", 300, 2, 0.0), 4).unwrap();
+        assert!(resampled.passed(), "{:?}", resampled.failures());
+        assert_eq!(resampled.neighbor_overlap(), None);
+    }
+
+    #[test]
+    fn test_drift_report_detects_shift() {
+        let baseline = snapshot("item-", 300, 1, 0.0);
+        let shifted = snapshot("item-", 300, 1, 0.5);
+        let report = drift_report(&baseline, &shifted, 4).unwrap();
+
+        assert!(!report.passed());
+        assert!((report.mean_shift() - 0.5).abs() < 0.1);
+        assert_eq!(report.ks_drift_fraction(), 1.0);
+        assert!(report.mmd() > 0.01);
+        assert_eq!(report.failures().len(), 4);
+        assert!(report.to_json().unwrap().contains("\"neighbor_overlap\""));
+    }
+
+    #[test]
+    fn test_drift_report_rejects_nan() {
+        let mut baseline = snapshot("item-", 10, 1, 0.0);
+        baseline.embeddings[4 * 3] = f32::NAN;
+        let mut current = snapshot("item-", 10, 2, 0.0);
+        current.embeddings[4 * 5] = f32::NAN;
+        let Err(err) = drift_report(&baseline, &current, 4) else {
+            panic!("a snapshot with NaN was compared");
+        };
+        assert_eq!(err.code(), "INVALID_ARGUMENT");
+        assert!(err.to_string().contains("item-3"), "{}", err);
+    }
+
+    fn fusion_model(strategy: FusionStrategy) -> FusionModel {
+        let mut model = FusionModel::new(strategy);
+        model.add_modality("text", 2, 0.6).unwrap();
+        model.add_modality("poster", 2, 0.3).unwrap();
+        model.add_modality("audio", 1, 0.1).unwrap();
+        model
+    }
+
+    #[test]
+    fn test_fusion_concatenate_renormalizes_missing_modality() {
+        let model = fusion_model(FusionStrategy::Concatenate);
+        let mut full = FusionInput::new();
+        full.set("text", &[3.0, 4.0]);
+        full.set("poster", &[0.0, 2.0]);
+        full.set("audio", &[-1.0]);
+
+        let fused = model.fuse(&full).unwrap();
+        assert_eq!(fused.len(), 5);
+        assert!((fused[0] - 0.6 * 0.6f32.sqrt()).abs() < 1e-6);
+        assert!((fused[3] - 0.3f32.sqrt()).abs() < 1e-6);
+        assert!((fused[4] + 0.1f32.sqrt()).abs() < 1e-6);
+
+        // Without audio, text and poster share the full weight
+        let mut partial = FusionInput::new();
+        partial.set("text", &[3.0, 4.0]);
+        partial.set("poster", &[0.0, 2.0]);
+        let fused = model.fuse(&partial).unwrap();
+        let norm: f32 = fused.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+        assert!((fused[3] - (0.3f32 / 0.9).sqrt()).abs() < 1e-6);
+        assert_eq!(fused[4], 0.0);
+    }
+
+    #[test]
+    fn test_fusion_projection_and_late_fusion() {
+        let mut model = fusion_model(FusionStrategy::Projection);
+        // Keep only the first text dimension and the audio dimension
+        model
+            .set_projection(vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0], 2)
+            .unwrap();
+        let mut input = FusionInput::new();
+        input.set("text", &[1.0, 0.0]);
+        input.set("audio", &[1.0]);
+        let fused = model.fuse(&input).unwrap();
+        assert_eq!(model.output_dimension(), 2);
+        assert!((fused[0] - 0.6f32.sqrt() / 0.7f32.sqrt()).abs() < 1e-6);
+
+        let late = fusion_model(FusionStrategy::LateFusion);
+        let mut query = FusionInput::new();
+        query.set("text", &[1.0, 0.0]);
+        query.set("poster", &[1.0, 0.0]);
+        let mut candidate = FusionInput::new();
+        candidate.set("text", &[1.0, 0.0]);
+        candidate.set("poster", &[0.0, 1.0]);
+        candidate.set("audio", &[1.0]);
+        let score = late
+            .score(&late.fuse(&query).unwrap(), &late.fuse(&candidate).unwrap())
+            .unwrap();
+        // Audio is missing from the query, so text and poster are reweighted to 2:1
+        assert!((score - 0.6 / 0.9).abs() < 1e-6);
+
+        let restored = FusionModel::from_json(&late.to_json().unwrap()).unwrap();
+        assert_eq!(restored.modalities(), vec!["text", "poster", "audio"]);
+
+        let restored = FusionModel::from_json(&model.to_json().unwrap()).unwrap();
+        assert_eq!(restored.fuse(&input).unwrap(), fused);
+        let mut truncated = model.clone();
+        truncated.projection.pop();
+        let mut negative = late.clone();
+        negative.modalities[1].weight = -0.3;
+        let mut resized = model;
+        resized.modalities[0].dimension = 3;
+        for broken in [truncated, negative, resized] {
+            let Err(err) = FusionModel::from_json(&broken.to_json().unwrap()) else {
+                panic!("an inconsistent fusion model was restored");
+            };
+            assert_eq!(err.code(), "PARSE_ERROR");
+        }
+    }
+
+    #[test]
+    fn test_model_registry() {
+        let names: Vec<String> = EmbeddingConfig::known_models()
+            .iter()
+            .map(|m| m.name())
+            .collect();
+        assert_eq!(names, vec!["minilm", "sentence_bert", "mpnet", "e5_large"]);
+
+        let config = EmbeddingConfig::for_model("mpnet").unwrap();
+        assert_eq!(config.dimension(), 768);
+        let info = config.model_info().unwrap();
+        assert_eq!(info.to_string(), "mpnet@1 (768d)");
+        // Same dimension, different model
+        let err = info
+            .check_compatible(&EmbeddingModelInfo::lookup("sentence_bert").unwrap())
+            .unwrap_err();
+        assert_eq!(err.field(), "name");
+    }
+
+    #[test]
+    fn test_compute_centroid() {
+        let embeddings = vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
 
         let centroid = compute_centroid(&embeddings, 3).unwrap();
-        assert!((centroid[0] - 1.0/3.0).abs() < 1e-6);
-        assert!((centroid[1] - 1.0/3.0).abs() < 1e-6);
-        assert!((centroid[2] - 1.0/3.0).abs() < 1e-6);
+        assert!((centroid[0] - 1.0 / 3.0).abs() < 1e-6);
+        assert!((centroid[1] - 1.0 / 3.0).abs() < 1e-6);
+        assert!((centroid[2] - 1.0 / 3.0).abs() < 1e-6);
     }
 }
@@ -0,0 +1,708 @@
+//! CPU inference for BERT-style sentence embedding models
+//!
+//! Runs the transformer forward pass of MiniLM / Sentence-BERT / E5 style
+//! encoders from weights in a local safetensors file, then pools the token
+//! states into a sentence embedding with [`EmbeddingGenerator`].
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::chunking::{encode_chunks, ChunkConfig, DocumentEmbedding};
+use crate::embeddings::{EmbeddingConfig, EmbeddingGenerator};
+use crate::error::Error;
+use crate::safetensors::SafeTensors;
+use crate::tokenizer::Tokenizer;
+use crate::vector_io::FormatError;
+
+/// Prefixes under which BERT weights are commonly stored
+const WEIGHT_PREFIXES: [&str; 2] = ["", "bert."];
+
+/// Weight and bias tensors of the six linear and two layer norm blocks in an encoder layer
+const TENSORS_PER_LAYER: usize = 16;
+
+/// Hyperparameters from a Hugging Face `config.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BertConfig {
+    pub vocab_size: usize,
+    pub hidden_size: usize,
+    pub num_hidden_layers: usize,
+    pub num_attention_heads: usize,
+    pub intermediate_size: usize,
+    pub max_position_embeddings: usize,
+    #[serde(default = "default_type_vocab_size")]
+    pub type_vocab_size: usize,
+    #[serde(default = "default_layer_norm_eps")]
+    pub layer_norm_eps: f64,
+    #[serde(default = "default_hidden_act")]
+    pub hidden_act: String,
+}
+
+fn default_type_vocab_size() -> usize {
+    2
+}
+
+fn default_layer_norm_eps() -> f64 {
+    1e-12
+}
+
+fn default_hidden_act() -> String {
+    "gelu".to_string()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Activation {
+    Gelu,
+    GeluTanh,
+    Relu,
+}
+
+/// Fully connected layer with row-major `[out_dim, in_dim]` weights
+#[derive(Debug, Clone)]
+struct Linear {
+    weight: Vec<f32>,
+    bias: Vec<f32>,
+    in_dim: usize,
+    out_dim: usize,
+}
+
+impl Linear {
+    fn forward(&self, input: &[f32]) -> Vec<f32> {
+        let rows = input.len() / self.in_dim;
+        let mut output = Vec::with_capacity(rows * self.out_dim);
+        for row in input.chunks_exact(self.in_dim) {
+            for (weights, bias) in self.weight.chunks_exact(self.in_dim).zip(&self.bias) {
+                output.push(bias + crate::vector_search::dot_product(row, weights));
+            }
+        }
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LayerNorm {
+    weight: Vec<f32>,
+    bias: Vec<f32>,
+    eps: f32,
+}
+
+impl LayerNorm {
+    fn forward(&self, values: &mut [f32]) {
+        let dim = self.weight.len();
+        for row in values.chunks_exact_mut(dim) {
+            let mean = row.iter().sum::<f32>() / dim as f32;
+            let variance = row.iter().map(|x| (x - mean) * (x - mean)).sum::<f32>() / dim as f32;
+            let inv_std = 1.0 / (variance + self.eps).sqrt();
+            for ((x, w), b) in row.iter_mut().zip(&self.weight).zip(&self.bias) {
+                *x = (*x - mean) * inv_std * w + b;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct EncoderLayer {
+    query: Linear,
+    key: Linear,
+    value: Linear,
+    attention_output: Linear,
+    attention_norm: LayerNorm,
+    intermediate: Linear,
+    output: Linear,
+    output_norm: LayerNorm,
+}
+
+/// BERT encoder weights and forward pass
+#[derive(Debug, Clone)]
+pub struct BertModel {
+    config: BertConfig,
+    activation: Activation,
+    word_embeddings: Vec<f32>,
+    position_embeddings: Vec<f32>,
+    token_type_embeddings: Vec<f32>,
+    embeddings_norm: LayerNorm,
+    layers: Vec<EncoderLayer>,
+}
+
+impl BertModel {
+    /// Load a model from a safetensors buffer
+    pub fn from_safetensors(config: BertConfig, bytes: &[u8]) -> Result<Self, Error> {
+        let activation = match config.hidden_act.as_str() {
+            "gelu" => Activation::Gelu,
+            "gelu_new" | "gelu_pytorch_tanh" => Activation::GeluTanh,
+            "relu" => Activation::Relu,
+            other => {
+                return Err(Error::InvalidArgument {
+                    operation: "BertModel::from_safetensors",
+                    message: format!(
+                        "unsupported hidden_act {:?}; expected gelu, gelu_new, gelu_pytorch_tanh or relu",
+                        other
+                    ),
+                })
+            }
+        };
+        for (name, value) in [
+            ("vocab_size", config.vocab_size),
+            ("type_vocab_size", config.type_vocab_size),
+            ("hidden_size", config.hidden_size),
+            ("intermediate_size", config.intermediate_size),
+            ("num_attention_heads", config.num_attention_heads),
+            ("max_position_embeddings", config.max_position_embeddings),
+        ] {
+            if value == 0 {
+                return Err(Error::InvalidArgument {
+                    operation: "BertModel::from_safetensors",
+                    message: format!("{} must be positive", name),
+                });
+            }
+        }
+        if !config
+            .hidden_size
+            .is_multiple_of(config.num_attention_heads)
+        {
+            return Err(Error::InvalidArgument {
+                operation: "BertModel::from_safetensors",
+                message: format!(
+                    "hidden_size {} is not divisible by num_attention_heads {}",
+                    config.hidden_size, config.num_attention_heads
+                ),
+            });
+        }
+
+        let tensors = SafeTensors::parse(bytes)?;
+        let prefix = WEIGHT_PREFIXES
+            .iter()
+            .find(|prefix| {
+                tensors
+                    .info(&format!("{}embeddings.word_embeddings.weight", prefix))
+                    .is_some()
+            })
+            .ok_or_else(|| {
+                FormatError::MissingTensor("embeddings.word_embeddings.weight".to_string())
+            })?;
+        // Every layer needs its own tensors, so a file bounds how many it can hold
+        let max_layers = tensors.names().len() / TENSORS_PER_LAYER;
+        if config.num_hidden_layers > max_layers {
+            return Err(Error::InvalidArgument {
+                operation: "BertModel::from_safetensors",
+                message: format!(
+                    "num_hidden_layers {} exceeds the {} layers the weights can hold",
+                    config.num_hidden_layers, max_layers
+                ),
+            });
+        }
+
+        let load = |name: &str, shape: &[usize]| -> Result<Vec<f32>, FormatError> {
+            let full_name = format!("{}{}", prefix, name);
+            let (actual, values) = tensors.to_f32(&full_name)?;
+            if actual != shape {
+                return Err(FormatError::TensorShape {
+                    name: full_name,
+                    expected: shape.to_vec(),
+                    actual,
+                });
+            }
+            Ok(values)
+        };
+        let hidden = config.hidden_size;
+        let linear = |name: &str, in_dim: usize, out_dim: usize| -> Result<Linear, FormatError> {
+            Ok(Linear {
+                weight: load(&format!("{}.weight", name), &[out_dim, in_dim])?,
+                bias: load(&format!("{}.bias", name), &[out_dim])?,
+                in_dim,
+                out_dim,
+            })
+        };
+        let eps = config.layer_norm_eps as f32;
+        let layer_norm = |name: &str| -> Result<LayerNorm, FormatError> {
+            Ok(LayerNorm {
+                weight: load(&format!("{}.weight", name), &[hidden])?,
+                bias: load(&format!("{}.bias", name), &[hidden])?,
+                eps,
+            })
+        };
+
+        let mut layers = Vec::new();
+        for i in 0..config.num_hidden_layers {
+            let layer = format!("encoder.layer.{}", i);
+            layers.push(EncoderLayer {
+                query: linear(&format!("{}.attention.self.query", layer), hidden, hidden)?,
+                key: linear(&format!("{}.attention.self.key", layer), hidden, hidden)?,
+                value: linear(&format!("{}.attention.self.value", layer), hidden, hidden)?,
+                attention_output: linear(
+                    &format!("{}.attention.output.dense", layer),
+                    hidden,
+                    hidden,
+                )?,
+                attention_norm: layer_norm(&format!("{}.attention.output.LayerNorm", layer))?,
+                intermediate: linear(
+                    &format!("{}.intermediate.dense", layer),
+                    hidden,
+                    config.intermediate_size,
+                )?,
+                output: linear(
+                    &format!("{}.output.dense", layer),
+                    config.intermediate_size,
+                    hidden,
+                )?,
+                output_norm: layer_norm(&format!("{}.output.LayerNorm", layer))?,
+            });
+        }
+
+        Ok(BertModel {
+            activation,
+            word_embeddings: load(
+                "embeddings.word_embeddings.weight",
+                &[config.vocab_size, hidden],
+            )?,
+            position_embeddings: load(
+                "embeddings.position_embeddings.weight",
+                &[config.max_position_embeddings, hidden],
+            )?,
+            token_type_embeddings: load(
+                "embeddings.token_type_embeddings.weight",
+                &[config.type_vocab_size, hidden],
+            )?,
+            embeddings_norm: layer_norm("embeddings.LayerNorm")?,
+            layers,
+            config,
+        })
+    }
+
+    /// Model hyperparameters
+    pub fn config(&self) -> &BertConfig {
+        &self.config
+    }
+
+    /// Run the encoder on one sequence, returning `[seq_len, hidden_size]` hidden states
+    ///
+    /// Token and type IDs outside the vocabulary are clamped to the last entry;
+    /// sequences longer than `max_position_embeddings` are truncated.
+    pub fn forward(&self, input_ids: &[u32], type_ids: &[u32], attention_mask: &[u32]) -> Vec<f32> {
+        let hidden = self.config.hidden_size;
+        let seq_len = input_ids.len().min(self.config.max_position_embeddings);
+
+        let mut states = vec![0.0f32; seq_len * hidden];
+        for (pos, row) in states.chunks_exact_mut(hidden).enumerate() {
+            let token = (input_ids[pos] as usize).min(self.config.vocab_size - 1);
+            let token_type = (type_ids.get(pos).copied().unwrap_or(0) as usize)
+                .min(self.config.type_vocab_size - 1);
+            let word = &self.word_embeddings[token * hidden..(token + 1) * hidden];
+            let position = &self.position_embeddings[pos * hidden..(pos + 1) * hidden];
+            let kind = &self.token_type_embeddings[token_type * hidden..(token_type + 1) * hidden];
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = word[j] + position[j] + kind[j];
+            }
+        }
+        self.embeddings_norm.forward(&mut states);
+
+        let mask: Vec<bool> = (0..seq_len)
+            .map(|i| attention_mask.get(i).copied().unwrap_or(1) != 0)
+            .collect();
+        for layer in &self.layers {
+            states = self.layer_forward(layer, &states, &mask);
+        }
+
+        states
+    }
+
+    fn layer_forward(&self, layer: &EncoderLayer, input: &[f32], mask: &[bool]) -> Vec<f32> {
+        let hidden = self.config.hidden_size;
+        let heads = self.config.num_attention_heads;
+        let head_dim = hidden / heads;
+        let seq_len = mask.len();
+        let scale = 1.0 / (head_dim as f32).sqrt();
+
+        let query = layer.query.forward(input);
+        let key = layer.key.forward(input);
+        let value = layer.value.forward(input);
+
+        let mut context = vec![0.0f32; seq_len * hidden];
+        let mut scores = vec![0.0f32; seq_len];
+        for head in 0..heads {
+            let offset = head * head_dim;
+            for i in 0..seq_len {
+                let q = &query[i * hidden + offset..i * hidden + offset + head_dim];
+                let mut max_score = f32::NEG_INFINITY;
+                for (j, score) in scores.iter_mut().enumerate() {
+                    *score = if mask[j] {
+                        let k = &key[j * hidden + offset..j * hidden + offset + head_dim];
+                        crate::vector_search::dot_product(q, k) * scale
+                    } else {
+                        f32::NEG_INFINITY
+                    };
+                    max_score = max_score.max(*score);
+                }
+                if max_score == f32::NEG_INFINITY {
+                    continue;
+                }
+
+                let mut total = 0.0;
+                for score in scores.iter_mut() {
+                    *score = (*score - max_score).exp();
+                    total += *score;
+                }
+                let out = &mut context[i * hidden + offset..i * hidden + offset + head_dim];
+                for (j, &weight) in scores.iter().enumerate() {
+                    if weight == 0.0 {
+                        continue;
+                    }
+                    let v = &value[j * hidden + offset..j * hidden + offset + head_dim];
+                    let weight = weight / total;
+                    for (o, x) in out.iter_mut().zip(v) {
+                        *o += weight * x;
+                    }
+                }
+            }
+        }
+
+        let mut attention = layer.attention_output.forward(&context);
+        for (x, residual) in attention.iter_mut().zip(input) {
+            *x += residual;
+        }
+        layer.attention_norm.forward(&mut attention);
+
+        let mut intermediate = layer.intermediate.forward(&attention);
+        for x in intermediate.iter_mut() {
+            *x = activate(self.activation, *x);
+        }
+
+        let mut output = layer.output.forward(&intermediate);
+        for (x, residual) in output.iter_mut().zip(&attention) {
+            *x += residual;
+        }
+        layer.output_norm.forward(&mut output);
+        output
+    }
+}
+
+fn activate(activation: Activation, x: f32) -> f32 {
+    match activation {
+        Activation::Gelu => 0.5 * x * (1.0 + erf(x as f64 / std::f64::consts::SQRT_2) as f32),
+        Activation::GeluTanh => {
+            let inner = (2.0 / std::f32::consts::PI).sqrt() * (x + 0.044715 * x * x * x);
+            0.5 * x * (1.0 + inner.tanh())
+        }
+        Activation::Relu => x.max(0.0),
+    }
+}
+
+/// Error function (Abramowitz and Stegun 7.1.26, max error 1.5e-7)
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+/// Sentence embedding model: tokenizer, BERT encoder and pooling
+#[wasm_bindgen]
+pub struct SentenceEncoder {
+    tokenizer: Tokenizer,
+    model: BertModel,
+    generator: EmbeddingGenerator,
+}
+
+#[wasm_bindgen]
+impl SentenceEncoder {
+    /// Create an encoder from `config.json` contents, safetensors weights and a tokenizer
+    pub fn from_bytes(
+        config_json: &str,
+        weights: &[u8],
+        tokenizer: Tokenizer,
+    ) -> Result<SentenceEncoder, Error> {
+        Self::load(config_json, weights, tokenizer)
+    }
+
+    /// Embedding dimension
+    pub fn dimension(&self) -> usize {
+        self.model.config.hidden_size
+    }
+
+    /// Enable or disable L2 normalization of sentence embeddings
+    pub fn set_normalize(&mut self, normalize: bool) {
+        self.generator.set_normalize(normalize);
+        self.generator.clear_cache();
+    }
+
+    /// Encode one text into a sentence embedding
+    pub fn encode_text(&mut self, text: &str) -> Vec<f32> {
+        if let Some(cached) = self.generator.cached(text) {
+            return cached;
+        }
+        let encoding = self.tokenizer.encode(text);
+        let embedding = self.embed(
+            &encoding.ids(),
+            &encoding.type_ids(),
+            &encoding.attention_mask(),
+        );
+        self.generator.cache_insert(text, &embedding);
+        embedding
+    }
+
+    /// Encode a batch of texts into a flat `count * dimension` buffer
+    pub fn encode_batch(&mut self, texts: Vec<String>) -> Vec<f32> {
+        let mut result = Vec::with_capacity(texts.len() * self.dimension());
+        for text in &texts {
+            result.extend(self.encode_text(text));
+        }
+        result
+    }
+
+    /// Encode text of any length by pooling overlapping windows of the model's maximum length
+    pub fn encode_long_text(&self, text: &str, overlap: usize) -> Result<Vec<f32>, Error> {
+        let config = ChunkConfig::new(self.model.config.max_position_embeddings, overlap);
+        Ok(self.encode_document(text, &config)?.embedding())
+    }
+
+    /// Split text into chunks, embed each one and pool them into a document vector
+    ///
+    /// Windows are capped at the model's maximum sequence length.
+    pub fn encode_document(
+        &self,
+        text: &str,
+        config: &ChunkConfig,
+    ) -> Result<DocumentEmbedding, Error> {
+        let max_tokens = config
+            .max_tokens()
+            .min(self.model.config.max_position_embeddings);
+        let config = ChunkConfig::new(max_tokens, config.overlap());
+
+        let mut chunks = Vec::new();
+        let mut chunk_embeddings = Vec::new();
+        for (chunk, encoding) in encode_chunks(&self.tokenizer, text, &config) {
+            chunk_embeddings.extend(self.embed(
+                &encoding.ids(),
+                &encoding.type_ids(),
+                &encoding.attention_mask(),
+            ));
+            chunks.push(chunk);
+        }
+
+        let embedding = self
+            .generator
+            .pool_embeddings(&chunk_embeddings, chunks.len())?;
+        Ok(DocumentEmbedding::new(embedding, chunks, chunk_embeddings))
+    }
+}
+
+impl SentenceEncoder {
+    /// Load an encoder from a model directory
+    ///
+    /// The directory must contain `config.json`, `model.safetensors` and either
+    /// `tokenizer.json` or `vocab.txt`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_dir(dir: impl AsRef<std::path::Path>) -> Result<SentenceEncoder, Error> {
+        let dir = dir.as_ref();
+        let config_path = dir.join("config.json");
+        let config =
+            std::fs::read_to_string(&config_path).map_err(|e| Error::io(&config_path, e))?;
+        let weights_path = dir.join("model.safetensors");
+        let weights = std::fs::read(&weights_path).map_err(|e| Error::io(&weights_path, e))?;
+        let tokenizer_json = dir.join("tokenizer.json");
+        let tokenizer = if tokenizer_json.exists() {
+            Tokenizer::from_file(tokenizer_json)?
+        } else {
+            Tokenizer::from_file(dir.join("vocab.txt"))?
+        };
+        Self::load(&config, &weights, tokenizer)
+    }
+
+    /// The underlying transformer
+    pub fn model(&self) -> &BertModel {
+        &self.model
+    }
+
+    fn load(config_json: &str, weights: &[u8], mut tokenizer: Tokenizer) -> Result<Self, Error> {
+        let config: BertConfig =
+            serde_json::from_str(config_json).map_err(|e| Error::parse("model config", e))?;
+        let model = BertModel::from_safetensors(config, weights)?;
+
+        let max_length = model.config.max_position_embeddings;
+        if tokenizer
+            .truncation()
+            .is_none_or(|current| current > max_length)
+        {
+            tokenizer.set_truncation(max_length);
+        }
+        tokenizer.clear_padding();
+
+        let generator = EmbeddingGenerator::new(EmbeddingConfig::new(model.config.hidden_size));
+        Ok(SentenceEncoder {
+            tokenizer,
+            model,
+            generator,
+        })
+    }
+
+    /// Mean-pool the hidden states of non-padding tokens
+    fn embed(&self, ids: &[u32], type_ids: &[u32], attention_mask: &[u32]) -> Vec<f32> {
+        let hidden = self.dimension();
+        let states = self.model.forward(ids, type_ids, attention_mask);
+        let tokens: Vec<f32> = states
+            .chunks_exact(hidden)
+            .zip(attention_mask)
+            .filter(|(_, &mask)| mask != 0)
+            .flat_map(|(row, _)| row.iter().copied())
+            .collect();
+
+        let count = tokens.len() / hidden;
+        if count == 0 {
+            return vec![0.0; hidden];
+        }
+        self.generator.pool(&tokens, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    // Reference outputs come from candle-transformers' BertModel on a tiny
+    // randomly initialized model (tests/fixtures/model).
+    const CONFIG: &str = include_str!("../tests/fixtures/model/config.json");
+    const WEIGHTS: &[u8] = include_bytes!("../tests/fixtures/model/model.safetensors");
+    const WEIGHTS_F16: &[u8] = include_bytes!("../tests/fixtures/model/model-f16.safetensors");
+    const EXPECTED: &str = include_str!("../tests/fixtures/model/expected.json");
+    const TOKENIZER: &str = include_str!("../tests/fixtures/tokenizer/wordpiece_tokenizer.json");
+
+    fn encoder(weights: &[u8]) -> SentenceEncoder {
+        SentenceEncoder::load(CONFIG, weights, Tokenizer::from_json(TOKENIZER).unwrap()).unwrap()
+    }
+
+    fn expected(key: &str) -> Vec<Vec<f32>> {
+        let value: Value = serde_json::from_str(EXPECTED).unwrap();
+        serde_json::from_value(value[key].clone()).unwrap()
+    }
+
+    fn texts() -> Vec<String> {
+        let value: Value = serde_json::from_str(EXPECTED).unwrap();
+        serde_json::from_value(value["texts"].clone()).unwrap()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32], tolerance: f32) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < tolerance, "{} vs {}", a, e);
+        }
+    }
+
+    #[test]
+    fn test_hidden_states_match_reference() {
+        let encoder = encoder(WEIGHTS);
+        let encoding = encoder.tokenizer.encode(&texts()[0]);
+        let states = encoder.model.forward(
+            &encoding.ids(),
+            &encoding.type_ids(),
+            &encoding.attention_mask(),
+        );
+
+        let expected: Vec<f32> = expected("first_hidden_state")
+            .into_iter()
+            .take(encoding.len())
+            .flatten()
+            .collect();
+        assert_close(&states, &expected, 1e-4);
+    }
+
+    #[test]
+    fn test_sentence_embeddings_match_reference() {
+        let mut encoder = encoder(WEIGHTS);
+        let dim = encoder.dimension();
+        let embeddings = encoder.encode_batch(texts());
+
+        for (actual, expected) in embeddings.chunks_exact(dim).zip(expected("normalized")) {
+            assert_close(actual, &expected, 1e-4);
+        }
+
+        encoder.set_normalize(false);
+        let pooled = encoder.encode_text(&texts()[1]);
+        assert_close(&pooled, &expected("pooled")[1], 1e-4);
+    }
+
+    #[test]
+    fn test_half_precision_weights() {
+        let mut encoder = encoder(WEIGHTS_F16);
+        let embedding = encoder.encode_text(&texts()[3]);
+        assert_close(&embedding, &expected("normalized")[3], 1e-2);
+    }
+
+    #[test]
+    fn test_invalid_weights() {
+        let config: BertConfig = serde_json::from_str(CONFIG).unwrap();
+        let Err(err) = BertModel::from_safetensors(config.clone(), &WEIGHTS[..4]) else {
+            panic!("truncated weights were accepted");
+        };
+        assert!(matches!(err, Error::Format(FormatError::Truncated { .. })));
+
+        let mut wider = config;
+        wider.intermediate_size += 1;
+        let Err(err) = BertModel::from_safetensors(wider, WEIGHTS) else {
+            panic!("weights of the wrong shape were accepted");
+        };
+        assert!(
+            matches!(err, Error::Format(FormatError::TensorShape { ref name, .. }) if name.ends_with("intermediate.dense.weight")),
+            "{}",
+            err
+        );
+
+        for (field, value) in [
+            ("vocab_size", 0),
+            ("type_vocab_size", 0),
+            ("hidden_size", 0),
+            ("intermediate_size", 0),
+            ("num_attention_heads", 0),
+            ("num_hidden_layers", u64::MAX),
+        ] {
+            let mut config: serde_json::Value = serde_json::from_str(CONFIG).unwrap();
+            config[field] = value.into();
+            let config: BertConfig = serde_json::from_value(config).unwrap();
+            let Err(err) = BertModel::from_safetensors(config, WEIGHTS) else {
+                panic!("a config with {} {} was accepted", field, value);
+            };
+            assert_eq!(err.code(), "INVALID_ARGUMENT", "{}", err);
+        }
+
+        let mut config: BertConfig = serde_json::from_str(CONFIG).unwrap();
+        config.hidden_act = "swish".to_string();
+        let Err(err) = BertModel::from_safetensors(config, WEIGHTS) else {
+            panic!("an unsupported activation was accepted");
+        };
+        assert_eq!(err.code(), "INVALID_ARGUMENT");
+        assert!(err.to_string().contains("\"swish\""), "{}", err);
+    }
+
+    #[test]
+    fn test_long_text_chunks_are_pooled() {
+        let mut encoder = encoder(WEIGHTS);
+        let text = texts().join(" ");
+        let document = encoder
+            .encode_document(&text, &ChunkConfig::new(8, 2))
+            .unwrap();
+        assert!(document.chunk_count() > 2);
+
+        // Every chunk is embedded like a standalone text of the same span
+        let chunk = document.chunk(1).unwrap();
+        assert_eq!(&text[chunk.start()..chunk.end()], chunk.text());
+        assert!(chunk.token_count() <= 6);
+        let standalone = encoder.encode_text(&chunk.text());
+        assert_close(&document.chunk_embedding(1).unwrap(), &standalone, 1e-5);
+
+        let chunk_embeddings: Vec<f32> = (0..document.chunk_count())
+            .flat_map(|i| document.chunk_embedding(i).unwrap())
+            .collect();
+        let pooled =
+            crate::embeddings::compute_centroid(&chunk_embeddings, encoder.dimension()).unwrap();
+        let mut expected = pooled;
+        crate::vector_search::normalize_vector(&mut expected);
+        assert_close(&document.embedding(), &expected, 1e-5);
+
+        // Short texts fit in a single window and match `encode_text`
+        let short = encoder.encode_long_text(&texts()[0], 16).unwrap();
+        assert_close(&short, &encoder.encode_text(&texts()[0]), 1e-6);
+    }
+}
@@ -0,0 +1,376 @@
+//! Error type shared by every fallible API
+//!
+//! Each variant has a stable, SCREAMING_SNAKE_CASE code and structured fields.
+//! In Rust it is an ordinary `std::error::Error`; across the WASM boundary it
+//! becomes a `MetaMediaError`, a JavaScript `Error` subclass carrying `code`
+//! and `details` so callers never have to match on message text.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::embeddings::ModelMismatchError;
+use crate::vector_io::FormatError;
+
+#[wasm_bindgen(inline_js = r#"
+export class MetaMediaError extends Error {
+    constructor(code, message, details) {
+        super(message);
+        this.name = "MetaMediaError";
+        this.code = code;
+        this.details = details;
+    }
+}
+
+export function createError(code, message, details) {
+    return new MetaMediaError(code, message, JSON.parse(details));
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(js_name = createError)]
+    fn create_error(code: &str, message: &str, details: &str) -> JsValue;
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const META_MEDIA_ERROR_TS: &str = r#"
+/** Error thrown by every fallible call into this module. */
+export interface MetaMediaError extends Error {
+    name: "MetaMediaError";
+    /** Stable code, e.g. "DIMENSION_MISMATCH" */
+    code: string;
+    /** Structured fields of the error, e.g. { expected: 384, actual: 768 } */
+    details: Record<string, unknown>;
+}
+"#;
+
+/// Errors returned by the search, embedding and I/O APIs
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A vector does not have the dimension the operation expects
+    DimensionMismatch {
+        operation: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// A flat buffer is not a whole number of vectors
+    InvalidBatch {
+        operation: &'static str,
+        len: usize,
+        dimension: usize,
+    },
+    /// The operation needs at least one item
+    EmptyInput { operation: &'static str },
+    /// An index or ID does not refer to a stored item
+    IndexOutOfBounds {
+        operation: &'static str,
+        index: usize,
+        len: usize,
+    },
+    /// A named entity (model, modality, token, operation) does not exist
+    NotFound { kind: &'static str, name: String },
+    /// An argument is outside the range the operation supports
+    InvalidArgument {
+        operation: &'static str,
+        message: String,
+    },
+    /// The object is not in a state that allows the operation
+    InvalidState {
+        operation: &'static str,
+        message: String,
+    },
+    /// Data (JSON, vocabulary, model weights) could not be parsed
+    Parse { what: &'static str, message: String },
+    /// A value could not be serialized (e.g. to JSON)
+    Serialize { what: &'static str, message: String },
+    /// Vectors or an index belong to an incompatible embedding model
+    ModelMismatch(ModelMismatchError),
+    /// An encoded vector file is malformed or unsupported
+    Format(FormatError),
+    /// A host API (window, performance) is not available
+    Unavailable { api: &'static str },
+    /// The caller cancelled the operation through its abort signal
+    Aborted { operation: &'static str },
+    /// A file could not be read or written (native builds)
+    Io { path: String, message: String },
+}
+
+impl Error {
+    /// Stable identifier of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::DimensionMismatch { .. } => "DIMENSION_MISMATCH",
+            Error::InvalidBatch { .. } => "INVALID_BATCH",
+            Error::EmptyInput { .. } => "EMPTY_INPUT",
+            Error::IndexOutOfBounds { .. } => "INDEX_OUT_OF_BOUNDS",
+            Error::NotFound { .. } => "NOT_FOUND",
+            Error::InvalidArgument { .. } => "INVALID_ARGUMENT",
+            Error::InvalidState { .. } => "INVALID_STATE",
+            Error::Parse { .. } => "PARSE_ERROR",
+            Error::Serialize { .. } => "SERIALIZE_ERROR",
+            Error::ModelMismatch(_) => "MODEL_MISMATCH",
+            Error::Format(_) => "FORMAT_ERROR",
+            Error::Unavailable { .. } => "UNAVAILABLE",
+            Error::Aborted { .. } => "ABORTED",
+            Error::Io { .. } => "IO_ERROR",
+        }
+    }
+
+    /// Structured fields, exposed to JavaScript as `error.details`
+    pub fn details(&self) -> Value {
+        match self {
+            Error::DimensionMismatch {
+                operation,
+                expected,
+                actual,
+            } => json!({ "operation": operation, "expected": expected, "actual": actual }),
+            Error::InvalidBatch {
+                operation,
+                len,
+                dimension,
+            } => json!({ "operation": operation, "len": len, "dimension": dimension }),
+            Error::EmptyInput { operation } | Error::Aborted { operation } => {
+                json!({ "operation": operation })
+            }
+            Error::IndexOutOfBounds {
+                operation,
+                index,
+                len,
+            } => json!({ "operation": operation, "index": index, "len": len }),
+            Error::NotFound { kind, name } => json!({ "kind": kind, "name": name }),
+            Error::InvalidArgument { operation, .. } | Error::InvalidState { operation, .. } => {
+                json!({ "operation": operation })
+            }
+            Error::Parse { what, .. } | Error::Serialize { what, .. } => {
+                json!({ "what": what })
+            }
+            Error::ModelMismatch(e) => json!({
+                "field": e.field(),
+                "expected": e.expected().to_string(),
+                "actual": e.actual().to_string(),
+            }),
+            Error::Format(e) => match e {
+                FormatError::Io(message) => json!({ "kind": "io", "message": message }),
+                FormatError::Truncated { context } => {
+                    json!({ "kind": "truncated", "context": context })
+                }
+                FormatError::BadMagic => json!({ "kind": "bad_magic" }),
+                FormatError::InvalidHeader(message) => {
+                    json!({ "kind": "invalid_header", "message": message })
+                }
+                FormatError::UnsupportedDtype(dtype) => {
+                    json!({ "kind": "unsupported_dtype", "dtype": dtype })
+                }
+                FormatError::BigEndian(dtype) => json!({ "kind": "big_endian", "dtype": dtype }),
+                FormatError::FortranOrder => json!({ "kind": "fortran_order" }),
+                FormatError::InvalidShape(shape) => {
+                    json!({ "kind": "invalid_shape", "shape": shape })
+                }
+                FormatError::DimensionMismatch { expected, actual } => {
+                    json!({ "kind": "dimension_mismatch", "expected": expected, "actual": actual })
+                }
+                FormatError::RaggedData { len, dimension } => {
+                    json!({ "kind": "ragged_data", "len": len, "dimension": dimension })
+                }
+                // Non-finite values have no JSON number, so they come out as null
+                FormatError::ValueOutOfRange { value, format } => {
+                    json!({ "kind": "value_out_of_range", "value": value, "format": format })
+                }
+                FormatError::MissingTensor(name) => {
+                    json!({ "kind": "missing_tensor", "name": name })
+                }
+                FormatError::TensorShape {
+                    name,
+                    expected,
+                    actual,
+                } => json!({
+                    "kind": "tensor_shape",
+                    "name": name,
+                    "expected": expected,
+                    "actual": actual,
+                }),
+            },
+            Error::Unavailable { api } => json!({ "api": api }),
+            Error::Io { path, .. } => json!({ "path": path }),
+        }
+    }
+
+    pub(crate) fn parse(what: &'static str, e: impl fmt::Display) -> Self {
+        Error::Parse {
+            what,
+            message: e.to_string(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn io(path: &std::path::Path, e: std::io::Error) -> Self {
+        Error::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        }
+    }
+
+    pub(crate) fn serialize(what: &'static str, e: impl fmt::Display) -> Self {
+        Error::Serialize {
+            what,
+            message: e.to_string(),
+        }
+    }
+
+    /// Reject a flat buffer that is not a whole number of `dimension`-sized vectors
+    pub(crate) fn check_batch(
+        operation: &'static str,
+        data: &[f32],
+        dimension: usize,
+    ) -> Result<(), Error> {
+        if dimension == 0 || !data.len().is_multiple_of(dimension) {
+            return Err(Error::InvalidBatch {
+                operation,
+                len: data.len(),
+                dimension,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reject a vector whose length is not `expected`
+    pub(crate) fn check_dimension(
+        operation: &'static str,
+        expected: usize,
+        actual: usize,
+    ) -> Result<(), Error> {
+        if expected != actual {
+            return Err(Error::DimensionMismatch {
+                operation,
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DimensionMismatch {
+                operation,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Vector dimension mismatch in {}: expected {}, got {}",
+                operation, expected, actual
+            ),
+            Error::InvalidBatch {
+                operation,
+                len,
+                dimension,
+            } => write!(
+                f,
+                "Invalid batch size in {}: {} values is not a whole number of {}-dimensional vectors",
+                operation, len, dimension
+            ),
+            Error::EmptyInput { operation } => write!(f, "{} needs at least one item", operation),
+            Error::IndexOutOfBounds {
+                operation,
+                index,
+                len,
+            } => write!(
+                f,
+                "Index out of bounds in {}: {} is not below {}",
+                operation, index, len
+            ),
+            Error::NotFound { kind, name } => write!(f, "Unknown {}: {}", kind, name),
+            Error::InvalidArgument { operation, message } => {
+                write!(f, "Invalid argument to {}: {}", operation, message)
+            }
+            Error::InvalidState { operation, message } => {
+                write!(f, "Cannot {}: {}", operation, message)
+            }
+            Error::Parse { what, message } => write!(f, "Invalid {}: {}", what, message),
+            Error::Serialize { what, message } => {
+                write!(f, "Cannot serialize {}: {}", what, message)
+            }
+            Error::ModelMismatch(e) => e.fmt(f),
+            Error::Format(e) => e.fmt(f),
+            Error::Unavailable { api } => write!(f, "{} is not available", api),
+            Error::Aborted { operation } => write!(f, "{} was aborted", operation),
+            Error::Io { path, message } => write!(f, "{}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ModelMismatch(e) => Some(e),
+            Error::Format(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ModelMismatchError> for Error {
+    fn from(e: ModelMismatchError) -> Self {
+        Error::ModelMismatch(e)
+    }
+}
+
+impl From<FormatError> for Error {
+    fn from(e: FormatError) -> Self {
+        Error::Format(e)
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(e: Error) -> Self {
+        create_error(e.code(), &e.to_string(), &e.details().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_and_details() {
+        let e = Error::check_dimension("search", 384, 768).unwrap_err();
+        assert_eq!(e.code(), "DIMENSION_MISMATCH");
+        assert_eq!(e.details()["expected"], 384);
+        assert_eq!(e.details()["actual"], 768);
+        assert_eq!(
+            e.to_string(),
+            "Vector dimension mismatch in search: expected 384, got 768"
+        );
+
+        let e = Error::check_batch("add_vectors_batch", &[0.0; 5], 2).unwrap_err();
+        assert_eq!(e.code(), "INVALID_BATCH");
+        assert_eq!(e.details()["len"], 5);
+
+        let e = Error::serialize("k-means model", "non-finite value");
+        assert_eq!(e.code(), "SERIALIZE_ERROR");
+        assert_eq!(e.details()["what"], "k-means model");
+
+        let e = Error::from(FormatError::BadMagic);
+        assert_eq!(e.code(), "FORMAT_ERROR");
+        assert!(std::error::Error::source(&e).is_some());
+        assert_eq!(e.details()["kind"], "bad_magic");
+
+        let e = Error::from(FormatError::TensorShape {
+            name: "pooler.dense.weight".into(),
+            expected: vec![384, 384],
+            actual: vec![384],
+        });
+        assert_eq!(e.details()["kind"], "tensor_shape");
+        assert_eq!(e.details()["name"], "pooler.dense.weight");
+        assert_eq!(e.details()["actual"], json!([384]));
+
+        let e = Error::from(FormatError::ValueOutOfRange {
+            value: 70000.0,
+            format: "float16",
+        });
+        assert_eq!(e.details()["value"], 70000.0);
+        assert_eq!(e.details()["format"], "float16");
+    }
+}
@@ -0,0 +1,408 @@
+//! Persisted index files
+//!
+//! An index file is a safetensors container, so any safetensors reader can
+//! load the vectors. It holds one `(count, dimension)` float32 tensor named
+//! [`VECTORS_TENSOR`] with the vectors as stored (after any transform and
+//! cosine normalization). Everything else lives in the string metadata:
+//!
+//! - `format` = `mms-index` and `version` = `1`
+//! - `dimension`: the dimension of vectors added and queried
+//! - `metric`, `model`, `transform`: JSON, `model` and `transform` optional
+//! - `keys`: optional JSON array of external item keys, one per vector
+//! - `checksum`: FNV-1a 64 of the tensor bytes, in hex
+
+use std::collections::HashMap;
+
+use crate::embeddings::EmbeddingTransform;
+use crate::error::Error;
+use crate::safetensors::{serialize_f32, SafeTensors};
+use crate::vector_search::{DistanceMetric, VectorIndex};
+
+/// Value of the `format` metadata entry
+pub const INDEX_FORMAT: &str = "mms-index";
+
+/// Current version of the layout described in the module docs
+pub const INDEX_VERSION: u32 = 1;
+
+/// Name of the tensor holding the stored vectors
+pub const VECTORS_TENSOR: &str = "vectors";
+
+/// A vector index together with optional external keys for its items
+#[derive(Clone)]
+pub struct IndexFile {
+    index: VectorIndex,
+    keys: Option<Vec<String>>,
+    ids: HashMap<String, usize>,
+}
+
+impl IndexFile {
+    pub fn new(index: VectorIndex) -> Self {
+        IndexFile {
+            index,
+            keys: None,
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Attach one external key per vector, e.g. catalog IDs
+    pub fn with_keys(index: VectorIndex, keys: Vec<String>) -> Result<Self, Error> {
+        if keys.len() != index.size() {
+            return Err(Error::InvalidArgument {
+                operation: "IndexFile::with_keys",
+                message: format!("{} keys for {} vectors", keys.len(), index.size()),
+            });
+        }
+        let ids = keys
+            .iter()
+            .enumerate()
+            .map(|(id, key)| (key.clone(), id))
+            .collect();
+        Ok(IndexFile {
+            index,
+            keys: Some(keys),
+            ids,
+        })
+    }
+
+    pub fn index(&self) -> &VectorIndex {
+        &self.index
+    }
+
+    pub fn index_mut(&mut self) -> &mut VectorIndex {
+        &mut self.index
+    }
+
+    pub fn keys(&self) -> Option<&[String]> {
+        self.keys.as_deref()
+    }
+
+    /// The key of item `id`, or the ID itself when the index has no keys
+    pub fn key(&self, id: usize) -> String {
+        match &self.keys {
+            Some(keys) => keys.get(id).cloned().unwrap_or_default(),
+            None => id.to_string(),
+        }
+    }
+
+    /// The ID of the item with `key`; without keys, `key` is parsed as an ID
+    pub fn find(&self, key: &str) -> Option<usize> {
+        let id = match &self.keys {
+            Some(_) => *self.ids.get(key)?,
+            None => key.parse().ok()?,
+        };
+        (id < self.index.size()).then_some(id)
+    }
+
+    /// Add a vector under `key`, returning its ID
+    ///
+    /// Indexes with keys require one; indexes without keys ignore it.
+    pub fn push(&mut self, key: Option<String>, vector: &[f32]) -> Result<usize, Error> {
+        if self.keys.is_some() && key.is_none() {
+            return Err(Error::InvalidArgument {
+                operation: "IndexFile::push",
+                message: "this index has item keys, so every vector needs one".to_string(),
+            });
+        }
+        let id = self.index.add_vector(vector)?;
+        if let (Some(keys), Some(key)) = (&mut self.keys, key) {
+            // A repeated key resolves to its newest vector
+            self.ids.insert(key.clone(), id);
+            keys.push(key);
+        }
+        Ok(id)
+    }
+
+    /// Replace the vector under `key`, or add it if the key is new
+    ///
+    /// Returns the item's ID and whether it was added. In an index without
+    /// keys, `key` is an ID and may be at most one past the last item.
+    pub fn upsert(&mut self, key: &str, vector: &[f32]) -> Result<(usize, bool), Error> {
+        if let Some(id) = self.find(key) {
+            self.index.set_vector(id, vector)?;
+            return Ok((id, false));
+        }
+        if self.keys.is_some() {
+            return Ok((self.push(Some(key.to_string()), vector)?, true));
+        }
+        match key.parse::<usize>() {
+            Ok(id) if id == self.index.size() => Ok((self.push(None, vector)?, true)),
+            _ => Err(Error::InvalidArgument {
+                operation: "upsert",
+                message: format!(
+                    "this index has no item keys, so the ID must be below {} or equal to it",
+                    self.index.size()
+                ),
+            }),
+        }
+    }
+
+    /// Remove the item under `key`, returning the ID it had
+    ///
+    /// Items after it move down one ID; their keys are unaffected. Indexes
+    /// without keys refuse, since their IDs are the only names items have.
+    pub fn remove(&mut self, key: &str) -> Result<usize, Error> {
+        if self.keys.is_none() {
+            return Err(Error::InvalidState {
+                operation: "IndexFile::remove",
+                message: "this index has no item keys to remove by".to_string(),
+            });
+        }
+        let id = self.find(key).ok_or_else(|| Error::NotFound {
+            kind: "item",
+            name: key.to_string(),
+        })?;
+        self.index.remove_vector(id)?;
+        if let Some(keys) = &mut self.keys {
+            keys.remove(id);
+            self.ids = keys
+                .iter()
+                .enumerate()
+                .map(|(id, key)| (key.clone(), id))
+                .collect();
+        }
+        Ok(id)
+    }
+
+    /// Encode the index as a safetensors buffer
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let data: Vec<f32> = (0..self.index.size())
+            .filter_map(|id| self.index.stored(id))
+            .flatten()
+            .copied()
+            .collect();
+        let stored_dimension = self.stored_dimension();
+
+        let mut metadata = HashMap::new();
+        metadata.insert("format".to_string(), INDEX_FORMAT.to_string());
+        metadata.insert("version".to_string(), INDEX_VERSION.to_string());
+        metadata.insert("dimension".to_string(), self.index.dimension().to_string());
+        metadata.insert("metric".to_string(), to_json(&self.index.metric()));
+        if let Some(model) = self.index.model() {
+            metadata.insert("model".to_string(), to_json(&model));
+        }
+        if let Some(transform) = self.index.transform() {
+            metadata.insert("transform".to_string(), to_json(transform));
+        }
+        if let Some(keys) = &self.keys {
+            metadata.insert("keys".to_string(), to_json(keys));
+        }
+        metadata.insert("checksum".to_string(), format!("{:016x}", checksum(&data)));
+
+        let shape = [self.index.size(), stored_dimension];
+        serialize_f32(&[(VECTORS_TENSOR, &shape, &data)], &metadata)
+    }
+
+    /// Decode an index file, rejecting it if the checksum does not match
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let bad = |message: String| Error::Parse {
+            what: "index file",
+            message,
+        };
+        let tensors = SafeTensors::parse(bytes)?;
+        let metadata = tensors.metadata();
+        let field = |name: &str| {
+            metadata
+                .get(name)
+                .map(String::as_str)
+                .ok_or_else(|| bad(format!("missing '{}' metadata", name)))
+        };
+
+        if field("format")? != INDEX_FORMAT {
+            return Err(bad(format!("not an {} file", INDEX_FORMAT)));
+        }
+        let version: u32 = field("version")?
+            .parse()
+            .map_err(|e| Error::parse("index file version", e))?;
+        if version != INDEX_VERSION {
+            return Err(bad(format!(
+                "version {} is not supported (expected {})",
+                version, INDEX_VERSION
+            )));
+        }
+        let dimension: usize = field("dimension")?
+            .parse()
+            .map_err(|e| Error::parse("index file dimension", e))?;
+        let metric: DistanceMetric = from_json("index file metric", field("metric")?)?;
+
+        let mut index = VectorIndex::new(dimension, metric);
+        if let Some(transform) = metadata.get("transform") {
+            let transform: EmbeddingTransform = from_json("index file transform", transform)?;
+            index.set_transform(transform)?;
+        }
+        if let Some(model) = metadata.get("model") {
+            index.set_model(&from_json("index file model", model)?)?;
+        }
+
+        let (shape, data) = tensors.to_f32(VECTORS_TENSOR)?;
+        let expected = format!("{:016x}", checksum(&data));
+        if field("checksum")? != expected {
+            return Err(bad(format!(
+                "checksum mismatch: stored {}, computed {}",
+                field("checksum")?,
+                expected
+            )));
+        }
+        let stored_dimension = index
+            .transform()
+            .map_or(dimension, EmbeddingTransform::output_dimension);
+        match shape.as_slice() {
+            [_, d] if *d == stored_dimension => {}
+            [0] | [0, _] => {}
+            _ => {
+                return Err(bad(format!(
+                    "expected (count, {}) vectors, got shape {:?}",
+                    stored_dimension, shape
+                )))
+            }
+        }
+        for vector in data.chunks_exact(stored_dimension.max(1)) {
+            index.push_stored(vector.to_vec());
+        }
+
+        match metadata.get("keys") {
+            Some(keys) => IndexFile::with_keys(index, from_json("index file keys", keys)?),
+            None => Ok(IndexFile::new(index)),
+        }
+    }
+
+    /// Whether `bytes` look like an index file rather than plain vectors
+    pub fn is_index_file(bytes: &[u8]) -> bool {
+        SafeTensors::parse(bytes)
+            .map(|t| t.metadata().get("format").map(String::as_str) == Some(INDEX_FORMAT))
+            .unwrap_or(false)
+    }
+
+    /// Consistency problems a checksum cannot catch, empty for a healthy index
+    ///
+    /// Flags non-finite values, cosine vectors that are not unit length and
+    /// duplicate keys.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for id in 0..self.index.size() {
+            let Some(vector) = self.index.stored(id) else {
+                continue;
+            };
+            if vector.iter().any(|v| !v.is_finite()) {
+                problems.push(format!("vector {} has non-finite values", id));
+                continue;
+            }
+            if self.index.metric() == DistanceMetric::Cosine {
+                let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+                // Zero vectors stay zero under normalization
+                if norm != 0.0 && (norm - 1.0).abs() > 1e-3 {
+                    problems.push(format!("vector {} has norm {} in a cosine index", id, norm));
+                }
+            }
+        }
+        if let Some(keys) = &self.keys {
+            let mut seen = HashMap::new();
+            for (id, key) in keys.iter().enumerate() {
+                if let Some(first) = seen.insert(key.as_str(), id) {
+                    problems.push(format!(
+                        "key {:?} is used by vectors {} and {}",
+                        key, first, id
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// Dimension of the vectors as stored, after any transform
+    pub fn stored_dimension(&self) -> usize {
+        self.index.stored_dimension()
+    }
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(what: &'static str, json: &str) -> Result<T, Error> {
+    serde_json::from_str(json).map_err(|e| Error::parse(what, e))
+}
+
+/// FNV-1a over the little-endian bytes of `data`
+fn checksum(data: &[f32]) -> u64 {
+    data.iter()
+        .flat_map(|v| v.to_le_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::EmbeddingModelInfo;
+
+    #[test]
+    fn test_round_trip_and_corruption() {
+        let model = EmbeddingModelInfo::lookup("minilm").unwrap();
+        let mut index = VectorIndex::new(model.dimension(), DistanceMetric::Cosine);
+        index.set_model(&model).unwrap();
+        let mut file = IndexFile::with_keys(index, Vec::new()).unwrap();
+        for key in ["tt0111161", "tt0068646"] {
+            let vector: Vec<f32> = (0..model.dimension())
+                .map(|i| (i + key.len()) as f32)
+                .collect();
+            file.push(Some(key.to_string()), &vector).unwrap();
+        }
+        assert!(file.push(None, &vec![0.0; model.dimension()]).is_err());
+
+        let bytes = file.to_bytes();
+        assert!(IndexFile::is_index_file(&bytes));
+        let mut loaded = IndexFile::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.index().size(), 2);
+        assert_eq!(loaded.index().model(), Some(model));
+        assert_eq!(loaded.find("tt0068646"), Some(1));
+        assert_eq!(loaded.key(0), "tt0111161");
+        assert_eq!(loaded.index().get_vector(1), file.index().get_vector(1));
+        assert!(loaded.problems().is_empty());
+
+        let replacement = vec![1.0; loaded.index().dimension()];
+        assert_eq!(
+            loaded.upsert("tt0068646", &replacement).unwrap(),
+            (1, false)
+        );
+        assert_eq!(loaded.upsert("tt0110912", &replacement).unwrap(), (2, true));
+        assert_eq!(loaded.index().get_vector(1), loaded.index().get_vector(2));
+        assert_eq!(loaded.remove("tt0111161").unwrap(), 0);
+        assert_eq!(loaded.find("tt0110912"), Some(1));
+        assert!(loaded.remove("tt0111161").is_err());
+
+        let mut keyless = IndexFile::new(VectorIndex::new(2, DistanceMetric::Cosine));
+        keyless.push(None, &[1.0, 0.0]).unwrap();
+        keyless.push(None, &[0.0, 1.0]).unwrap();
+        let Err(err) = keyless.remove("0") else {
+            panic!("a keyless index removed an item");
+        };
+        assert_eq!(err.code(), "INVALID_STATE");
+        assert_eq!(keyless.index().size(), 2);
+
+        let mut corrupted = bytes.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0x40;
+        let Err(err) = IndexFile::from_bytes(&corrupted) else {
+            panic!("corrupted file was accepted");
+        };
+        assert!(err.to_string().contains("checksum mismatch"), "{}", err);
+
+        let mut plain = Vec::new();
+        crate::vector_io::write_vectors(
+            &mut plain,
+            &[1.0, 2.0],
+            2,
+            crate::VectorFormat::Safetensors,
+        )
+        .unwrap();
+        assert!(!IndexFile::is_index_file(&plain));
+        assert!(matches!(
+            IndexFile::from_bytes(&plain),
+            Err(Error::Parse {
+                what: "index file",
+                ..
+            })
+        ));
+    }
+}
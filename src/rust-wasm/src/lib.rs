@@ -6,20 +6,75 @@
 //!
 //! - Fast cosine similarity computation
 //! - Efficient vector search with multiple distance metrics
+//! - "More like these, less like that" example queries
 //! - WASM-optimized data structures
 //! - Embedding generation utilities
-//! - Batch processing support
+//! - WordPiece and BPE tokenization for transformer models
+//! - CPU sentence embedding inference for BERT-style encoders
+//! - Overlapping chunking and pooled embeddings for long texts
+//! - Streaming, mergeable embedding statistics
+//! - Standardization and whitening transforms for embeddings
+//! - Drift detection between embedding snapshots
+//! - Multimodal fusion of text, image and audio embeddings
+//! - Embedding model registry with index compatibility checks
+//! - Import/export of .npy, .fvecs/.bvecs and safetensors embedding files
+//! - Seeded 2D projection of embeddings for the discovery map
+//! - K-means and mini-batch k-means clustering
+//! - Near-duplicate grouping across catalog sources
+//! - Batch processing support with serializable preprocessing pipelines
+//! - Cancellable, non-blocking index building with progress callbacks
+//! - Optional multi-threaded search (`parallel`, `wasm-threads` features)
+//! - Latency histograms and counters exported as JSON or Prometheus text
+//! - Search timing in browsers, Web Workers, Node and native builds
+//! - Structured errors with stable codes (`MetaMediaError` in JavaScript)
 
 use wasm_bindgen::prelude::*;
 
-pub mod vector_search;
+pub mod build;
+pub mod chunking;
+pub mod clock;
+pub mod clustering;
+pub mod dedup;
 pub mod embeddings;
-pub mod wasm_bindings;
+pub mod encoder;
+pub mod error;
+pub mod index_file;
+pub mod metrics;
+pub mod parallel;
+pub mod pipeline;
+pub mod projection;
+pub mod safetensors;
+pub mod tokenizer;
 pub mod utils;
+pub mod vector_io;
+pub mod vector_search;
+pub mod wasm_bindings;
 
 // Re-export main types
-pub use vector_search::{VectorIndex, DistanceMetric, SearchResult};
-pub use embeddings::{EmbeddingGenerator, EmbeddingConfig};
+pub use build::{BuildOptions, IndexBuild};
+pub use chunking::{
+    chunk_text, ChunkConfig, DocumentEmbedding, DocumentIndex, DocumentMatch, TextChunk,
+};
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use clustering::{KMeans, KMeansConfig};
+pub use dedup::{normalize_title, CatalogMetadata, DuplicateConfig, DuplicateGroup};
+pub use embeddings::{
+    drift_report, drift_report_with_config, DriftConfig, DriftReport, EmbeddingConfig,
+    EmbeddingGenerator, EmbeddingModelInfo, EmbeddingSnapshot, EmbeddingStats,
+    EmbeddingStatsAccumulator, EmbeddingTransform, FusionInput, FusionModel, FusionStrategy,
+    ModelMismatchError, PoolingStrategy, Standardizer, Whitener, WhiteningMode,
+};
+pub use encoder::{BertConfig, BertModel, SentenceEncoder};
+pub use error::Error;
+pub use index_file::IndexFile;
+pub use metrics::{Histogram, MetricsRegistry};
+pub use parallel::{init_threads, threads_enabled};
+pub use pipeline::{Pipeline, PipelineOutput};
+pub use projection::{Projection, ProjectionConfig};
+pub use tokenizer::{Encoding, Tokenizer};
+pub use utils::VectorBuffer;
+pub use vector_io::{FormatError, NpyDtype, VectorFormat};
+pub use vector_search::{DistanceMetric, SearchResult, VectorIndex};
 pub use wasm_bindings::*;
 
 /// Initialize the WASM module
@@ -0,0 +1,477 @@
+//! Latency histograms, counters and gauges
+//!
+//! Every component records into a per-thread [`MetricsRegistry`] (one per WASM
+//! instance or Web Worker): the search engine times searches and adds, the
+//! embedding generator times pooling and counts cache hits. The registry
+//! exports to JSON and to the Prometheus text format, with constant labels
+//! such as a device class attached to every series.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_json::{json, Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+
+/// Bits of sub-bucket precision; each power of two is split into 32 buckets,
+/// so a recorded value is off by at most 1/32 (about 3%)
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Quantiles included in both exports, with their JSON keys
+const EXPORTED_QUANTILES: [(f64, &str); 4] = [
+    (0.5, "p50_ms"),
+    (0.9, "p90_ms"),
+    (0.95, "p95_ms"),
+    (0.99, "p99_ms"),
+];
+
+/// HDR-style latency histogram with log-linear buckets over microseconds
+///
+/// Memory grows with the log of the largest value, not with the number of
+/// samples, and quantiles keep a bounded relative error at every scale.
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum_ms: f64,
+    min_us: u64,
+    max_us: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a duration in milliseconds; negative and NaN durations count as 0
+    pub fn record(&mut self, ms: f64) {
+        let us = (ms.max(0.0) * 1000.0).round() as u64;
+        let bucket = bucket_index(us);
+        if bucket >= self.counts.len() {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+        self.min_us = if self.count == 0 {
+            us
+        } else {
+            self.min_us.min(us)
+        };
+        self.max_us = self.max_us.max(us);
+        self.count += 1;
+        self.sum_ms += us as f64 / 1000.0;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn sum_ms(&self) -> f64 {
+        self.sum_ms
+    }
+
+    pub fn min_ms(&self) -> f64 {
+        self.min_us as f64 / 1000.0
+    }
+
+    pub fn max_ms(&self) -> f64 {
+        self.max_us as f64 / 1000.0
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum_ms / self.count as f64
+        }
+    }
+
+    /// Smallest recorded latency that at least `q` of the samples do not exceed
+    ///
+    /// Reported as the upper end of its bucket, clamped to the observed range.
+    pub fn quantile(&self, q: f64) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = bucket_lower_bound(bucket + 1).saturating_sub(1);
+                return upper.clamp(self.min_us, self.max_us) as f64 / 1000.0;
+            }
+        }
+        self.max_ms()
+    }
+}
+
+/// Bucket holding `value`: exact below `2 * SUB_BUCKETS`, then
+/// `SUB_BUCKETS` equal-width buckets per power of two
+pub(crate) fn bucket_index(value: u64) -> usize {
+    if value < 2 * SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    let shift = 63 - value.leading_zeros() - SUB_BUCKET_BITS;
+    shift as usize * SUB_BUCKETS + (value >> shift) as usize
+}
+
+/// Smallest value that falls into `bucket`
+pub(crate) fn bucket_lower_bound(bucket: usize) -> u64 {
+    if bucket < 2 * SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = bucket / SUB_BUCKETS - 1;
+    ((bucket % SUB_BUCKETS + SUB_BUCKETS) as u64) << shift
+}
+
+/// Named latency histograms, counters and gauges
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    labels: BTreeMap<String, String>,
+    latencies: BTreeMap<String, Histogram>,
+    counters: BTreeMap<String, u64>,
+    gauges: BTreeMap<String, f64>,
+    /// Gauges owned by one search engine, by engine name then gauge
+    engine_gauges: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+#[wasm_bindgen]
+impl MetricsRegistry {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a constant label (e.g. `device_class`) to every exported series
+    pub fn set_label(&mut self, name: &str, value: &str) {
+        self.labels.insert(sanitize_name(name), value.to_string());
+    }
+
+    /// Record one `operation` (search, add, pool, ...) taking `ms` milliseconds
+    pub fn record_latency(&mut self, operation: &str, ms: f64) {
+        self.latencies
+            .entry(operation.to_string())
+            .or_default()
+            .record(ms);
+    }
+
+    /// Add `by` to a counter such as `vectors_scanned` or `cache_hits`
+    pub fn increment(&mut self, counter: &str, by: usize) {
+        *self.counters.entry(counter.to_string()).or_default() += by as u64;
+    }
+
+    /// Set a gauge such as `index_size` to its current value
+    pub fn set_gauge(&mut self, gauge: &str, value: f64) {
+        self.gauges.insert(gauge.to_string(), value);
+    }
+
+    /// Set a gauge such as `index_size` for the search engine named `engine`
+    ///
+    /// Exported with an `engine` label, so engines sharing a registry never
+    /// overwrite each other's values.
+    pub fn set_engine_gauge(&mut self, engine: &str, gauge: &str, value: f64) {
+        self.engine_gauges
+            .entry(engine.to_string())
+            .or_default()
+            .insert(gauge.to_string(), value);
+    }
+
+    /// A latency quantile for `operation` in milliseconds, if it was ever recorded
+    pub fn latency_quantile(&self, operation: &str, q: f64) -> Option<f64> {
+        self.latencies.get(operation).map(|h| h.quantile(q))
+    }
+
+    pub fn counter(&self, counter: &str) -> f64 {
+        self.counters.get(counter).copied().unwrap_or(0) as f64
+    }
+
+    pub fn gauge(&self, gauge: &str) -> Option<f64> {
+        self.gauges.get(gauge).copied()
+    }
+
+    pub fn engine_gauge(&self, engine: &str, gauge: &str) -> Option<f64> {
+        self.engine_gauges.get(engine)?.get(gauge).copied()
+    }
+
+    /// Drop every recorded value, keeping the labels
+    pub fn reset(&mut self) {
+        self.latencies.clear();
+        self.counters.clear();
+        self.gauges.clear();
+        self.engine_gauges.clear();
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(&self.to_value()).map_err(|e| Error::serialize("metrics", e))
+    }
+
+    /// Render every series in the Prometheus text exposition format
+    ///
+    /// Latencies become `mms_operation_latency_ms` summaries with an
+    /// `operation` label, counters get a `_total` suffix, and every name is
+    /// prefixed with `mms_`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        if !self.latencies.is_empty() {
+            let name = "mms_operation_latency_ms";
+            out.push_str("# HELP mms_operation_latency_ms Operation latency in milliseconds\n");
+            out.push_str("# TYPE mms_operation_latency_ms summary\n");
+            for (operation, h) in &self.latencies {
+                let op = [("operation", operation.as_str())];
+                for (q, _) in EXPORTED_QUANTILES {
+                    let quantile = q.to_string();
+                    let labels = self.render_labels(&[op[0], ("quantile", &quantile)]);
+                    let _ = writeln!(out, "{}{} {}", name, labels, h.quantile(q));
+                }
+                let labels = self.render_labels(&op);
+                let _ = writeln!(out, "{}_sum{} {}", name, labels, h.sum_ms());
+                let _ = writeln!(out, "{}_count{} {}", name, labels, h.count());
+            }
+        }
+        let labels = self.render_labels(&[]);
+        for (counter, value) in &self.counters {
+            let name = format!("mms_{}_total", sanitize_name(counter));
+            let _ = writeln!(out, "# TYPE {} counter\n{}{} {}", name, name, labels, value);
+        }
+        for (gauge, value) in &self.gauges {
+            let name = format!("mms_{}", sanitize_name(gauge));
+            let _ = writeln!(out, "# TYPE {} gauge\n{}{} {}", name, name, labels, value);
+        }
+        let mut by_gauge: BTreeMap<&str, Vec<(&str, f64)>> = BTreeMap::new();
+        for (engine, gauges) in &self.engine_gauges {
+            for (gauge, &value) in gauges {
+                by_gauge.entry(gauge).or_default().push((engine, value));
+            }
+        }
+        for (gauge, series) in by_gauge {
+            let name = format!("mms_{}", sanitize_name(gauge));
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            for (engine, value) in series {
+                let labels = self.render_labels(&[("engine", engine)]);
+                let _ = writeln!(out, "{}{} {}", name, labels, value);
+            }
+        }
+        out
+    }
+}
+
+impl MetricsRegistry {
+    /// The latency histogram for `operation`, if it was ever recorded
+    pub fn latency(&self, operation: &str) -> Option<&Histogram> {
+        self.latencies.get(operation)
+    }
+
+    /// Drop the gauges of a search engine that no longer exists
+    pub(crate) fn remove_engine(&mut self, engine: &str) {
+        self.engine_gauges.remove(engine);
+    }
+
+    fn to_value(&self) -> Value {
+        let latency: Map<String, Value> = self
+            .latencies
+            .iter()
+            .map(|(operation, h)| {
+                let mut summary = json!({
+                    "count": h.count(),
+                    "sum_ms": h.sum_ms(),
+                    "min_ms": h.min_ms(),
+                    "max_ms": h.max_ms(),
+                    "mean_ms": h.mean_ms(),
+                });
+                for (q, key) in EXPORTED_QUANTILES {
+                    summary[key] = json!(h.quantile(q));
+                }
+                (operation.clone(), summary)
+            })
+            .collect();
+        json!({
+            "labels": self.labels,
+            "latency": latency,
+            "counters": self.counters,
+            "gauges": self.gauges,
+            "engines": self.engine_gauges,
+        })
+    }
+
+    /// `{a="1",b="2"}` from the constant labels plus `extra`, or "" if there are none
+    fn render_labels(&self, extra: &[(&str, &str)]) -> String {
+        let pairs: Vec<String> = self
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .chain(extra.iter().copied())
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+            .collect();
+        if pairs.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", pairs.join(","))
+        }
+    }
+}
+
+/// Replace characters Prometheus does not allow in names with `_`
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+thread_local! {
+    static REGISTRY: RefCell<MetricsRegistry> = RefCell::new(MetricsRegistry::new());
+}
+
+/// Run `f` with this thread's registry
+pub fn with_registry<R>(f: impl FnOnce(&mut MetricsRegistry) -> R) -> R {
+    REGISTRY.with(|registry| f(&mut registry.borrow_mut()))
+}
+
+/// Snapshot of this instance's registry, with memory gauges refreshed
+#[wasm_bindgen]
+pub fn metrics() -> MetricsRegistry {
+    with_registry(|registry| {
+        registry.set_gauge("wasm_memory_bytes", wasm_memory_bytes() as f64);
+        registry.set_gauge("js_heap_bytes", js_heap_bytes() as f64);
+        registry.clone()
+    })
+}
+
+/// Attach a constant label to every series this instance exports
+#[wasm_bindgen]
+pub fn set_metrics_label(name: &str, value: &str) {
+    with_registry(|registry| registry.set_label(name, value));
+}
+
+/// Clear this instance's recorded metrics
+#[wasm_bindgen]
+pub fn reset_metrics() {
+    with_registry(MetricsRegistry::reset);
+}
+
+/// Size of the WASM linear memory in bytes (0 on native builds)
+pub fn wasm_memory_bytes() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        use wasm_bindgen::JsCast;
+
+        wasm_bindgen::memory()
+            .unchecked_into::<js_sys::WebAssembly::Memory>()
+            .buffer()
+            .unchecked_into::<js_sys::ArrayBuffer>()
+            .byte_length() as usize
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+/// JS heap in use where the host reports it (Chromium's `performance.memory`), else 0
+pub fn js_heap_bytes() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let get = |target: &JsValue, key: &str| {
+            js_sys::Reflect::get(target, &JsValue::from_str(key))
+                .ok()
+                .filter(|value| !value.is_undefined() && !value.is_null())
+        };
+        get(&js_sys::global(), "performance")
+            .and_then(|performance| get(&performance, "memory"))
+            .and_then(|memory| get(&memory, "usedJSHeapSize"))
+            .and_then(|used| used.as_f64())
+            .unwrap_or(0.0) as usize
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_quantiles() {
+        for value in [0, 1, 63, 64, 65, 127, 128, 1000, 123_456, u64::MAX >> 1] {
+            let bucket = bucket_index(value);
+            assert!(bucket_lower_bound(bucket) <= value, "{}", value);
+            assert!(bucket_lower_bound(bucket + 1) > value, "{}", value);
+        }
+
+        let mut h = Histogram::new();
+        // 1..=1000 ms
+        for i in 1..=1000 {
+            h.record(i as f64);
+        }
+        assert_eq!(h.count(), 1000);
+        assert_eq!((h.min_ms(), h.max_ms()), (1.0, 1000.0));
+        assert!((h.mean_ms() - 500.5).abs() < 1e-9);
+        for (q, exact) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0)] {
+            let estimate = h.quantile(q);
+            assert!(
+                estimate >= exact && estimate <= exact * (1.0 + 1.0 / 32.0),
+                "{}",
+                estimate
+            );
+        }
+        assert_eq!(h.quantile(1.0), 1000.0);
+        assert_eq!(Histogram::new().quantile(0.5), 0.0);
+    }
+
+    #[test]
+    fn test_exports() {
+        let mut registry = MetricsRegistry::new();
+        registry.set_label("device_class", "mobile \"low\"");
+        registry.record_latency("search", 2.0);
+        registry.record_latency("search", 4.0);
+        registry.increment("vectors_scanned", 300);
+        registry.set_gauge("wasm_memory_bytes", 65536.0);
+        registry.set_engine_gauge("catalog", "index_size", 150.0);
+        registry.set_engine_gauge("recent", "index_size", 20.0);
+
+        let json: Value = serde_json::from_str(&registry.to_json().unwrap()).unwrap();
+        assert_eq!(json["latency"]["search"]["count"], 2);
+        assert_eq!(json["latency"]["search"]["p95_ms"], 4.0);
+        assert_eq!(json["counters"]["vectors_scanned"], 300);
+        assert_eq!(json["labels"]["device_class"], "mobile \"low\"");
+        assert_eq!(json["engines"]["recent"]["index_size"], 20.0);
+
+        let text = registry.to_prometheus();
+        assert!(text.contains("# TYPE mms_operation_latency_ms summary\n"));
+        assert!(text.contains(
+            "mms_operation_latency_ms{device_class=\"mobile \\\"low\\\"\",operation=\"search\",quantile=\"0.95\"} 4\n"
+        ));
+        assert!(text.contains("mms_operation_latency_ms_count{device_class=\"mobile \\\"low\\\"\",operation=\"search\"} 2\n"));
+        assert!(text.contains("# TYPE mms_vectors_scanned_total counter\n"));
+        assert!(text.contains("mms_wasm_memory_bytes{device_class=\"mobile \\\"low\\\"\"} 65536\n"));
+        assert_eq!(text.matches("# TYPE mms_index_size gauge\n").count(), 1);
+        assert!(text.contains(
+            "mms_index_size{device_class=\"mobile \\\"low\\\"\",engine=\"catalog\"} 150\n"
+        ));
+        assert!(text.contains(
+            "mms_index_size{device_class=\"mobile \\\"low\\\"\",engine=\"recent\"} 20\n"
+        ));
+
+        registry.reset();
+        assert!(registry.to_prometheus().is_empty());
+        assert_eq!(registry.latency_quantile("search", 0.5), None);
+    }
+}
@@ -0,0 +1,88 @@
+//! Optional multi-threaded scoring
+//!
+//! With the `parallel` feature, large brute-force and batch searches are split
+//! across a rayon thread pool. Native builds use rayon's global pool straight
+//! away. `wasm-threads` builds run the pool in Web Workers over shared memory,
+//! which only exists on cross-origin isolated pages; until [`init_threads`]
+//! has started the workers, and on pages where it cannot, every search runs
+//! on the calling thread with identical results. Waiting on the pool blocks,
+//! which browsers forbid on a page's main thread, so only searches made from
+//! a Worker use it.
+//!
+//! `wasm-threads` needs a nightly toolchain and a rebuilt standard library;
+//! `./build.sh --threads` sets both up.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+/// Below this many vectors, splitting the work costs more than it saves
+pub const PARALLEL_MIN_VECTORS: usize = 4096;
+
+static THREADS_READY: AtomicBool =
+    AtomicBool::new(cfg!(all(feature = "parallel", not(target_arch = "wasm32"))));
+
+/// Whether searches may currently use the thread pool
+///
+/// Always `false` on a page's main thread, even once the pool is running.
+#[wasm_bindgen]
+pub fn threads_enabled() -> bool {
+    THREADS_READY.load(Ordering::Relaxed) && can_block()
+}
+
+/// Whether the calling thread may block on `Atomics.wait` (not a page's main thread)
+#[cfg(all(feature = "wasm-threads", target_arch = "wasm32"))]
+fn can_block() -> bool {
+    thread_local! {
+        // `WorkerGlobalScope` is only exposed inside Workers
+        static IN_WORKER: bool =
+            js_sys::Reflect::has(&js_sys::global(), &JsValue::from_str("WorkerGlobalScope"))
+                .unwrap_or(false);
+    }
+    IN_WORKER.with(|in_worker| *in_worker)
+}
+
+#[cfg(not(all(feature = "wasm-threads", target_arch = "wasm32")))]
+fn can_block() -> bool {
+    true
+}
+
+/// Whether work over `len` items should be split across threads
+#[cfg(feature = "parallel")]
+pub(crate) fn should_split(len: usize) -> bool {
+    len >= PARALLEL_MIN_VECTORS && threads_enabled()
+}
+
+/// Start a pool of `threads` Web Workers; resolves to whether threading is active
+///
+/// Resolves to `false` without starting workers when the build has no thread
+/// support or the page is not cross-origin isolated (no `SharedArrayBuffer`).
+/// Call it from the Worker that runs searches: on the main thread the pool
+/// starts, but searches made there stay single-threaded.
+#[wasm_bindgen]
+pub async fn init_threads(threads: usize) -> bool {
+    #[cfg(all(feature = "wasm-threads", target_arch = "wasm32"))]
+    {
+        if THREADS_READY.load(Ordering::Relaxed) {
+            return threads_enabled();
+        }
+        let isolated =
+            js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crossOriginIsolated"))
+                .map(|v| v.is_truthy())
+                .unwrap_or(false);
+        if !isolated {
+            return false;
+        }
+        let started =
+            wasm_bindgen_futures::JsFuture::from(wasm_bindgen_rayon::init_thread_pool(threads))
+                .await
+                .is_ok();
+        THREADS_READY.store(started, Ordering::Relaxed);
+        threads_enabled()
+    }
+    #[cfg(not(all(feature = "wasm-threads", target_arch = "wasm32")))]
+    {
+        let _ = threads;
+        threads_enabled()
+    }
+}
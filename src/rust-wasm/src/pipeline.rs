@@ -0,0 +1,610 @@
+//! Serializable preprocessing pipelines
+//!
+//! A [`Pipeline`] chains vector operations (normalize, mean-center, PCA or
+//! random projection, clip, quantize, dedupe). Steps that learn parameters are
+//! fitted once on a sample; the fitted pipeline serializes to JSON so the
+//! backend and the browser apply exactly the same preprocessing. Input is
+//! streamed through the steps in chunks so progress can be reported.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use wasm_bindgen::prelude::*;
+
+use crate::embeddings::{symmetric_eigen, EmbeddingStatsAccumulator};
+use crate::error::Error;
+use crate::utils::{check_len, SeededRng};
+use crate::vector_search::{dot_product, normalize_vector};
+
+/// Largest random projection matrix (`components × input` values) a pipeline will generate
+pub const MAX_PROJECTION_VALUES: usize = 1 << 24;
+
+/// One operation of a pipeline, with its fitted parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Step {
+    Normalize,
+    MeanCenter {
+        mean: Option<Vec<f32>>,
+    },
+    Pca {
+        components: usize,
+        mean: Option<Vec<f32>>,
+        /// Row-major `components × input` matrix of principal axes
+        basis: Option<Vec<f32>>,
+    },
+    /// Gaussian projection regenerated from `seed`, so it never needs fitting
+    RandomProjection {
+        components: usize,
+        seed: u64,
+    },
+    Clip {
+        min: f32,
+        max: f32,
+    },
+    /// Snap values to `2^bits` evenly spaced levels over the fitted range
+    Quantize {
+        bits: u8,
+        range: Option<(f32, f32)>,
+    },
+    /// Drop vectors whose cosine similarity to an earlier kept vector reaches `threshold`
+    Dedupe {
+        threshold: f32,
+    },
+}
+
+impl Step {
+    fn output_dimension(&self, input: usize) -> usize {
+        match self {
+            Step::Pca { components, .. } => (*components).clamp(1, input.max(1)),
+            Step::RandomProjection { components, .. } => *components,
+            _ => input,
+        }
+    }
+
+    fn is_fitted(&self) -> bool {
+        match self {
+            Step::MeanCenter { mean } => mean.is_some(),
+            Step::Pca { basis, .. } => basis.is_some(),
+            Step::Quantize { range, .. } => range.is_some(),
+            _ => true,
+        }
+    }
+
+    /// Check the parameters against the dimension of the vectors reaching this step
+    ///
+    /// Steps built through [`Pipeline`]'s methods always pass; this guards
+    /// pipelines restored from JSON.
+    fn validate(&self, input: usize) -> Result<(), String> {
+        let check_fitted = |name: &str, values: &Option<Vec<f32>>, expected: usize| match values {
+            Some(values) => check_len(name, values.len(), expected),
+            None => Ok(()),
+        };
+        match self {
+            Step::MeanCenter { mean } => check_fitted("mean", mean, input),
+            Step::Pca { mean, basis, .. } => {
+                check_fitted("mean", mean, input)?;
+                check_fitted("basis", basis, self.output_dimension(input) * input)
+            }
+            Step::RandomProjection { components: 0, .. } => {
+                Err("components must be at least 1".to_string())
+            }
+            Step::RandomProjection { components, .. }
+                if components
+                    .checked_mul(input)
+                    .is_none_or(|len| len > MAX_PROJECTION_VALUES) =>
+            {
+                Err(format!(
+                    "a {}×{} projection exceeds the {} values a pipeline can hold",
+                    components, input, MAX_PROJECTION_VALUES
+                ))
+            }
+            // NaN bounds compare as unordered, and are rejected too
+            Step::Clip { min, max } if !is_ordered(*min, *max) => {
+                Err(format!("min {} is not at most max {}", min, max))
+            }
+            Step::Quantize { bits, .. } if !(1..=16).contains(bits) => {
+                Err(format!("bits {} is not between 1 and 16", bits))
+            }
+            Step::Quantize {
+                range: Some((lo, hi)),
+                ..
+            } if !is_ordered(*lo, *hi) => Err(format!("range ({}, {}) is reversed", lo, hi)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Learn this step's parameters from `sample` (vectors of `dimension` values)
+    fn fit(&mut self, sample: &[f32], dimension: usize) -> Result<(), Error> {
+        match self {
+            Step::MeanCenter { mean } => {
+                let mut accumulator = EmbeddingStatsAccumulator::new(dimension);
+                accumulator.update(sample)?;
+                *mean = Some(accumulator.mean());
+            }
+            Step::Pca {
+                components,
+                mean,
+                basis,
+            } => {
+                let mut accumulator = EmbeddingStatsAccumulator::with_covariance_dims(
+                    dimension,
+                    (0..dimension).collect(),
+                )?;
+                accumulator.update(sample)?;
+                let covariance = accumulator.covariance().iter().map(|&c| c as f64).collect();
+                let (_, eigenvectors) = symmetric_eigen(covariance, dimension);
+                let k = (*components).clamp(1, dimension);
+                // Eigenvectors are columns; store them as rows, largest variance first
+                let rows = (0..k)
+                    .flat_map(|c| (0..dimension).map(move |r| (r, c)))
+                    .map(|(r, c)| eigenvectors[r * dimension + c] as f32)
+                    .collect();
+                *mean = Some(accumulator.mean());
+                *basis = Some(rows);
+            }
+            Step::Quantize { range, .. } => {
+                let lo = sample.iter().copied().fold(f32::INFINITY, f32::min);
+                let hi = sample.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                *range = Some(if lo <= hi { (lo, hi) } else { (0.0, 0.0) });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Whether `lo <= hi`, with neither being NaN
+fn is_ordered(lo: f32, hi: f32) -> bool {
+    matches!(lo.partial_cmp(&hi), Some(Ordering::Less | Ordering::Equal))
+}
+
+/// A chain of vector operations applied in order
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pipeline {
+    dimension: usize,
+    steps: Vec<Step>,
+}
+
+#[wasm_bindgen]
+impl Pipeline {
+    /// Start an empty pipeline for vectors of `dimension` values
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimension: usize) -> Self {
+        Pipeline {
+            dimension,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Scale every vector to unit length
+    pub fn normalize(&mut self) {
+        self.steps.push(Step::Normalize);
+    }
+
+    /// Subtract the mean vector learned by [`fit`](Self::fit)
+    pub fn mean_center(&mut self) {
+        self.steps.push(Step::MeanCenter { mean: None });
+    }
+
+    /// Project onto the `components` leading principal axes learned by `fit`
+    pub fn pca(&mut self, components: usize) {
+        self.steps.push(Step::Pca {
+            components,
+            mean: None,
+            basis: None,
+        });
+    }
+
+    /// Project onto `components` seeded Gaussian directions
+    pub fn random_projection(&mut self, components: usize, seed: u64) {
+        self.steps.push(Step::RandomProjection {
+            components: components.max(1),
+            seed,
+        });
+    }
+
+    /// Clamp every value to `[min, max]`
+    pub fn clip(&mut self, min: f32, max: f32) {
+        self.steps.push(Step::Clip {
+            min: min.min(max),
+            max: max.max(min),
+        });
+    }
+
+    /// Snap values to `2^bits` levels over the range seen by `fit` (1 to 16 bits)
+    pub fn quantize(&mut self, bits: u8) {
+        self.steps.push(Step::Quantize {
+            bits: bits.clamp(1, 16),
+            range: None,
+        });
+    }
+
+    /// Drop vectors nearly identical (by cosine similarity) to an earlier one
+    pub fn dedupe(&mut self, threshold: f32) {
+        self.steps.push(Step::Dedupe { threshold });
+    }
+
+    /// Number of steps
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Dimension of the vectors the pipeline produces
+    pub fn output_dimension(&self) -> usize {
+        self.steps
+            .iter()
+            .fold(self.dimension, |dim, step| step.output_dimension(dim))
+    }
+
+    /// Whether every step that learns parameters has been fitted
+    pub fn is_fitted(&self) -> bool {
+        self.steps.iter().all(Step::is_fitted)
+    }
+
+    /// Fit every learning step on `sample`, each on the output of the steps before it
+    pub fn fit(&mut self, sample: &[f32]) -> Result<(), Error> {
+        Error::check_batch("Pipeline::fit", sample, self.dimension)?;
+        self.validate("Pipeline::fit")?;
+        if sample.is_empty() {
+            return Err(Error::EmptyInput {
+                operation: "Pipeline::fit",
+            });
+        }
+
+        let mut data = sample.to_vec();
+        let mut dimension = self.dimension;
+        for step in &mut self.steps {
+            step.fit(&data, dimension)?;
+            let mut state = StepState::new(step, dimension);
+            let (next, next_dimension) = apply(step, &mut state, &data, dimension, None);
+            data = next;
+            dimension = next_dimension;
+        }
+        Ok(())
+    }
+
+    /// Run the whole input through the pipeline in one chunk
+    pub fn run(&self, data: &[f32]) -> Result<PipelineOutput, Error> {
+        let chunk = data.len() / self.dimension.max(1);
+        self.run_chunked(data, chunk.max(1), |_, _| {})
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("pipeline", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Pipeline, Error> {
+        let pipeline: Pipeline = serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        pipeline.validate("Pipeline::from_json")?;
+        Ok(pipeline)
+    }
+}
+
+impl Pipeline {
+    /// Check every step against the dimension of the vectors reaching it
+    fn validate(&self, operation: &'static str) -> Result<(), Error> {
+        let mut dimension = self.dimension;
+        for (position, step) in self.steps.iter().enumerate() {
+            step.validate(dimension)
+                .map_err(|message| Error::InvalidArgument {
+                    operation,
+                    message: format!("step {}: {}", position, message),
+                })?;
+            dimension = step.output_dimension(dimension);
+        }
+        Ok(())
+    }
+
+    /// Stream `data` through the pipeline `batch_size` vectors at a time
+    ///
+    /// `progress` is called after every chunk with the number of input vectors
+    /// processed so far and the total.
+    pub fn run_chunked<F>(
+        &self,
+        data: &[f32],
+        batch_size: usize,
+        mut progress: F,
+    ) -> Result<PipelineOutput, Error>
+    where
+        F: FnMut(usize, usize),
+    {
+        Error::check_batch("Pipeline::run", data, self.dimension)?;
+        self.validate("Pipeline::run")?;
+        if !self.is_fitted() {
+            return Err(Error::InvalidState {
+                operation: "run the pipeline",
+                message: "call fit before running a pipeline with learned steps".to_string(),
+            });
+        }
+
+        let total = data.len() / self.dimension;
+        let batch_size = batch_size.max(1);
+        let mut states: Vec<StepState> = Vec::with_capacity(self.steps.len());
+        let mut dimension = self.dimension;
+        for step in &self.steps {
+            states.push(StepState::new(step, dimension));
+            dimension = step.output_dimension(dimension);
+        }
+
+        let mut output = PipelineOutput {
+            data: Vec::new(),
+            dimension,
+            indices: Vec::new(),
+        };
+        for (chunk_index, chunk) in data.chunks(batch_size * self.dimension).enumerate() {
+            let first = chunk_index * batch_size;
+            let mut indices: Vec<u32> = (first..first + chunk.len() / self.dimension)
+                .map(|i| i as u32)
+                .collect();
+            let mut values = chunk.to_vec();
+            let mut dimension = self.dimension;
+            for (step, state) in self.steps.iter().zip(&mut states) {
+                let (next, next_dimension) =
+                    apply(step, state, &values, dimension, Some(&mut indices));
+                values = next;
+                dimension = next_dimension;
+            }
+            output.data.extend_from_slice(&values);
+            output.indices.extend_from_slice(&indices);
+            progress((first + batch_size).min(total), total);
+        }
+        Ok(output)
+    }
+}
+
+/// Per-run state of a step: the random matrix, or the vectors kept by dedupe
+enum StepState {
+    None,
+    Projection(Vec<f32>),
+    Kept(Vec<Vec<f32>>),
+}
+
+impl StepState {
+    fn new(step: &Step, input: usize) -> Self {
+        match step {
+            Step::RandomProjection { components, seed } => {
+                let mut rng = SeededRng::new(*seed);
+                let scale = 1.0 / (*components as f64).sqrt();
+                StepState::Projection(
+                    (0..components * input)
+                        .map(|_| (rng.normal() * scale) as f32)
+                        .collect(),
+                )
+            }
+            Step::Dedupe { .. } => StepState::Kept(Vec::new()),
+            _ => StepState::None,
+        }
+    }
+}
+
+/// Apply one step to a chunk, returning the new chunk and its dimension
+///
+/// `indices` holds the input position of every row and is filtered alongside
+/// the rows when a step drops vectors.
+fn apply(
+    step: &Step,
+    state: &mut StepState,
+    data: &[f32],
+    dimension: usize,
+    indices: Option<&mut Vec<u32>>,
+) -> (Vec<f32>, usize) {
+    let rows = data.chunks_exact(dimension.max(1));
+    match (step, state) {
+        (Step::Normalize, _) => {
+            let mut out = data.to_vec();
+            crate::vector_search::normalize_vectors_batch(&mut out, dimension);
+            (out, dimension)
+        }
+        (Step::MeanCenter { mean: Some(mean) }, _) => {
+            let out = rows
+                .flat_map(|row| row.iter().zip(mean).map(|(x, m)| x - m))
+                .collect();
+            (out, dimension)
+        }
+        (
+            Step::Pca {
+                mean: Some(mean),
+                basis: Some(basis),
+                ..
+            },
+            _,
+        ) => {
+            let components = basis.len() / dimension;
+            let mut centered = vec![0.0f32; dimension];
+            let mut out = Vec::with_capacity(data.len() / dimension * components);
+            for row in rows {
+                for ((c, x), m) in centered.iter_mut().zip(row).zip(mean) {
+                    *c = x - m;
+                }
+                out.extend(
+                    basis
+                        .chunks_exact(dimension)
+                        .map(|axis| dot_product(axis, &centered)),
+                );
+            }
+            (out, components)
+        }
+        (Step::RandomProjection { components, .. }, StepState::Projection(matrix)) => {
+            let out = rows
+                .flat_map(|row| {
+                    matrix
+                        .chunks_exact(dimension)
+                        .map(move |direction| dot_product(direction, row))
+                })
+                .collect();
+            (out, *components)
+        }
+        (Step::Clip { min, max }, _) => (
+            data.iter().map(|x| x.clamp(*min, *max)).collect(),
+            dimension,
+        ),
+        (
+            Step::Quantize {
+                bits,
+                range: Some((lo, hi)),
+            },
+            _,
+        ) => {
+            let levels = ((1u32 << bits) - 1) as f32;
+            let step = (hi - lo) / levels;
+            let out = data
+                .iter()
+                .map(|x| {
+                    if step > 0.0 {
+                        lo + ((x.clamp(*lo, *hi) - lo) / step).round() * step
+                    } else {
+                        *lo
+                    }
+                })
+                .collect();
+            (out, dimension)
+        }
+        (Step::Dedupe { threshold }, StepState::Kept(kept)) => {
+            let mut out = Vec::with_capacity(data.len());
+            let mut keep = Vec::new();
+            for row in rows {
+                let mut unit = row.to_vec();
+                normalize_vector(&mut unit);
+                let duplicate = kept.iter().any(|k| dot_product(k, &unit) >= *threshold);
+                keep.push(!duplicate);
+                if !duplicate {
+                    out.extend_from_slice(row);
+                    kept.push(unit);
+                }
+            }
+            if let Some(indices) = indices {
+                let mut flags = keep.iter();
+                indices.retain(|_| *flags.next().unwrap_or(&true));
+            }
+            (out, dimension)
+        }
+        // Unfitted learning steps: `run` rejects them and `fit` fits each step first
+        _ => (data.to_vec(), dimension),
+    }
+}
+
+/// Vectors produced by a pipeline run
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineOutput {
+    data: Vec<f32>,
+    dimension: usize,
+    indices: Vec<u32>,
+}
+
+#[wasm_bindgen]
+impl PipelineOutput {
+    /// Flat buffer of output vectors
+    pub fn data(&self) -> Vec<f32> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Input position of every output vector (fewer than the input after dedupe)
+    pub fn indices(&self) -> Vec<u32> {
+        self.indices.clone()
+    }
+
+    pub fn count(&self) -> usize {
+        self.indices.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunked_run_matches_single_run() {
+        let sample: Vec<f32> = (0..40).map(|i| ((i * 7) % 11) as f32 - 5.0).collect();
+        let mut pipeline = Pipeline::new(4);
+        pipeline.mean_center();
+        pipeline.pca(2);
+        pipeline.clip(-3.0, 3.0);
+        pipeline.quantize(4);
+        assert!(!pipeline.is_fitted());
+        pipeline.fit(&sample).unwrap();
+        assert!(pipeline.is_fitted());
+        assert_eq!(pipeline.output_dimension(), 2);
+
+        let whole = pipeline.run(&sample).unwrap();
+        let mut calls = Vec::new();
+        let chunked = pipeline
+            .run_chunked(&sample, 3, |done, total| calls.push((done, total)))
+            .unwrap();
+        assert_eq!(whole.data(), chunked.data());
+        assert_eq!(calls.first(), Some(&(3, 10)));
+        assert_eq!(calls.last(), Some(&(10, 10)));
+
+        // A restored pipeline reproduces the output exactly
+        let restored = Pipeline::from_json(&pipeline.to_json().unwrap()).unwrap();
+        assert_eq!(restored.run(&sample).unwrap().data(), whole.data());
+    }
+
+    #[test]
+    fn test_dedupe_across_chunks_and_projection() {
+        let data = [1.0, 0.0, 0.0, 1.0, 2.0, 0.01, 0.0, 3.0, -1.0, 0.0];
+        let mut pipeline = Pipeline::new(2);
+        pipeline.dedupe(0.999);
+        pipeline.normalize();
+        pipeline.random_projection(3, 7);
+
+        let output = pipeline.run_chunked(&data, 2, |_, _| {}).unwrap();
+        assert_eq!(output.indices(), vec![0, 1, 4]);
+        assert_eq!(output.dimension(), 3);
+        assert_eq!(output.data().len(), 9);
+
+        let err = {
+            let mut learned = Pipeline::new(2);
+            learned.mean_center();
+            learned.run(&data).unwrap_err()
+        };
+        assert_eq!(err.code(), "INVALID_STATE");
+    }
+
+    #[test]
+    fn test_restored_steps_are_validated() {
+        let mut pipeline = Pipeline::new(3);
+        pipeline.mean_center();
+        pipeline.fit(&[1.0, 2.0, 3.0, 3.0, 2.0, 1.0]).unwrap();
+        let json = pipeline.to_json().unwrap();
+        assert!(Pipeline::from_json(&json).is_ok());
+
+        for bad in [
+            json.replace("[2.0,2.0,2.0]", "[2.0,2.0]"),
+            r#"{"dimension":2,"steps":[{"op":"clip","min":1.0,"max":-1.0}]}"#.to_string(),
+            r#"{"dimension":2,"steps":[{"op":"quantize","bits":40,"range":null}]}"#.to_string(),
+            r#"{"dimension":2,"steps":[{"op":"quantize","bits":4,"range":[1.0,0.0]}]}"#.to_string(),
+            r#"{"dimension":2,"steps":[{"op":"random_projection","components":3,"seed":1},
+                {"op":"pca","components":1,"mean":[0.0,0.0,0.0],"basis":[1.0,0.0]}]}"#
+                .to_string(),
+            format!(
+                r#"{{"dimension":2,"steps":[{{"op":"random_projection","components":{},"seed":1}}]}}"#,
+                MAX_PROJECTION_VALUES
+            ),
+            format!(
+                r#"{{"dimension":2,"steps":[{{"op":"random_projection","components":{},"seed":1}}]}}"#,
+                usize::MAX
+            ),
+        ] {
+            let Err(err) = Pipeline::from_json(&bad) else {
+                panic!("accepted {}", bad);
+            };
+            assert_eq!(err.code(), "INVALID_ARGUMENT", "{}", bad);
+        }
+    }
+}
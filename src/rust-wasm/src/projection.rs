@@ -0,0 +1,628 @@
+//! 2D projection of embeddings for the discovery map
+//!
+//! A UMAP-style layout: builds a fuzzy k-nearest-neighbor graph over cosine
+//! distances, initializes from the two leading principal components and refines
+//! the layout with seeded stochastic gradient descent. New items can be placed
+//! against a fitted map without moving the existing points.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+use crate::utils::{check_len, SeededRng};
+use crate::vector_search::normalize_vector;
+
+/// Half-width of the square the initial layout is scaled into
+const INIT_EXTENT: f32 = 10.0;
+
+/// Gradient components are clipped to this magnitude, as in UMAP
+const GRADIENT_CLIP: f32 = 4.0;
+
+/// Most items [`Projection::fit`] lays out at once
+///
+/// The neighbor graph is built by brute force, comparing every pair of items,
+/// so larger collections should be sampled before fitting.
+pub const MAX_FIT_ITEMS: usize = 10_000;
+
+/// Parameters of the layout
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectionConfig {
+    n_neighbors: usize,
+    min_dist: f32,
+    spread: f32,
+    epochs: usize,
+    placement_epochs: usize,
+    negative_samples: usize,
+    learning_rate: f32,
+    seed: u64,
+}
+
+#[wasm_bindgen]
+impl ProjectionConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        ProjectionConfig {
+            n_neighbors: 15,
+            min_dist: 0.1,
+            spread: 1.0,
+            epochs: 200,
+            placement_epochs: 50,
+            negative_samples: 5,
+            learning_rate: 1.0,
+            seed: 42,
+        }
+    }
+
+    /// Size of the neighborhood that shapes the layout (local vs global structure)
+    pub fn set_n_neighbors(&mut self, n_neighbors: usize) {
+        self.n_neighbors = n_neighbors.max(2);
+    }
+
+    /// Minimum distance between points in the layout
+    pub fn set_min_dist(&mut self, min_dist: f32) {
+        self.min_dist = min_dist.max(0.0);
+    }
+
+    /// Number of optimization epochs for a full layout
+    pub fn set_epochs(&mut self, epochs: usize) {
+        self.epochs = epochs;
+    }
+
+    /// Number of optimization epochs when placing new items
+    pub fn set_placement_epochs(&mut self, epochs: usize) {
+        self.placement_epochs = epochs;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+}
+
+impl Default for ProjectionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A fitted 2D layout of a set of embeddings
+#[wasm_bindgen]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Projection {
+    config: ProjectionConfig,
+    dimension: usize,
+    /// Normalized copies of the laid-out embeddings, used to find neighbors
+    embeddings: Vec<f32>,
+    /// Interleaved x/y coordinates
+    coordinates: Vec<f32>,
+    /// Parameters of the low-dimensional similarity curve `1 / (1 + a d^(2b))`
+    a: f32,
+    b: f32,
+}
+
+#[wasm_bindgen]
+impl Projection {
+    /// Lay out a flat buffer of embeddings in 2D
+    ///
+    /// Takes time quadratic in the number of items, of which at most
+    /// [`MAX_FIT_ITEMS`] are accepted.
+    pub fn fit(
+        embeddings: &[f32],
+        dimension: usize,
+        config: &ProjectionConfig,
+    ) -> Result<Projection, Error> {
+        if dimension == 0 || !embeddings.len().is_multiple_of(dimension) {
+            return Err(Error::InvalidBatch {
+                operation: "Projection::fit",
+                len: embeddings.len(),
+                dimension,
+            });
+        }
+        if embeddings.len() / dimension > MAX_FIT_ITEMS {
+            return Err(Error::InvalidArgument {
+                operation: "Projection::fit",
+                message: format!(
+                    "{} items exceed the {} a map can be fitted to; sample them first",
+                    embeddings.len() / dimension,
+                    MAX_FIT_ITEMS
+                ),
+            });
+        }
+
+        let (a, b) = fit_curve(config.spread, config.min_dist);
+        let mut projection = Projection {
+            config: config.clone(),
+            dimension,
+            embeddings: normalized(embeddings, dimension),
+            coordinates: Vec::new(),
+            a,
+            b,
+        };
+        let count = projection.len();
+        if count == 0 {
+            return Ok(projection);
+        }
+
+        let mut rng = SeededRng::new(config.seed);
+        projection.coordinates = pca_init(&projection.embeddings, dimension, &mut rng);
+
+        let k = config.n_neighbors.min(count - 1);
+        let mut edges = Vec::new();
+        for i in 0..count {
+            let neighbors = projection.nearest(projection.vector(i), k, Some(i));
+            for (j, weight) in memberships(&neighbors) {
+                edges.push((i, j, weight));
+            }
+        }
+        let edges = symmetrize(edges);
+
+        projection.optimize(&edges, 0, config.epochs, &mut rng);
+        Ok(projection)
+    }
+
+    /// Number of laid-out items
+    pub fn len(&self) -> usize {
+        self.embeddings.len() / self.dimension
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.embeddings.is_empty()
+    }
+
+    /// Interleaved x/y coordinates of every item
+    pub fn coordinates(&self) -> Vec<f32> {
+        self.coordinates.clone()
+    }
+
+    /// Place new items on the map without moving existing ones
+    ///
+    /// The items are added to the map, so later placements can use them as
+    /// neighbors. Returns their interleaved x/y coordinates.
+    pub fn place(&mut self, embeddings: &[f32]) -> Result<Vec<f32>, Error> {
+        if self.is_empty() {
+            return Err(Error::InvalidState {
+                operation: "place items",
+                message: "the map is empty".to_string(),
+            });
+        }
+        Error::check_batch("Projection::place", embeddings, self.dimension)?;
+
+        let first_new = self.len();
+        let new_embeddings = normalized(embeddings, self.dimension);
+        let new_count = new_embeddings.len() / self.dimension;
+        let k = self.config.n_neighbors.min(first_new);
+
+        // Mix the map size into the seed so successive placements draw different samples
+        let mut rng = SeededRng::new(self.config.seed ^ (first_new as u64).rotate_left(32));
+        let mut edges = Vec::new();
+        for (offset, vector) in new_embeddings.chunks_exact(self.dimension).enumerate() {
+            let neighbors = self.nearest(vector, k, None);
+            let weights = memberships(&neighbors);
+            let total: f32 = weights.iter().map(|&(_, w)| w).sum();
+            let (mut x, mut y) = (0.0, 0.0);
+            for &(j, weight) in &weights {
+                x += self.coordinates[2 * j] * weight / total;
+                y += self.coordinates[2 * j + 1] * weight / total;
+            }
+            self.coordinates.push(x);
+            self.coordinates.push(y);
+            edges.extend(weights.into_iter().map(|(j, w)| (first_new + offset, j, w)));
+        }
+        self.embeddings.extend_from_slice(&new_embeddings);
+
+        self.optimize(&edges, first_new, self.config.placement_epochs, &mut rng);
+        Ok(self.coordinates[2 * first_new..2 * (first_new + new_count)].to_vec())
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| Error::serialize("projection", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Projection, Error> {
+        let projection: Projection =
+            serde_json::from_str(json).map_err(|e| Error::parse("JSON", e))?;
+        projection
+            .validate()
+            .map_err(|e| Error::parse("projection", e))?;
+        Ok(projection)
+    }
+}
+
+impl Projection {
+    /// Check that a deserialized projection is consistent
+    fn validate(&self) -> Result<(), String> {
+        if self.dimension == 0 {
+            return Err("dimension must be positive".to_string());
+        }
+        if !self.embeddings.len().is_multiple_of(self.dimension) {
+            return Err(format!(
+                "{} embedding values do not form whole vectors of dimension {}",
+                self.embeddings.len(),
+                self.dimension
+            ));
+        }
+        if !(self.a.is_finite() && self.b.is_finite()) {
+            return Err("curve parameters must be finite".to_string());
+        }
+        check_len("coordinates", self.coordinates.len(), 2 * self.len())
+    }
+
+    fn vector(&self, i: usize) -> &[f32] {
+        &self.embeddings[i * self.dimension..(i + 1) * self.dimension]
+    }
+
+    /// `k` nearest laid-out items by cosine distance, closest first
+    fn nearest(&self, query: &[f32], k: usize, exclude: Option<usize>) -> Vec<(usize, f32)> {
+        let mut distances: Vec<(usize, f32)> = self
+            .embeddings
+            .chunks_exact(self.dimension)
+            .enumerate()
+            .filter(|&(j, _)| Some(j) != exclude)
+            .map(|(j, other)| {
+                let similarity: f32 = query.iter().zip(other).map(|(a, b)| a * b).sum();
+                (j, (1.0 - similarity).max(0.0))
+            })
+            .collect();
+        let by_distance =
+            |a: &(usize, f32), b: &(usize, f32)| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0));
+        if k < distances.len() {
+            distances.select_nth_unstable_by(k, by_distance);
+            distances.truncate(k);
+        }
+        distances.sort_by(by_distance);
+        distances
+    }
+
+    /// Run SGD over `edges`, moving only points at or after `movable_from`
+    fn optimize(
+        &mut self,
+        edges: &[(usize, usize, f32)],
+        movable_from: usize,
+        epochs: usize,
+        rng: &mut SeededRng,
+    ) {
+        let max_weight = edges.iter().map(|e| e.2).fold(0.0f32, f32::max);
+        if epochs == 0 || max_weight == 0.0 {
+            return;
+        }
+
+        // Edges are sampled in proportion to their weight, as in UMAP
+        let epochs_per_sample: Vec<f32> = edges.iter().map(|e| max_weight / e.2).collect();
+        let mut next_sample = epochs_per_sample.clone();
+        let count = self.len();
+        let (a, b) = (self.a, self.b);
+
+        for epoch in 0..epochs {
+            let alpha = self.config.learning_rate * (1.0 - epoch as f32 / epochs as f32);
+            for (edge, &(i, j, _)) in edges.iter().enumerate() {
+                if next_sample[edge] > (epoch + 1) as f32 {
+                    continue;
+                }
+                next_sample[edge] += epochs_per_sample[edge];
+
+                let (dx, dy) = self.delta(i, j);
+                let dist2 = dx * dx + dy * dy;
+                let coefficient = if dist2 > 0.0 {
+                    -2.0 * a * b * dist2.powf(b - 1.0) / (1.0 + a * dist2.powf(b))
+                } else {
+                    0.0
+                };
+                let (gx, gy) = (clip(coefficient * dx), clip(coefficient * dy));
+                self.nudge(i, gx * alpha, gy * alpha, movable_from);
+                self.nudge(j, -gx * alpha, -gy * alpha, movable_from);
+
+                for _ in 0..self.config.negative_samples {
+                    let other = rng.below(count);
+                    if other == i {
+                        continue;
+                    }
+                    let (dx, dy) = self.delta(i, other);
+                    let dist2 = dx * dx + dy * dy;
+                    let coefficient = 2.0 * b / ((0.001 + dist2) * (1.0 + a * dist2.powf(b)));
+                    let (gx, gy) = if dist2 > 0.0 {
+                        (clip(coefficient * dx), clip(coefficient * dy))
+                    } else {
+                        (GRADIENT_CLIP, GRADIENT_CLIP)
+                    };
+                    self.nudge(i, gx * alpha, gy * alpha, movable_from);
+                }
+            }
+        }
+    }
+
+    fn delta(&self, i: usize, j: usize) -> (f32, f32) {
+        (
+            self.coordinates[2 * i] - self.coordinates[2 * j],
+            self.coordinates[2 * i + 1] - self.coordinates[2 * j + 1],
+        )
+    }
+
+    fn nudge(&mut self, i: usize, dx: f32, dy: f32, movable_from: usize) {
+        if i >= movable_from {
+            self.coordinates[2 * i] += dx;
+            self.coordinates[2 * i + 1] += dy;
+        }
+    }
+}
+
+fn clip(value: f32) -> f32 {
+    value.clamp(-GRADIENT_CLIP, GRADIENT_CLIP)
+}
+
+fn normalized(embeddings: &[f32], dimension: usize) -> Vec<f32> {
+    let mut result = embeddings.to_vec();
+    for vector in result.chunks_exact_mut(dimension) {
+        normalize_vector(vector);
+    }
+    result
+}
+
+/// Fuzzy set memberships of a point's neighbors (closest first)
+///
+/// Finds the bandwidth σ for which the memberships `exp(-(d - ρ) / σ)` sum to
+/// `log2(k)`, where ρ is the distance to the nearest neighbor.
+fn memberships(neighbors: &[(usize, f32)]) -> Vec<(usize, f32)> {
+    let Some(&(_, rho)) = neighbors.first() else {
+        return Vec::new();
+    };
+    let target = (neighbors.len() as f32).log2().max(1.0);
+    let total = |sigma: f32| -> f32 {
+        neighbors
+            .iter()
+            .map(|&(_, d)| (-(d - rho).max(0.0) / sigma).exp())
+            .sum()
+    };
+
+    let (mut low, mut high, mut sigma) = (0.0f32, f32::INFINITY, 1.0f32);
+    for _ in 0..64 {
+        let sum = total(sigma);
+        if (sum - target).abs() < 1e-5 {
+            break;
+        }
+        if sum > target {
+            high = sigma;
+            sigma = (low + high) / 2.0;
+        } else {
+            low = sigma;
+            sigma = if high.is_finite() {
+                (low + high) / 2.0
+            } else {
+                sigma * 2.0
+            };
+        }
+    }
+    let sigma = sigma.max(1e-3);
+
+    neighbors
+        .iter()
+        .map(|&(j, d)| (j, (-(d - rho).max(0.0) / sigma).exp()))
+        .collect()
+}
+
+/// Combine directed memberships with the fuzzy union `w_ij + w_ji - w_ij w_ji`
+fn symmetrize(edges: Vec<(usize, usize, f32)>) -> Vec<(usize, usize, f32)> {
+    let mut combined: std::collections::BTreeMap<(usize, usize), (f32, f32)> =
+        std::collections::BTreeMap::new();
+    for (i, j, weight) in edges {
+        if i < j {
+            combined.entry((i, j)).or_default().0 = weight;
+        } else {
+            combined.entry((j, i)).or_default().1 = weight;
+        }
+    }
+    combined
+        .into_iter()
+        .map(|((i, j), (forward, backward))| (i, j, forward + backward - forward * backward))
+        .filter(|e| e.2 > 0.0)
+        .collect()
+}
+
+/// Initial layout from the two leading principal components, scaled to ±[`INIT_EXTENT`]
+fn pca_init(embeddings: &[f32], dimension: usize, rng: &mut SeededRng) -> Vec<f32> {
+    let count = embeddings.len() / dimension;
+    let mut mean = vec![0.0f32; dimension];
+    for vector in embeddings.chunks_exact(dimension) {
+        for (m, x) in mean.iter_mut().zip(vector) {
+            *m += x / count as f32;
+        }
+    }
+    let centered: Vec<f32> = embeddings
+        .chunks_exact(dimension)
+        .flat_map(|vector| {
+            vector
+                .iter()
+                .zip(&mean)
+                .map(|(x, m)| x - m)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    // Power iteration on XᵀX, deflating the first component for the second
+    let mut components: Vec<Vec<f32>> = Vec::new();
+    for _ in 0..2 {
+        let mut v: Vec<f32> = (0..dimension).map(|_| rng.normal() as f32).collect();
+        for _ in 0..100 {
+            let projections: Vec<f32> = centered
+                .chunks_exact(dimension)
+                .map(|row| row.iter().zip(&v).map(|(x, w)| x * w).sum())
+                .collect();
+            let mut next = vec![0.0f32; dimension];
+            for (row, p) in centered.chunks_exact(dimension).zip(&projections) {
+                for (n, x) in next.iter_mut().zip(row) {
+                    *n += x * p;
+                }
+            }
+            for previous in &components {
+                let overlap: f32 = next.iter().zip(previous).map(|(a, b)| a * b).sum();
+                for (n, p) in next.iter_mut().zip(previous) {
+                    *n -= overlap * p;
+                }
+            }
+            normalize_vector(&mut next);
+            v = next;
+        }
+        components.push(v);
+    }
+
+    let mut coordinates: Vec<f32> = centered
+        .chunks_exact(dimension)
+        .flat_map(|row| {
+            components
+                .iter()
+                .map(|c| row.iter().zip(c).map(|(x, w)| x * w).sum::<f32>())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let extent = coordinates.iter().fold(0.0f32, |m, c| m.max(c.abs()));
+    for c in coordinates.iter_mut() {
+        // Identical embeddings get a small seeded jitter so they can separate
+        *c = if extent > 0.0 {
+            *c / extent * INIT_EXTENT
+        } else {
+            (rng.next_f64() as f32 - 0.5) * 1e-3
+        };
+    }
+    coordinates
+}
+
+/// Fit `a` and `b` so that `1 / (1 + a x^(2b))` approximates UMAP's target curve
+///
+/// The target is 1 up to `min_dist` and decays as `exp(-(x - min_dist) / spread)`
+/// beyond it. Uses a coarse-to-fine grid search on the squared error.
+fn fit_curve(spread: f32, min_dist: f32) -> (f32, f32) {
+    let xs: Vec<f32> = (1..=300).map(|i| i as f32 * spread * 3.0 / 300.0).collect();
+    let target: Vec<f32> = xs
+        .iter()
+        .map(|&x| {
+            if x < min_dist {
+                1.0
+            } else {
+                (-(x - min_dist) / spread).exp()
+            }
+        })
+        .collect();
+    let error = |a: f32, b: f32| -> f32 {
+        xs.iter()
+            .zip(&target)
+            .map(|(&x, &t)| (1.0 / (1.0 + a * x.powf(2.0 * b)) - t).powi(2))
+            .sum()
+    };
+
+    let (mut a, mut b) = (1.0f32, 1.0f32);
+    let (mut step_a, mut step_b) = (1.0f32, 0.5f32);
+    for _ in 0..30 {
+        let mut best = (error(a, b), a, b);
+        for da in [-1.0, 0.0, 1.0] {
+            for db in [-1.0, 0.0, 1.0] {
+                let (ca, cb) = ((a + da * step_a).max(1e-3), (b + db * step_b).max(1e-3));
+                let e = error(ca, cb);
+                if e < best.0 {
+                    best = (e, ca, cb);
+                }
+            }
+        }
+        if (best.1, best.2) == (a, b) {
+            step_a /= 2.0;
+            step_b /= 2.0;
+        }
+        a = best.1;
+        b = best.2;
+    }
+    (a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two well separated groups of 20 points in 8 dimensions
+    fn clusters() -> Vec<f32> {
+        let mut rng = SeededRng::new(3);
+        (0..40)
+            .flat_map(|i| {
+                let axis = if i < 20 { 0 } else { 4 };
+                let mut vector: Vec<f32> = (0..8).map(|_| rng.normal() as f32 * 0.1).collect();
+                vector[axis] += 1.0;
+                vector
+            })
+            .collect()
+    }
+
+    fn centroid(coordinates: &[f32]) -> (f32, f32) {
+        let n = (coordinates.len() / 2) as f32;
+        let x = coordinates.iter().step_by(2).sum::<f32>() / n;
+        let y = coordinates.iter().skip(1).step_by(2).sum::<f32>() / n;
+        (x, y)
+    }
+
+    fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+        ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn test_curve_fit_matches_umap_defaults() {
+        // umap-learn's find_ab_params(1.0, 0.1) gives a ≈ 1.577, b ≈ 0.895
+        let (a, b) = fit_curve(1.0, 0.1);
+        assert!((a - 1.577).abs() < 0.05, "a = {}", a);
+        assert!((b - 0.895).abs() < 0.02, "b = {}", b);
+    }
+
+    #[test]
+    fn test_layout_separates_clusters_deterministically() {
+        let embeddings = clusters();
+        let mut config = ProjectionConfig::new();
+        config.set_n_neighbors(5);
+        let projection = Projection::fit(&embeddings, 8, &config).unwrap();
+        let coordinates = projection.coordinates();
+        assert_eq!(coordinates.len(), 80);
+
+        let (first, second) = (centroid(&coordinates[..40]), centroid(&coordinates[40..]));
+        let spread = coordinates[..40]
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .map(|p| distance((p[0], p[1]), first))
+            .fold(0.0f32, f32::max);
+        assert!(distance(first, second) > 2.0 * spread);
+
+        let again = Projection::fit(&embeddings, 8, &config).unwrap();
+        assert_eq!(again.coordinates(), coordinates);
+    }
+
+    #[test]
+    fn test_incremental_placement() {
+        let embeddings = clusters();
+        let mut config = ProjectionConfig::new();
+        config.set_n_neighbors(5);
+        let mut projection = Projection::fit(&embeddings, 8, &config).unwrap();
+        let before = projection.coordinates();
+
+        let mut newcomer = vec![0.0f32; 8];
+        newcomer[4] = 1.0;
+        let placed = projection.place(&newcomer).unwrap();
+        assert_eq!(projection.len(), 41);
+        assert_eq!(&projection.coordinates()[..80], before.as_slice());
+
+        let point = (placed[0], placed[1]);
+        assert!(
+            distance(point, centroid(&before[40..])) < distance(point, centroid(&before[..40]))
+        );
+
+        let restored = Projection::from_json(&projection.to_json().unwrap()).unwrap();
+        assert_eq!(restored.coordinates(), projection.coordinates());
+        projection.coordinates.truncate(80);
+        let Err(err) = Projection::from_json(&projection.to_json().unwrap()) else {
+            panic!("a projection missing coordinates was restored");
+        };
+        assert_eq!(err.code(), "PARSE_ERROR");
+        projection.dimension = 0;
+        assert!(Projection::from_json(&projection.to_json().unwrap()).is_err());
+
+        let too_many = vec![1.0; MAX_FIT_ITEMS + 1];
+        let Err(err) = Projection::fit(&too_many, 1, &config) else {
+            panic!("a map larger than the limit was fitted");
+        };
+        assert_eq!(err.code(), "INVALID_ARGUMENT");
+    }
+}
@@ -0,0 +1,333 @@
+//! Minimal safetensors reader and writer
+//!
+//! Parses the safetensors container (8-byte little-endian header length, JSON
+//! header, raw tensor data) without external dependencies and converts
+//! floating point tensors to `f32`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::vector_io::FormatError;
+
+/// Name of the optional string-to-string metadata entry in the header
+const METADATA_KEY: &str = "__metadata__";
+
+/// Element type of a stored tensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Dtype {
+    F64,
+    F32,
+    F16,
+    BF16,
+    I64,
+    I32,
+    U8,
+}
+
+impl Dtype {
+    /// Size of one element in bytes
+    pub fn size(self) -> usize {
+        match self {
+            Dtype::F64 | Dtype::I64 => 8,
+            Dtype::F32 | Dtype::I32 => 4,
+            Dtype::F16 | Dtype::BF16 => 2,
+            Dtype::U8 => 1,
+        }
+    }
+}
+
+/// Header entry describing one tensor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TensorInfo {
+    pub dtype: Dtype,
+    pub shape: Vec<usize>,
+    pub data_offsets: (usize, usize),
+}
+
+/// Borrowed view over a safetensors buffer
+#[derive(Debug)]
+pub struct SafeTensors<'a> {
+    tensors: HashMap<String, TensorInfo>,
+    metadata: HashMap<String, String>,
+    data: &'a [u8],
+}
+
+impl<'a> SafeTensors<'a> {
+    /// Parse a safetensors buffer, validating the header against the data size
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, FormatError> {
+        if bytes.len() < 8 {
+            return Err(FormatError::Truncated {
+                context: "safetensors header",
+            });
+        }
+        let mut len_bytes = [0u8; 8];
+        len_bytes.copy_from_slice(&bytes[..8]);
+        let header_len = u64::from_le_bytes(len_bytes) as usize;
+        let data_start = 8usize
+            .checked_add(header_len)
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| {
+                FormatError::InvalidHeader(format!(
+                    "safetensors header length {} exceeds buffer size {}",
+                    header_len,
+                    bytes.len()
+                ))
+            })?;
+
+        let header: HashMap<String, serde_json::Value> =
+            serde_json::from_slice(&bytes[8..data_start])
+                .map_err(|e| FormatError::InvalidHeader(e.to_string()))?;
+
+        let data = &bytes[data_start..];
+        let mut tensors = HashMap::new();
+        let mut metadata = HashMap::new();
+        for (name, value) in header {
+            if name == METADATA_KEY {
+                metadata = serde_json::from_value(value)
+                    .map_err(|e| FormatError::InvalidHeader(format!("metadata: {}", e)))?;
+                continue;
+            }
+            let info: TensorInfo = serde_json::from_value(value)
+                .map_err(|e| FormatError::InvalidHeader(format!("entry {}: {}", name, e)))?;
+            let (start, end) = info.data_offsets;
+            let expected = info
+                .shape
+                .iter()
+                .try_fold(info.dtype.size(), |len, &dim| len.checked_mul(dim))
+                .ok_or_else(|| {
+                    FormatError::InvalidHeader(format!(
+                        "tensor {} has shape {:?}, too large to address",
+                        name, info.shape
+                    ))
+                })?;
+            if start > end || end > data.len() || end - start != expected {
+                return Err(FormatError::InvalidHeader(format!(
+                    "tensor {} has data offsets {:?} inconsistent with shape {:?} and dtype {:?}",
+                    name, info.data_offsets, info.shape, info.dtype
+                )));
+            }
+            tensors.insert(name, info);
+        }
+
+        Ok(SafeTensors {
+            tensors,
+            metadata,
+            data,
+        })
+    }
+
+    /// Names of all tensors in the buffer
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tensors.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Header entry for a tensor
+    pub fn info(&self, name: &str) -> Option<&TensorInfo> {
+        self.tensors.get(name)
+    }
+
+    /// Free-form string metadata stored in the header
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Raw little-endian bytes of a tensor
+    pub fn bytes(&self, name: &str) -> Option<&'a [u8]> {
+        self.tensors
+            .get(name)
+            .map(|info| &self.data[info.data_offsets.0..info.data_offsets.1])
+    }
+
+    /// Read a floating point tensor as `f32`, returning its shape and values
+    pub fn to_f32(&self, name: &str) -> Result<(Vec<usize>, Vec<f32>), FormatError> {
+        let info = self
+            .tensors
+            .get(name)
+            .ok_or_else(|| FormatError::MissingTensor(name.to_string()))?;
+        let bytes = &self.data[info.data_offsets.0..info.data_offsets.1];
+        let values = match info.dtype {
+            Dtype::F32 => bytes
+                .as_chunks::<4>()
+                .0
+                .iter()
+                .map(|b| f32::from_le_bytes(*b))
+                .collect(),
+            Dtype::F64 => bytes
+                .as_chunks::<8>()
+                .0
+                .iter()
+                .map(|b| f64::from_le_bytes(*b) as f32)
+                .collect(),
+            Dtype::F16 => bytes
+                .as_chunks::<2>()
+                .0
+                .iter()
+                .map(|b| f16_to_f32(u16::from_le_bytes(*b)))
+                .collect(),
+            Dtype::BF16 => bytes
+                .as_chunks::<2>()
+                .0
+                .iter()
+                .map(|b| f32::from_bits((u16::from_le_bytes(*b) as u32) << 16))
+                .collect(),
+            other => return Err(FormatError::UnsupportedDtype(format!("{:?}", other))),
+        };
+        Ok((info.shape.clone(), values))
+    }
+}
+
+/// Serialize `f32` tensors into a safetensors buffer
+///
+/// Tensors are written in the order given; `metadata` is stored under `__metadata__`.
+pub fn serialize_f32(
+    tensors: &[(&str, &[usize], &[f32])],
+    metadata: &HashMap<String, String>,
+) -> Vec<u8> {
+    let mut header = BTreeMap::new();
+    if !metadata.is_empty() {
+        header.insert(METADATA_KEY.to_string(), serde_json::json!(metadata));
+    }
+
+    let mut offset = 0;
+    for (name, shape, values) in tensors {
+        let end = offset + values.len() * Dtype::F32.size();
+        let info = TensorInfo {
+            dtype: Dtype::F32,
+            shape: shape.to_vec(),
+            data_offsets: (offset, end),
+        };
+        header.insert(name.to_string(), serde_json::json!(info));
+        offset = end;
+    }
+
+    let mut header_bytes = serde_json::to_vec(&header).unwrap_or_default();
+    // Pad the header with spaces so the data section is 8-byte aligned
+    while header_bytes.len() % 8 != 0 {
+        header_bytes.push(b' ');
+    }
+
+    let mut out = Vec::with_capacity(8 + header_bytes.len() + offset);
+    out.extend_from_slice(&(header_bytes.len() as u64).to_le_bytes());
+    out.extend_from_slice(&header_bytes);
+    for (_, _, values) in tensors {
+        for value in values.iter() {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// Convert IEEE 754 half precision bits to `f32`
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+
+    let value = match (exponent, mantissa) {
+        (0, 0) => sign,
+        (0, _) => {
+            // Subnormal: renormalize the mantissa
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3ff;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(value)
+}
+
+/// Convert `f32` to IEEE 754 half precision bits, rounding to nearest even
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let rebiased = exponent - 127 + 15;
+    if rebiased >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let round = |value: u32, shift: u32| {
+        let truncated = value >> shift;
+        let remainder = value & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        if remainder > halfway || (remainder == halfway && truncated & 1 == 1) {
+            truncated + 1
+        } else {
+            truncated
+        }
+    };
+
+    if rebiased <= 0 {
+        // Subnormal (or zero) in half precision
+        if rebiased < -10 {
+            return sign;
+        }
+        return sign | round(mantissa | 0x80_0000, (14 - rebiased) as u32) as u16;
+    }
+
+    // A carry out of the mantissa correctly bumps the exponent (up to infinity)
+    sign | round(((rebiased as u32) << 23) | mantissa, 13) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut metadata = HashMap::new();
+        metadata.insert("format".to_string(), "pt".to_string());
+        let bytes = serialize_f32(
+            &[
+                ("a", &[2, 2], &[1.0, 2.0, 3.0, 4.0]),
+                ("b", &[3], &[-1.0, 0.5, 0.25]),
+            ],
+            &metadata,
+        );
+
+        let tensors = SafeTensors::parse(&bytes).unwrap();
+        assert_eq!(tensors.names(), vec!["a", "b"]);
+        assert_eq!(
+            tensors.metadata().get("format").map(String::as_str),
+            Some("pt")
+        );
+        let (shape, values) = tensors.to_f32("b").unwrap();
+        assert_eq!(shape, vec![3]);
+        assert_eq!(values, vec![-1.0, 0.5, 0.25]);
+
+        // A shape whose byte length overflows is refused, not wrapped
+        let header =
+            br#"{"x":{"dtype":"F32","shape":[4611686018427387904,8],"data_offsets":[0,0]}}"#;
+        let mut crafted = (header.len() as u64).to_le_bytes().to_vec();
+        crafted.extend_from_slice(header);
+        let Err(err) = SafeTensors::parse(&crafted) else {
+            panic!("an overflowing shape was accepted");
+        };
+        assert!(matches!(err, FormatError::InvalidHeader(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_f16_conversion() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 0.333_251_95);
+        assert_eq!(f16_to_f32(0x0001), 5.960_464_5e-8);
+        assert!(f16_to_f32(0x7c00).is_infinite());
+
+        for bits in [0x3c00u16, 0xc000, 0x3555, 0x0001, 0x03ff, 0x7bff, 0x7c00] {
+            assert_eq!(f32_to_f16(f16_to_f32(bits)), bits);
+        }
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1e-9), 0);
+    }
+}
@@ -0,0 +1,1405 @@
+//! Text tokenization for transformer embedding models
+//!
+//! Pure-Rust WordPiece and byte-level BPE tokenizers that load the Hugging Face
+//! `vocab.txt` and `tokenizer.json` formats and reproduce their encodings,
+//! including byte offsets into the original text.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use unicode_categories::UnicodeCategories;
+use unicode_normalization::char::{canonical_combining_class, decompose_canonical};
+use wasm_bindgen::prelude::*;
+
+use crate::error::Error;
+
+/// Maximum token sequence length accepted by BERT-style encoders
+pub const DEFAULT_MAX_SEQUENCE_LENGTH: usize = 512;
+
+/// Words longer than this many characters become a single unknown token
+const DEFAULT_MAX_INPUT_CHARS_PER_WORD: usize = 100;
+
+/// Special tokens registered when loading a BERT `vocab.txt`
+const BERT_SPECIAL_TOKENS: [&str; 5] = ["[PAD]", "[UNK]", "[CLS]", "[SEP]", "[MASK]"];
+
+/// Result of encoding a single text
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Encoding {
+    ids: Vec<u32>,
+    type_ids: Vec<u32>,
+    attention_mask: Vec<u32>,
+    special_tokens_mask: Vec<u32>,
+    offsets: Vec<(usize, usize)>,
+    tokens: Vec<String>,
+}
+
+#[wasm_bindgen]
+impl Encoding {
+    /// Token IDs
+    pub fn ids(&self) -> Vec<u32> {
+        self.ids.clone()
+    }
+
+    /// Segment (token type) IDs
+    pub fn type_ids(&self) -> Vec<u32> {
+        self.type_ids.clone()
+    }
+
+    /// Attention mask: 1 for real tokens, 0 for padding
+    pub fn attention_mask(&self) -> Vec<u32> {
+        self.attention_mask.clone()
+    }
+
+    /// Special tokens mask: 1 for special and padding tokens
+    pub fn special_tokens_mask(&self) -> Vec<u32> {
+        self.special_tokens_mask.clone()
+    }
+
+    /// Token strings
+    pub fn tokens(&self) -> Vec<String> {
+        self.tokens.clone()
+    }
+
+    /// Byte offsets into the original text as flat `[start, end, ...]` pairs
+    pub fn offsets(&self) -> Vec<u32> {
+        self.offsets
+            .iter()
+            .flat_map(|&(start, end)| [start as u32, end as u32])
+            .collect()
+    }
+
+    /// Number of tokens, including special and padding tokens
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl Encoding {
+    /// Byte offsets into the original text, one `(start, end)` pair per token
+    pub fn offset_pairs(&self) -> &[(usize, usize)] {
+        &self.offsets
+    }
+
+    fn push(&mut self, token: Token, type_id: u32, special: bool) {
+        self.ids.push(token.id);
+        self.type_ids.push(type_id);
+        self.attention_mask.push(1);
+        self.special_tokens_mask.push(special as u32);
+        self.offsets.push(token.offsets);
+        self.tokens.push(token.value);
+    }
+
+    fn pad_to(&mut self, length: usize, pad_id: u32, pad_token: &str) {
+        while self.ids.len() < length {
+            self.ids.push(pad_id);
+            self.type_ids.push(0);
+            self.attention_mask.push(0);
+            self.special_tokens_mask.push(1);
+            self.offsets.push((0, 0));
+            self.tokens.push(pad_token.to_string());
+        }
+    }
+}
+
+/// Tokenizer for WordPiece (BERT) and byte-level BPE (GPT-2/RoBERTa) models
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Tokenizer {
+    normalizer: Normalizer,
+    pre_tokenizer: PreTokenizer,
+    model: Model,
+    post_processor: PostProcessor,
+    added_tokens: Vec<(String, u32)>,
+    special_ids: Vec<u32>,
+    truncation: Option<usize>,
+    padding: Option<Padding>,
+    pad_id: u32,
+    pad_token: String,
+}
+
+/// Padding strategy applied to encoded sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Padding {
+    /// Pad every sequence in a batch to the longest one
+    BatchLongest,
+    /// Pad every sequence to a fixed length
+    Fixed(usize),
+}
+
+#[wasm_bindgen]
+impl Tokenizer {
+    /// Create a BERT WordPiece tokenizer from the contents of a `vocab.txt` file
+    pub fn from_vocab(vocab: &str, lowercase: bool) -> Result<Tokenizer, Error> {
+        Self::parse_vocab(vocab, lowercase).map_err(|e| Error::parse("vocabulary", e))
+    }
+
+    /// Create a tokenizer from the contents of a Hugging Face `tokenizer.json` file
+    pub fn from_json(json: &str) -> Result<Tokenizer, Error> {
+        Self::parse_json(json).map_err(|e| Error::parse("tokenizer JSON", e))
+    }
+
+    /// Truncate encodings to at most `max_length` tokens, special tokens included
+    pub fn set_truncation(&mut self, max_length: usize) {
+        self.truncation = Some(max_length);
+    }
+
+    /// Disable truncation
+    pub fn clear_truncation(&mut self) {
+        self.truncation = None;
+    }
+
+    /// Pad encodings to `length`, or to the longest sequence of a batch when `None`
+    pub fn set_padding(&mut self, length: Option<usize>) {
+        self.padding = Some(match length {
+            Some(length) => Padding::Fixed(length),
+            None => Padding::BatchLongest,
+        });
+    }
+
+    /// Disable padding
+    pub fn clear_padding(&mut self) {
+        self.padding = None;
+    }
+
+    /// Use `token` for padding; it must be part of the vocabulary
+    pub fn set_pad_token(&mut self, token: &str) -> Result<(), Error> {
+        let id = self.token_to_id(token).ok_or_else(|| Error::NotFound {
+            kind: "pad token",
+            name: token.to_string(),
+        })?;
+        self.pad_id = id;
+        self.pad_token = token.to_string();
+        Ok(())
+    }
+
+    /// Encode a single text, adding the model's special tokens
+    pub fn encode(&self, text: &str) -> Encoding {
+        let mut encoding = self.encode_unpadded(text);
+        if let Some(Padding::Fixed(length)) = self.padding {
+            encoding.pad_to(length, self.pad_id, &self.pad_token);
+        }
+        encoding
+    }
+
+    /// Encode a batch of texts, padding them according to the padding strategy
+    pub fn encode_batch(&self, texts: Vec<String>) -> Vec<Encoding> {
+        let mut encodings: Vec<Encoding> = texts
+            .iter()
+            .map(|text| self.encode_unpadded(text))
+            .collect();
+
+        let target = match self.padding {
+            Some(Padding::Fixed(length)) => Some(length),
+            Some(Padding::BatchLongest) => encodings.iter().map(|e| e.len()).max(),
+            None => None,
+        };
+        if let Some(length) = target {
+            for encoding in encodings.iter_mut() {
+                encoding.pad_to(length, self.pad_id, &self.pad_token);
+            }
+        }
+
+        encodings
+    }
+
+    /// Look up the ID of a token
+    pub fn token_to_id(&self, token: &str) -> Option<u32> {
+        self.added_tokens
+            .iter()
+            .find(|(content, _)| content == token)
+            .map(|&(_, id)| id)
+            .or_else(|| self.model.vocab().get(token).copied())
+    }
+
+    /// Look up the token for an ID
+    pub fn id_to_token(&self, id: u32) -> Option<String> {
+        self.added_tokens
+            .iter()
+            .find(|&&(_, added_id)| added_id == id)
+            .map(|(content, _)| content.clone())
+            .or_else(|| {
+                self.model
+                    .vocab()
+                    .iter()
+                    .find(|&(_, &vocab_id)| vocab_id == id)
+                    .map(|(token, _)| token.clone())
+            })
+    }
+
+    /// Number of entries in the vocabulary, including added tokens
+    pub fn vocab_size(&self) -> usize {
+        let vocab = self.model.vocab();
+        vocab.len()
+            + self
+                .added_tokens
+                .iter()
+                .filter(|(content, _)| !vocab.contains_key(content))
+                .count()
+    }
+}
+
+impl Tokenizer {
+    /// Load a tokenizer from disk, dispatching on the file name
+    ///
+    /// Files ending in `.json` are read as `tokenizer.json`, anything else as a
+    /// lowercasing BERT `vocab.txt`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Tokenizer, Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&contents)
+        } else {
+            Self::from_vocab(&contents, true)
+        }
+    }
+
+    /// Maximum sequence length encodings are truncated to, if any
+    pub fn truncation(&self) -> Option<usize> {
+        self.truncation
+    }
+
+    /// Tokenize text into model tokens without special tokens, truncation or padding
+    pub fn tokenize(&self, text: &str) -> Vec<(String, u32, (usize, usize))> {
+        self.raw_tokens(text)
+            .into_iter()
+            .map(|token| (token.value, token.id, token.offsets))
+            .collect()
+    }
+
+    /// Number of special tokens the post-processor adds to a single sequence
+    pub fn num_special_tokens(&self) -> usize {
+        self.post_processor.added_tokens()
+    }
+
+    /// Encode text as overlapping windows of at most `max_length` tokens each
+    ///
+    /// Consecutive windows share `overlap` model tokens; special tokens are added
+    /// to every window and count towards `max_length`. Truncation and padding
+    /// settings are ignored. Text without tokens yields a single window.
+    pub fn encode_windows(&self, text: &str, max_length: usize, overlap: usize) -> Vec<Encoding> {
+        let tokens = self.raw_tokens(text);
+        let window = max_length
+            .saturating_sub(self.post_processor.added_tokens())
+            .max(1);
+        let step = window - overlap.min(window - 1);
+
+        let mut encodings = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = (start + window).min(tokens.len());
+            encodings.push(self.post_process(tokens[start..end].to_vec()));
+            if end == tokens.len() {
+                return encodings;
+            }
+            start += step;
+        }
+    }
+
+    fn parse_vocab(vocab: &str, lowercase: bool) -> Result<Self, String> {
+        let vocab: HashMap<String, u32> = vocab
+            .lines()
+            .enumerate()
+            .map(|(id, line)| (line.trim_end().to_string(), id as u32))
+            .collect();
+
+        let lookup = |token: &str| {
+            vocab
+                .get(token)
+                .map(|&id| (token.to_string(), id))
+                .ok_or_else(|| format!("Vocabulary is missing the {} token", token))
+        };
+        let cls = lookup("[CLS]")?;
+        let sep = lookup("[SEP]")?;
+        lookup("[UNK]")?;
+
+        let added_tokens: Vec<(String, u32)> = BERT_SPECIAL_TOKENS
+            .iter()
+            .filter_map(|token| lookup(token).ok())
+            .collect();
+
+        Self::build(
+            Normalizer::Bert {
+                clean_text: true,
+                handle_chinese_chars: true,
+                strip_accents: lowercase,
+                lowercase,
+            },
+            PreTokenizer::Bert,
+            Model::WordPiece {
+                vocab,
+                unk_token: "[UNK]".to_string(),
+                prefix: "##".to_string(),
+                max_input_chars_per_word: DEFAULT_MAX_INPUT_CHARS_PER_WORD,
+            },
+            PostProcessor::Wrap { cls, sep },
+            added_tokens,
+        )
+    }
+
+    fn parse_json(json: &str) -> Result<Self, String> {
+        let file: TokenizerFile =
+            serde_json::from_str(json).map_err(|e| format!("Invalid tokenizer.json: {}", e))?;
+
+        let normalizer = match file.normalizer {
+            None => Normalizer::None,
+            Some(NormalizerSpec::BertNormalizer {
+                clean_text,
+                handle_chinese_chars,
+                strip_accents,
+                lowercase,
+            }) => Normalizer::Bert {
+                clean_text,
+                handle_chinese_chars,
+                strip_accents: strip_accents.unwrap_or(lowercase),
+                lowercase,
+            },
+            Some(NormalizerSpec::Lowercase) => Normalizer::Lowercase,
+        };
+
+        let pre_tokenizer = match file.pre_tokenizer {
+            None => PreTokenizer::None,
+            Some(PreTokenizerSpec::BertPreTokenizer) => PreTokenizer::Bert,
+            Some(PreTokenizerSpec::ByteLevel {
+                add_prefix_space,
+                use_regex,
+            }) => {
+                if !use_regex {
+                    return Err("ByteLevel pre-tokenizer without regex is not supported".into());
+                }
+                PreTokenizer::ByteLevel { add_prefix_space }
+            }
+        };
+
+        let model = match file.model {
+            ModelSpec::WordPiece {
+                vocab,
+                unk_token,
+                continuing_subword_prefix,
+                max_input_chars_per_word,
+            } => {
+                if !vocab.contains_key(&unk_token) {
+                    return Err(format!("Vocabulary is missing the {} token", unk_token));
+                }
+                Model::WordPiece {
+                    vocab,
+                    unk_token,
+                    prefix: continuing_subword_prefix,
+                    max_input_chars_per_word,
+                }
+            }
+            ModelSpec::BPE {
+                vocab,
+                merges,
+                unk_token,
+                continuing_subword_prefix,
+                end_of_word_suffix,
+                ignore_merges,
+                dropout,
+            } => {
+                if dropout.is_some() {
+                    return Err("BPE dropout is not supported".into());
+                }
+                Model::bpe(
+                    vocab,
+                    merges,
+                    unk_token,
+                    continuing_subword_prefix,
+                    end_of_word_suffix,
+                    ignore_merges,
+                )?
+            }
+        };
+
+        let post_processor = match file.post_processor {
+            None => PostProcessor::None,
+            Some(PostProcessorSpec::BertProcessing { cls, sep })
+            | Some(PostProcessorSpec::RobertaProcessing { cls, sep }) => {
+                PostProcessor::Wrap { cls, sep }
+            }
+            Some(PostProcessorSpec::TemplateProcessing {
+                single,
+                special_tokens,
+            }) => {
+                let mut pieces = Vec::with_capacity(single.len());
+                for piece in single {
+                    pieces.push(match piece {
+                        TemplatePieceSpec::Sequence { type_id, .. } => {
+                            TemplatePiece::Sequence(type_id)
+                        }
+                        TemplatePieceSpec::SpecialToken { id, type_id } => {
+                            let special = special_tokens.get(&id).ok_or_else(|| {
+                                format!("Template references unknown special token {}", id)
+                            })?;
+                            let tokens = special
+                                .tokens
+                                .iter()
+                                .cloned()
+                                .zip(special.ids.iter().copied())
+                                .collect();
+                            TemplatePiece::Special(tokens, type_id)
+                        }
+                    });
+                }
+                PostProcessor::Template(pieces)
+            }
+        };
+
+        let added_tokens = file
+            .added_tokens
+            .into_iter()
+            .map(|token| (token.content, token.id))
+            .collect();
+
+        Self::build(
+            normalizer,
+            pre_tokenizer,
+            model,
+            post_processor,
+            added_tokens,
+        )
+    }
+
+    fn build(
+        normalizer: Normalizer,
+        pre_tokenizer: PreTokenizer,
+        model: Model,
+        post_processor: PostProcessor,
+        mut added_tokens: Vec<(String, u32)>,
+    ) -> Result<Self, String> {
+        let special_ids = added_tokens.iter().map(|&(_, id)| id).collect();
+
+        // Longest tokens first so that leftmost-longest matching is a simple scan
+        added_tokens.retain(|(content, _)| !content.is_empty());
+        added_tokens.sort_by_key(|(content, _)| std::cmp::Reverse(content.len()));
+
+        let (pad_token, pad_id) = ["[PAD]", "<pad>"]
+            .iter()
+            .find_map(|token| {
+                added_tokens
+                    .iter()
+                    .find(|(content, _)| content == token)
+                    .map(|&(_, id)| (token.to_string(), id))
+                    .or_else(|| model.vocab().get(*token).map(|&id| (token.to_string(), id)))
+            })
+            .unwrap_or_else(|| ("[PAD]".to_string(), 0));
+
+        Ok(Tokenizer {
+            normalizer,
+            pre_tokenizer,
+            model,
+            post_processor,
+            added_tokens,
+            special_ids,
+            truncation: None,
+            padding: None,
+            pad_id,
+            pad_token,
+        })
+    }
+
+    fn encode_unpadded(&self, text: &str) -> Encoding {
+        let mut tokens = self.raw_tokens(text);
+
+        if let Some(max_length) = self.truncation {
+            tokens.truncate(max_length.saturating_sub(self.post_processor.added_tokens()));
+        }
+
+        self.post_process(tokens)
+    }
+
+    /// Wrap model tokens with the post-processor's special tokens
+    fn post_process(&self, tokens: Vec<Token>) -> Encoding {
+        let mut encoding = Encoding::default();
+        match &self.post_processor {
+            PostProcessor::None => {
+                for token in tokens {
+                    let special = self.special_ids.contains(&token.id);
+                    encoding.push(token, 0, special);
+                }
+            }
+            PostProcessor::Wrap { cls, sep } => {
+                encoding.push(Token::special(cls), 0, true);
+                for token in tokens {
+                    encoding.push(token, 0, false);
+                }
+                encoding.push(Token::special(sep), 0, true);
+            }
+            PostProcessor::Template(pieces) => {
+                let mut sequence = Some(tokens);
+                for piece in pieces {
+                    match piece {
+                        TemplatePiece::Sequence(type_id) => {
+                            for token in sequence.take().unwrap_or_default() {
+                                encoding.push(token, *type_id, false);
+                            }
+                        }
+                        TemplatePiece::Special(specials, type_id) => {
+                            for special in specials {
+                                encoding.push(Token::special(special), *type_id, true);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        encoding
+    }
+
+    fn raw_tokens(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        for segment in self.split_added_tokens(text) {
+            match segment {
+                Segment::Added(id, start, end) => tokens.push(Token {
+                    id,
+                    value: text[start..end].to_string(),
+                    offsets: (start, end),
+                }),
+                Segment::Text(start, end) => {
+                    let chars = self
+                        .normalizer
+                        .normalize(aligned_chars(&text[start..end], start));
+                    for word in self.pre_tokenizer.split(chars) {
+                        self.model.tokenize(&word, &mut tokens);
+                    }
+                }
+            }
+        }
+
+        tokens
+    }
+
+    /// Split text around occurrences of added tokens (leftmost-longest match)
+    fn split_added_tokens(&self, text: &str) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut text_start = 0;
+        let mut pos = 0;
+
+        while pos < text.len() {
+            let rest = &text[pos..];
+            let matched = self
+                .added_tokens
+                .iter()
+                .find(|(content, _)| rest.starts_with(content.as_str()));
+
+            match matched {
+                Some((content, id)) => {
+                    if text_start < pos {
+                        segments.push(Segment::Text(text_start, pos));
+                    }
+                    segments.push(Segment::Added(*id, pos, pos + content.len()));
+                    pos += content.len();
+                    text_start = pos;
+                }
+                None => {
+                    pos += rest.chars().next().map_or(1, char::len_utf8);
+                }
+            }
+        }
+
+        if text_start < text.len() {
+            segments.push(Segment::Text(text_start, text.len()));
+        }
+
+        segments
+    }
+}
+
+/// Piece of input text: either an added token or text to run through the model
+enum Segment {
+    Added(u32, usize, usize),
+    Text(usize, usize),
+}
+
+/// Model token with its byte span in the original text
+#[derive(Debug, Clone)]
+struct Token {
+    id: u32,
+    value: String,
+    offsets: (usize, usize),
+}
+
+impl Token {
+    fn special((value, id): &(String, u32)) -> Self {
+        Token {
+            id: *id,
+            value: value.clone(),
+            offsets: (0, 0),
+        }
+    }
+}
+
+/// Normalized character with the byte span of the original character it came from
+#[derive(Debug, Clone, Copy)]
+struct AlignedChar {
+    c: char,
+    start: usize,
+    end: usize,
+}
+
+fn aligned_chars(text: &str, base: usize) -> Vec<AlignedChar> {
+    text.char_indices()
+        .map(|(i, c)| AlignedChar {
+            c,
+            start: base + i,
+            end: base + i + c.len_utf8(),
+        })
+        .collect()
+}
+
+fn span(chars: &[AlignedChar]) -> (usize, usize) {
+    match (chars.first(), chars.last()) {
+        (Some(first), Some(last)) => (first.start, last.end),
+        _ => (0, 0),
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Normalizer {
+    None,
+    Lowercase,
+    Bert {
+        clean_text: bool,
+        handle_chinese_chars: bool,
+        strip_accents: bool,
+        lowercase: bool,
+    },
+}
+
+impl Normalizer {
+    fn normalize(&self, chars: Vec<AlignedChar>) -> Vec<AlignedChar> {
+        match *self {
+            Normalizer::None => chars,
+            Normalizer::Lowercase => lowercase(chars),
+            Normalizer::Bert {
+                clean_text,
+                handle_chinese_chars,
+                strip_accents,
+                lowercase: lower,
+            } => {
+                let mut chars = chars;
+                if clean_text {
+                    chars = chars
+                        .into_iter()
+                        .filter(|ac| !(ac.c == '\0' || ac.c == '\u{fffd}' || is_bert_control(ac.c)))
+                        .map(|ac| AlignedChar {
+                            c: if is_bert_whitespace(ac.c) { ' ' } else { ac.c },
+                            ..ac
+                        })
+                        .collect();
+                }
+                if handle_chinese_chars {
+                    chars = chars
+                        .into_iter()
+                        .flat_map(|ac| {
+                            if is_chinese_char(ac.c) {
+                                let space = AlignedChar { c: ' ', ..ac };
+                                vec![space, ac, space]
+                            } else {
+                                vec![ac]
+                            }
+                        })
+                        .collect();
+                }
+                if strip_accents {
+                    chars = nfd(chars);
+                    chars.retain(|ac| !ac.c.is_mark_nonspacing());
+                }
+                if lower {
+                    chars = lowercase(chars);
+                }
+                chars
+            }
+        }
+    }
+}
+
+fn lowercase(chars: Vec<AlignedChar>) -> Vec<AlignedChar> {
+    chars
+        .into_iter()
+        .flat_map(|ac| ac.c.to_lowercase().map(move |c| AlignedChar { c, ..ac }))
+        .collect()
+}
+
+/// Canonical decomposition followed by canonical reordering of combining marks
+fn nfd(chars: Vec<AlignedChar>) -> Vec<AlignedChar> {
+    let mut out: Vec<AlignedChar> = Vec::with_capacity(chars.len());
+    for ac in chars {
+        decompose_canonical(ac.c, |c| out.push(AlignedChar { c, ..ac }));
+    }
+
+    let mut i = 0;
+    while i < out.len() {
+        if canonical_combining_class(out[i].c) == 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < out.len() && canonical_combining_class(out[i].c) != 0 {
+            i += 1;
+        }
+        out[start..i].sort_by_key(|ac| canonical_combining_class(ac.c));
+    }
+
+    out
+}
+
+fn is_bert_whitespace(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || c.is_whitespace()
+}
+
+fn is_bert_control(c: char) -> bool {
+    !matches!(c, '\t' | '\n' | '\r') && c.is_other()
+}
+
+fn is_bert_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || c.is_punctuation()
+}
+
+fn is_chinese_char(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x4E00..=0x9FFF
+            | 0x3400..=0x4DBF
+            | 0x20000..=0x2A6DF
+            | 0x2A700..=0x2B73F
+            | 0x2B740..=0x2B81F
+            | 0x2B920..=0x2CEAF
+            | 0xF900..=0xFAFF
+            | 0x2F800..=0x2FA1F
+    )
+}
+
+#[derive(Debug, Clone)]
+enum PreTokenizer {
+    None,
+    Bert,
+    ByteLevel { add_prefix_space: bool },
+}
+
+impl PreTokenizer {
+    fn split(&self, chars: Vec<AlignedChar>) -> Vec<Vec<AlignedChar>> {
+        match *self {
+            PreTokenizer::None => {
+                if chars.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![chars]
+                }
+            }
+            PreTokenizer::Bert => {
+                let mut words = Vec::new();
+                let mut current = Vec::new();
+                for ac in chars {
+                    if ac.c.is_whitespace() {
+                        if !current.is_empty() {
+                            words.push(std::mem::take(&mut current));
+                        }
+                    } else if is_bert_punctuation(ac.c) {
+                        if !current.is_empty() {
+                            words.push(std::mem::take(&mut current));
+                        }
+                        words.push(vec![ac]);
+                    } else {
+                        current.push(ac);
+                    }
+                }
+                if !current.is_empty() {
+                    words.push(current);
+                }
+                words
+            }
+            PreTokenizer::ByteLevel { add_prefix_space } => {
+                let mut chars = chars;
+                if add_prefix_space {
+                    if let Some(&first) = chars.first() {
+                        if first.c != ' ' {
+                            chars.insert(0, AlignedChar { c: ' ', ..first });
+                        }
+                    }
+                }
+
+                let mut words = Vec::new();
+                let mut pos = 0;
+                while pos < chars.len() {
+                    let len = gpt2_match_len(&chars, pos);
+                    let word = chars[pos..pos + len]
+                        .iter()
+                        .flat_map(|ac| {
+                            let mut buf = [0u8; 4];
+                            let bytes = ac.c.encode_utf8(&mut buf).as_bytes().to_vec();
+                            bytes.into_iter().map(move |b| AlignedChar {
+                                c: byte_to_char(b),
+                                ..*ac
+                            })
+                        })
+                        .collect();
+                    words.push(word);
+                    pos += len;
+                }
+                words
+            }
+        }
+    }
+}
+
+/// Length of the GPT-2 pre-tokenization match starting at `pos`
+///
+/// Hand-rolled equivalent of
+/// `'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+`.
+fn gpt2_match_len(chars: &[AlignedChar], pos: usize) -> usize {
+    let at = |i: usize| chars.get(i).map(|ac| ac.c);
+
+    if at(pos) == Some('\'') {
+        for suffix in ["s", "t", "re", "ve", "m", "ll", "d"] {
+            if suffix
+                .chars()
+                .enumerate()
+                .all(|(i, c)| at(pos + 1 + i) == Some(c))
+            {
+                return 1 + suffix.len();
+            }
+        }
+    }
+
+    let is_other = |c: char| !c.is_whitespace() && !c.is_letter() && !c.is_number();
+    let classes: [&dyn Fn(char) -> bool; 3] = [
+        &|c: char| c.is_letter(),
+        &|c: char| c.is_number(),
+        &is_other,
+    ];
+    let run = |start: usize, class: &dyn Fn(char) -> bool| {
+        chars[start..].iter().take_while(|ac| class(ac.c)).count()
+    };
+
+    for class in classes {
+        if at(pos) == Some(' ') {
+            let len = if pos + 1 < chars.len() {
+                run(pos + 1, class)
+            } else {
+                0
+            };
+            if len > 0 {
+                return 1 + len;
+            }
+        }
+        let len = run(pos, class);
+        if len > 0 {
+            return len;
+        }
+    }
+
+    // Whitespace: keep the last space attached to the following word
+    let len = run(pos, &|c: char| c.is_whitespace());
+    if pos + len == chars.len() || len == 1 {
+        len
+    } else {
+        len - 1
+    }
+}
+
+/// GPT-2 mapping of bytes to printable unicode characters
+fn byte_to_char(b: u8) -> char {
+    let printable = |b: u8| matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF);
+    if printable(b) {
+        return b as char;
+    }
+    let shift = (0..b).filter(|&other| !printable(other)).count() as u32;
+    char::from_u32(256 + shift).unwrap_or('\u{fffd}')
+}
+
+#[derive(Debug, Clone)]
+enum Model {
+    WordPiece {
+        vocab: HashMap<String, u32>,
+        unk_token: String,
+        prefix: String,
+        max_input_chars_per_word: usize,
+    },
+    Bpe {
+        vocab: HashMap<String, u32>,
+        vocab_r: HashMap<u32, String>,
+        merges: HashMap<(u32, u32), (u32, u32)>,
+        unk_token: Option<String>,
+        prefix: Option<String>,
+        suffix: Option<String>,
+        ignore_merges: bool,
+    },
+}
+
+impl Model {
+    fn bpe(
+        vocab: HashMap<String, u32>,
+        merge_list: Vec<MergeSpec>,
+        unk_token: Option<String>,
+        prefix: Option<String>,
+        suffix: Option<String>,
+        ignore_merges: bool,
+    ) -> Result<Self, String> {
+        let id = |token: &str| {
+            vocab.get(token).copied().ok_or_else(|| {
+                format!(
+                    "Merge references token missing from the vocabulary: {}",
+                    token
+                )
+            })
+        };
+
+        let mut merges = HashMap::with_capacity(merge_list.len());
+        for (rank, merge) in merge_list.into_iter().enumerate() {
+            let (left, right) = match merge {
+                MergeSpec::Pair(left, right) => (left, right),
+                MergeSpec::Joined(joined) => {
+                    let mut parts = joined.splitn(2, ' ');
+                    match (parts.next(), parts.next()) {
+                        (Some(left), Some(right)) => (left.to_string(), right.to_string()),
+                        _ => return Err(format!("Invalid merge: {}", joined)),
+                    }
+                }
+            };
+            let right_body = match &prefix {
+                Some(prefix) => right.strip_prefix(prefix.as_str()).unwrap_or(&right),
+                None => &right,
+            };
+            let merged = id(&format!("{}{}", left, right_body))?;
+            merges.insert((id(&left)?, id(&right)?), (rank as u32, merged));
+        }
+
+        let vocab_r = vocab
+            .iter()
+            .map(|(token, &id)| (id, token.clone()))
+            .collect();
+
+        Ok(Model::Bpe {
+            vocab,
+            vocab_r,
+            merges,
+            unk_token,
+            prefix,
+            suffix,
+            ignore_merges,
+        })
+    }
+
+    fn vocab(&self) -> &HashMap<String, u32> {
+        match self {
+            Model::WordPiece { vocab, .. } | Model::Bpe { vocab, .. } => vocab,
+        }
+    }
+
+    fn tokenize(&self, word: &[AlignedChar], out: &mut Vec<Token>) {
+        match self {
+            Model::WordPiece {
+                vocab,
+                unk_token,
+                prefix,
+                max_input_chars_per_word,
+            } => {
+                let unk = || Token {
+                    id: vocab[unk_token],
+                    value: unk_token.clone(),
+                    offsets: span(word),
+                };
+                if word.len() > *max_input_chars_per_word {
+                    out.push(unk());
+                    return;
+                }
+
+                let mut pieces = Vec::new();
+                let mut start = 0;
+                while start < word.len() {
+                    let mut end = word.len();
+                    let mut found = None;
+                    while start < end {
+                        let mut candidate: String =
+                            word[start..end].iter().map(|ac| ac.c).collect();
+                        if start > 0 {
+                            candidate.insert_str(0, prefix);
+                        }
+                        if let Some(&id) = vocab.get(&candidate) {
+                            found = Some(Token {
+                                id,
+                                value: candidate,
+                                offsets: span(&word[start..end]),
+                            });
+                            break;
+                        }
+                        end -= 1;
+                    }
+                    match found {
+                        Some(token) => pieces.push(token),
+                        None => {
+                            out.push(unk());
+                            return;
+                        }
+                    }
+                    start = end;
+                }
+                out.extend(pieces);
+            }
+            Model::Bpe {
+                vocab,
+                vocab_r,
+                merges,
+                unk_token,
+                prefix,
+                suffix,
+                ignore_merges,
+            } => {
+                if *ignore_merges {
+                    let whole: String = word.iter().map(|ac| ac.c).collect();
+                    if let Some(&id) = vocab.get(&whole) {
+                        out.push(Token {
+                            id,
+                            value: whole,
+                            offsets: span(word),
+                        });
+                        return;
+                    }
+                }
+
+                // Symbols are (token id, first char, one past last char)
+                let mut symbols: Vec<(u32, usize, usize)> = Vec::with_capacity(word.len());
+                for (i, ac) in word.iter().enumerate() {
+                    let mut symbol = ac.c.to_string();
+                    if i > 0 {
+                        if let Some(prefix) = prefix {
+                            symbol.insert_str(0, prefix);
+                        }
+                    }
+                    if i + 1 == word.len() {
+                        if let Some(suffix) = suffix {
+                            symbol.push_str(suffix);
+                        }
+                    }
+                    match vocab.get(&symbol) {
+                        Some(&id) => symbols.push((id, i, i + 1)),
+                        None => {
+                            if let Some(&id) = unk_token.as_ref().and_then(|unk| vocab.get(unk)) {
+                                symbols.push((id, i, i + 1));
+                            }
+                        }
+                    }
+                }
+
+                loop {
+                    let best = symbols
+                        .windows(2)
+                        .enumerate()
+                        .filter_map(|(i, pair)| {
+                            merges.get(&(pair[0].0, pair[1].0)).map(|&m| (m, i))
+                        })
+                        .min_by_key(|&((rank, _), i)| (rank, i));
+                    match best {
+                        Some(((_, merged), i)) => {
+                            symbols[i] = (merged, symbols[i].1, symbols[i + 1].2);
+                            symbols.remove(i + 1);
+                        }
+                        None => break,
+                    }
+                }
+
+                for (id, start, end) in symbols {
+                    out.push(Token {
+                        id,
+                        value: vocab_r.get(&id).cloned().unwrap_or_default(),
+                        offsets: span(&word[start..end]),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum TemplatePiece {
+    Sequence(u32),
+    Special(Vec<(String, u32)>, u32),
+}
+
+#[derive(Debug, Clone)]
+enum PostProcessor {
+    None,
+    /// `[CLS] sequence [SEP]` as used by BERT and RoBERTa
+    Wrap {
+        cls: (String, u32),
+        sep: (String, u32),
+    },
+    Template(Vec<TemplatePiece>),
+}
+
+impl PostProcessor {
+    fn added_tokens(&self) -> usize {
+        match self {
+            PostProcessor::None => 0,
+            PostProcessor::Wrap { .. } => 2,
+            PostProcessor::Template(pieces) => pieces
+                .iter()
+                .map(|piece| match piece {
+                    TemplatePiece::Sequence(_) => 0,
+                    TemplatePiece::Special(tokens, _) => tokens.len(),
+                })
+                .sum(),
+        }
+    }
+}
+
+// Subset of the Hugging Face `tokenizer.json` schema that this module understands
+
+#[derive(Deserialize)]
+struct TokenizerFile {
+    #[serde(default)]
+    added_tokens: Vec<AddedTokenSpec>,
+    normalizer: Option<NormalizerSpec>,
+    pre_tokenizer: Option<PreTokenizerSpec>,
+    post_processor: Option<PostProcessorSpec>,
+    model: ModelSpec,
+}
+
+#[derive(Deserialize)]
+struct AddedTokenSpec {
+    id: u32,
+    content: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum NormalizerSpec {
+    BertNormalizer {
+        clean_text: bool,
+        handle_chinese_chars: bool,
+        strip_accents: Option<bool>,
+        lowercase: bool,
+    },
+    Lowercase,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum PreTokenizerSpec {
+    BertPreTokenizer,
+    ByteLevel {
+        #[serde(default = "default_true")]
+        add_prefix_space: bool,
+        #[serde(default = "default_true")]
+        use_regex: bool,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+#[allow(clippy::enum_variant_names)]
+enum PostProcessorSpec {
+    BertProcessing {
+        cls: (String, u32),
+        sep: (String, u32),
+    },
+    RobertaProcessing {
+        cls: (String, u32),
+        sep: (String, u32),
+    },
+    TemplateProcessing {
+        single: Vec<TemplatePieceSpec>,
+        special_tokens: HashMap<String, SpecialTokenSpec>,
+    },
+}
+
+#[derive(Deserialize)]
+enum TemplatePieceSpec {
+    Sequence { type_id: u32 },
+    SpecialToken { id: String, type_id: u32 },
+}
+
+#[derive(Deserialize)]
+struct SpecialTokenSpec {
+    ids: Vec<u32>,
+    tokens: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+#[allow(clippy::upper_case_acronyms)]
+enum ModelSpec {
+    WordPiece {
+        vocab: HashMap<String, u32>,
+        unk_token: String,
+        continuing_subword_prefix: String,
+        max_input_chars_per_word: usize,
+    },
+    BPE {
+        vocab: HashMap<String, u32>,
+        merges: Vec<MergeSpec>,
+        unk_token: Option<String>,
+        continuing_subword_prefix: Option<String>,
+        end_of_word_suffix: Option<String>,
+        #[serde(default)]
+        ignore_merges: bool,
+        dropout: Option<f32>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MergeSpec {
+    Joined(String),
+    Pair(String, String),
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    // Expected encodings were produced by the Hugging Face `tokenizers` crate (0.19)
+    // from the texts in tests/fixtures/tokenizer/corpus.txt.
+    const WORDPIECE_VOCAB: &str = include_str!("../tests/fixtures/tokenizer/wordpiece-vocab.txt");
+    const WORDPIECE_JSON: &str =
+        include_str!("../tests/fixtures/tokenizer/wordpiece_tokenizer.json");
+    const WORDPIECE_EXPECTED: &str =
+        include_str!("../tests/fixtures/tokenizer/wordpiece_expected.jsonl");
+    const BPE_JSON: &str = include_str!("../tests/fixtures/tokenizer/bpe_tokenizer.json");
+    const BPE_EXPECTED: &str = include_str!("../tests/fixtures/tokenizer/bpe_expected.jsonl");
+
+    fn u32s(value: &Value) -> Vec<u32> {
+        serde_json::from_value(value.clone()).unwrap()
+    }
+
+    fn check_fixture(mut tokenizer: Tokenizer, expected: &str) {
+        let mut cases = 0;
+        for line in expected.lines() {
+            let case: Value = serde_json::from_str(line).unwrap();
+            match case["mode"].as_str().unwrap() {
+                "single" => {
+                    let text = case["text"].as_str().unwrap();
+                    let encoding = tokenizer.encode(text);
+                    assert_eq!(
+                        encoding.tokens(),
+                        serde_json::from_value::<Vec<String>>(case["tokens"].clone()).unwrap(),
+                        "{:?}",
+                        text
+                    );
+                    assert_eq!(encoding.ids(), u32s(&case["ids"]), "{:?}", text);
+                    assert_eq!(encoding.type_ids(), u32s(&case["type_ids"]), "{:?}", text);
+                    assert_eq!(
+                        encoding.attention_mask(),
+                        u32s(&case["attention_mask"]),
+                        "{:?}",
+                        text
+                    );
+                    let offsets: Vec<(usize, usize)> =
+                        serde_json::from_value(case["offsets"].clone()).unwrap();
+                    assert_eq!(encoding.offset_pairs(), &offsets[..], "{:?}", text);
+                }
+                "batch" => {
+                    tokenizer.set_truncation(case["max_length"].as_u64().unwrap() as usize);
+                    tokenizer.set_padding(None);
+                    let texts: Vec<String> = serde_json::from_value(case["texts"].clone()).unwrap();
+                    let encodings = tokenizer.encode_batch(texts);
+                    let expected = case["encodings"].as_array().unwrap();
+                    assert_eq!(encodings.len(), expected.len());
+                    for (encoding, expected) in encodings.iter().zip(expected) {
+                        assert_eq!(encoding.ids(), u32s(&expected["ids"]));
+                        assert_eq!(encoding.type_ids(), u32s(&expected["type_ids"]));
+                        assert_eq!(encoding.attention_mask(), u32s(&expected["attention_mask"]));
+                    }
+                    tokenizer.clear_truncation();
+                    tokenizer.clear_padding();
+                }
+                mode => panic!("unknown fixture mode {}", mode),
+            }
+            cases += 1;
+        }
+        assert!(cases > 0);
+    }
+
+    #[test]
+    fn test_wordpiece_vocab_matches_reference() {
+        let tokenizer = Tokenizer::from_vocab(WORDPIECE_VOCAB, true).unwrap();
+        check_fixture(tokenizer, WORDPIECE_EXPECTED);
+    }
+
+    #[test]
+    fn test_wordpiece_json_matches_reference() {
+        let tokenizer = Tokenizer::from_json(WORDPIECE_JSON).unwrap();
+        check_fixture(tokenizer, WORDPIECE_EXPECTED);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_from_file_errors() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let tokenizer = Tokenizer::from_file(fixtures.join("tokenizer/wordpiece_tokenizer.json"));
+        check_fixture(tokenizer.unwrap(), WORDPIECE_EXPECTED);
+
+        let Err(err) = Tokenizer::from_file(fixtures.join("tokenizer/missing.json")) else {
+            panic!("a missing file should not load");
+        };
+        assert_eq!(err.code(), "IO_ERROR");
+        assert!(err.details()["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("missing.json"));
+
+        // Anything but .json is read as a vocabulary, which this is not
+        let Err(err) = Tokenizer::from_file(fixtures.join("tokenizer/corpus.txt")) else {
+            panic!("a corpus is not a vocabulary");
+        };
+        assert_eq!(err.code(), "PARSE_ERROR");
+    }
+
+    #[test]
+    fn test_byte_level_bpe_matches_reference() {
+        let tokenizer = Tokenizer::from_json(BPE_JSON).unwrap();
+        check_fixture(tokenizer, BPE_EXPECTED);
+    }
+
+    #[test]
+    fn test_fixed_padding_and_truncation() {
+        let mut tokenizer = Tokenizer::from_vocab(WORDPIECE_VOCAB, true).unwrap();
+        tokenizer.set_truncation(4);
+        tokenizer.set_padding(Some(6));
+
+        let encoding = tokenizer.encode("a retired detective uncovers a conspiracy");
+        assert_eq!(encoding.len(), 6);
+        assert_eq!(encoding.tokens()[..4], ["[CLS]", "a", "retired", "[SEP]"]);
+        assert_eq!(encoding.attention_mask(), vec![1, 1, 1, 1, 0, 0]);
+        assert_eq!(encoding.special_tokens_mask(), vec![1, 0, 0, 1, 1, 1]);
+        assert_eq!(
+            tokenizer.id_to_token(encoding.ids()[5]).as_deref(),
+            Some("[PAD]")
+        );
+    }
+
+    #[test]
+    fn test_overlapping_windows() {
+        let tokenizer = Tokenizer::from_vocab(WORDPIECE_VOCAB, true).unwrap();
+        let text = "a retired detective uncovers a conspiracy";
+        let tokens: Vec<String> = tokenizer.tokenize(text).into_iter().map(|t| t.0).collect();
+
+        // Three model tokens per window plus [CLS] and [SEP], one token shared
+        let windows = tokenizer.encode_windows(text, 5, 1);
+        let mut covered: Vec<String> = Vec::new();
+        for (i, window) in windows.iter().enumerate() {
+            assert!(window.len() <= 5);
+            let content = &window.tokens()[1..window.len() - 1];
+            if i > 0 {
+                assert_eq!(content[0], covered[covered.len() - 1]);
+            }
+            covered.extend(content.iter().skip((i > 0) as usize).cloned());
+        }
+        assert_eq!(covered, tokens);
+        assert_eq!(windows[0].offset_pairs()[1], (0, 1));
+
+        assert_eq!(tokenizer.encode_windows("", 5, 1).len(), 1);
+    }
+}
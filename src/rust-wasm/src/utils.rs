@@ -2,6 +2,8 @@
 
 use wasm_bindgen::prelude::*;
 
+use crate::error::Error;
+
 /// Set panic hook for better error messages
 #[wasm_bindgen]
 pub fn set_panic_hook() {
@@ -9,19 +11,195 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
-/// Allocate memory for passing large arrays
+/// Vectors stored in WASM memory that JavaScript fills by copying in
+///
+/// The buffer owns its memory and frees it when dropped (or when JavaScript
+/// calls `free()`). JavaScript never sees a pointer or a view of WASM memory:
+/// it copies vectors in with [`VectorBuffer::fill_from`], and engine methods
+/// borrow the buffer by handle, so a freed buffer is rejected by the bindings
+/// instead of being read or written.
 #[wasm_bindgen]
-pub fn allocate(size: usize) -> *mut u8 {
-    let mut buf = Vec::with_capacity(size);
-    let ptr = buf.as_mut_ptr();
-    std::mem::forget(buf);
-    ptr
+#[derive(Debug, Clone)]
+pub struct VectorBuffer {
+    data: Vec<f32>,
+    dimension: usize,
 }
 
-/// Deallocate memory
 #[wasm_bindgen]
-pub fn deallocate(ptr: *mut u8, size: usize) {
-    unsafe {
-        let _ = Vec::from_raw_parts(ptr, 0, size);
+impl VectorBuffer {
+    /// A zero-filled buffer holding `count` vectors of `dimension` values
+    #[wasm_bindgen(constructor)]
+    pub fn new(dimension: usize, count: usize) -> Result<VectorBuffer, Error> {
+        let invalid = |message: String| Error::InvalidArgument {
+            operation: "VectorBuffer::new",
+            message,
+        };
+        if dimension == 0 {
+            return Err(invalid("dimension must be positive".to_string()));
+        }
+        let len = dimension.checked_mul(count).ok_or_else(|| {
+            invalid(format!(
+                "{} vectors of {} values overflow",
+                count, dimension
+            ))
+        })?;
+        let mut data = Vec::new();
+        data.try_reserve_exact(len)
+            .map_err(|e| invalid(format!("cannot allocate {} values: {}", len, e)))?;
+        data.resize(len, 0.0);
+        Ok(VectorBuffer { data, dimension })
+    }
+
+    /// Copy the vectors in `values` into the buffer, starting at vector `first`
+    ///
+    /// `values` must hold a whole number of vectors that fit. The copy goes
+    /// straight from the JavaScript array into the buffer without an
+    /// intermediate allocation.
+    pub fn fill_from(&mut self, values: &js_sys::Float32Array, first: usize) -> Result<(), Error> {
+        let slots = self.slots_mut("VectorBuffer::fill_from", first, values.length() as usize)?;
+        values.copy_to(slots);
+        Ok(())
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Number of vectors the buffer holds
+    pub fn count(&self) -> usize {
+        self.data.len() / self.dimension
+    }
+
+    /// Number of `f32` values the buffer holds
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl VectorBuffer {
+    /// Wrap a flat buffer of `dimension`-sized vectors
+    pub fn from_vec(data: Vec<f32>, dimension: usize) -> Result<Self, Error> {
+        Error::check_batch("VectorBuffer::from_vec", &data, dimension)?;
+        Ok(VectorBuffer { data, dimension })
+    }
+
+    /// Copy the vectors in `values` into the buffer, starting at vector `first`
+    pub fn fill(&mut self, values: &[f32], first: usize) -> Result<(), Error> {
+        self.slots_mut("VectorBuffer::fill", first, values.len())?
+            .copy_from_slice(values);
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    /// The `len` values starting at vector `first`, if they are whole vectors that fit
+    fn slots_mut(
+        &mut self,
+        operation: &'static str,
+        first: usize,
+        len: usize,
+    ) -> Result<&mut [f32], Error> {
+        if !len.is_multiple_of(self.dimension) {
+            return Err(Error::InvalidBatch {
+                operation,
+                len,
+                dimension: self.dimension,
+            });
+        }
+        let count = self.count();
+        let start = first.checked_mul(self.dimension);
+        match start.and_then(|start| Some(start..start.checked_add(len)?)) {
+            Some(range) if range.end <= self.data.len() => Ok(&mut self.data[range]),
+            _ => Err(Error::InvalidArgument {
+                operation,
+                message: format!(
+                    "{} vectors from {} do not fit in a buffer of {}",
+                    len / self.dimension,
+                    first,
+                    count
+                ),
+            }),
+        }
+    }
+}
+
+/// Check that a deserialized field holds `expected` values
+pub(crate) fn check_len(name: &str, len: usize, expected: usize) -> Result<(), String> {
+    if len == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} has {} values, expected {}",
+            name, len, expected
+        ))
+    }
+}
+
+/// Small deterministic PRNG (SplitMix64) for seeded sampling
+///
+/// Used wherever results must be reproducible across native and WASM builds.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform sample in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform index in [0, n); `n` must be non-zero
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize % n
+    }
+
+    /// Standard normal sample (Box-Muller)
+    pub fn normal(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+
+    /// Choose `k` distinct indices from `0..n` (all of them if `k >= n`)
+    ///
+    /// Uses Floyd's algorithm, so the cost depends on `k` rather than `n`.
+    pub fn sample_indices(&mut self, n: usize, k: usize) -> Vec<usize> {
+        let k = k.min(n);
+        let mut chosen = std::collections::HashSet::with_capacity(k);
+        let mut indices = Vec::with_capacity(k);
+        for j in n - k..n {
+            let candidate = self.below(j + 1);
+            let index = if chosen.insert(candidate) {
+                candidate
+            } else {
+                chosen.insert(j);
+                j
+            };
+            indices.push(index);
+        }
+        indices
     }
 }
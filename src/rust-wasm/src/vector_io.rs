@@ -0,0 +1,771 @@
+//! Import and export of embedding matrices in common interchange formats
+//!
+//! Supports NumPy `.npy` (float32/float16, little-endian, C order), the
+//! `.fvecs`/`.bvecs` formats used by ANN benchmarks, and safetensors. Readers
+//! decode one vector at a time so large files can be streamed into a
+//! [`VectorIndex`](crate::vector_search::VectorIndex) without an intermediate
+//! copy of the whole matrix.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+use wasm_bindgen::prelude::*;
+
+use crate::safetensors::{f16_to_f32, f32_to_f16, serialize_f32, Dtype, SafeTensors};
+
+/// Magic prefix of every `.npy` file
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Largest finite float16 value
+const F16_MAX: f32 = 65504.0;
+
+/// Tensor name used when exporting to (and, by default, importing from) safetensors
+pub const SAFETENSORS_TENSOR: &str = "embeddings";
+
+/// Supported file formats
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFormat {
+    /// NumPy array of shape `(count, dimension)`
+    Npy,
+    /// Records of a little-endian `i32` dimension followed by that many `f32`
+    Fvecs,
+    /// Records of a little-endian `i32` dimension followed by that many `u8`
+    Bvecs,
+    /// A single 2-D tensor named [`SAFETENSORS_TENSOR`]
+    Safetensors,
+}
+
+/// Element type written to `.npy` files
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NpyDtype {
+    F32,
+    F16,
+}
+
+/// Errors raised while decoding or encoding vector files
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatError {
+    /// The underlying reader or writer failed
+    Io(String),
+    /// The data ended in the middle of a header or record
+    Truncated { context: &'static str },
+    /// The file does not start with the expected magic bytes
+    BadMagic,
+    /// The header could not be parsed
+    InvalidHeader(String),
+    /// The element type is not supported by this format
+    UnsupportedDtype(String),
+    /// The data is stored big-endian
+    BigEndian(String),
+    /// The array is stored in Fortran (column-major) order
+    FortranOrder,
+    /// The array is not a matrix of vectors
+    InvalidShape(Vec<usize>),
+    /// A vector does not have the expected dimension
+    DimensionMismatch { expected: usize, actual: usize },
+    /// Flat data cannot be split into whole vectors of the given dimension
+    RaggedData { len: usize, dimension: usize },
+    /// A value cannot be represented in the target format
+    ValueOutOfRange { value: f32, format: &'static str },
+    /// A safetensors file has no tensor with this name
+    MissingTensor(String),
+    /// A named tensor does not have the shape its consumer expects
+    TensorShape {
+        name: String,
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "I/O error: {}", e),
+            FormatError::Truncated { context } => {
+                write!(f, "Unexpected end of data in {}", context)
+            }
+            FormatError::BadMagic => write!(f, "Not a .npy file: missing \\x93NUMPY magic"),
+            FormatError::InvalidHeader(e) => write!(f, "Invalid header: {}", e),
+            FormatError::UnsupportedDtype(dtype) => write!(
+                f,
+                "Unsupported dtype {}: expected float32 ('<f4') or float16 ('<f2')",
+                dtype
+            ),
+            FormatError::BigEndian(dtype) => write!(
+                f,
+                "Big-endian dtype {} is not supported; convert with arr.astype('<f4')",
+                dtype
+            ),
+            FormatError::FortranOrder => write!(
+                f,
+                "Fortran-ordered arrays are not supported; convert with np.ascontiguousarray"
+            ),
+            FormatError::InvalidShape(shape) => {
+                write!(
+                    f,
+                    "Expected a (count, dimension) matrix, got shape {:?}",
+                    shape
+                )
+            }
+            FormatError::DimensionMismatch { expected, actual } => write!(
+                f,
+                "Vector dimension mismatch: expected {}, got {}",
+                expected, actual
+            ),
+            FormatError::RaggedData { len, dimension } => write!(
+                f,
+                "{} values cannot be split into vectors of dimension {}",
+                len, dimension
+            ),
+            FormatError::ValueOutOfRange { value, format } => {
+                write!(f, "Value {} cannot be stored in {}", value, format)
+            }
+            FormatError::MissingTensor(name) => write!(f, "Missing tensor: {}", name),
+            FormatError::TensorShape {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Tensor {} has shape {:?}, expected {:?}",
+                name, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> Self {
+        FormatError::Io(e.to_string())
+    }
+}
+
+impl From<FormatError> for JsValue {
+    fn from(e: FormatError) -> Self {
+        crate::error::Error::from(e).into()
+    }
+}
+
+/// How the payload of each vector is encoded
+#[derive(Debug, Clone, Copy)]
+enum Element {
+    F32,
+    F16,
+    U8,
+}
+
+impl Element {
+    fn size(self) -> usize {
+        match self {
+            Element::F32 => 4,
+            Element::F16 => 2,
+            Element::U8 => 1,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], out: &mut Vec<f32>) {
+        out.clear();
+        match self {
+            Element::F32 => out.extend(
+                bytes
+                    .as_chunks::<4>()
+                    .0
+                    .iter()
+                    .map(|b| f32::from_le_bytes(*b)),
+            ),
+            Element::F16 => out.extend(
+                bytes
+                    .as_chunks::<2>()
+                    .0
+                    .iter()
+                    .map(|b| f16_to_f32(u16::from_le_bytes(*b))),
+            ),
+            Element::U8 => out.extend(bytes.iter().map(|&b| b as f32)),
+        }
+    }
+}
+
+/// Layout of the vectors still to be read
+#[derive(Debug)]
+enum Layout {
+    /// A fixed number of vectors of one dimension (npy)
+    Matrix { remaining: usize },
+    /// Self-describing records until end of input (fvecs, bvecs)
+    Records,
+    /// Vectors already decoded in full (safetensors)
+    Decoded { data: Vec<f32>, next: usize },
+}
+
+/// Streaming decoder yielding one vector at a time
+pub struct VectorReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    element: Element,
+    layout: Layout,
+    dimension: Option<usize>,
+    raw: Vec<u8>,
+    vector: Vec<f32>,
+}
+
+impl<'a> VectorReader<'a> {
+    /// Start reading vectors in `format`
+    ///
+    /// Safetensors cannot be streamed record by record, so that format reads the
+    /// whole input up front.
+    pub fn new<R: Read + 'a>(mut inner: R, format: VectorFormat) -> Result<Self, FormatError> {
+        let (element, layout, dimension) = match format {
+            VectorFormat::Npy => {
+                let header = read_npy_header(&mut inner)?;
+                let (count, dimension) = matrix_shape(&header.shape)?;
+                (
+                    header.element,
+                    Layout::Matrix { remaining: count },
+                    Some(dimension),
+                )
+            }
+            VectorFormat::Fvecs => (Element::F32, Layout::Records, None),
+            VectorFormat::Bvecs => (Element::U8, Layout::Records, None),
+            VectorFormat::Safetensors => {
+                let mut bytes = Vec::new();
+                inner.read_to_end(&mut bytes)?;
+                let (shape, data) = read_safetensors(&bytes, SAFETENSORS_TENSOR)?;
+                let (_, dimension) = matrix_shape(&shape)?;
+                (
+                    Element::F32,
+                    Layout::Decoded { data, next: 0 },
+                    Some(dimension),
+                )
+            }
+        };
+
+        Ok(VectorReader {
+            inner: Box::new(inner),
+            element,
+            layout,
+            dimension,
+            raw: Vec::new(),
+            vector: Vec::new(),
+        })
+    }
+
+    /// Dimension of the vectors, once known
+    ///
+    /// Matrix formats know it from the header; record formats after the first vector.
+    pub fn dimension(&self) -> Option<usize> {
+        self.dimension
+    }
+
+    /// Decode the next vector, or `None` at the end of the input
+    pub fn next_vector(&mut self) -> Result<Option<&[f32]>, FormatError> {
+        let dimension = match &mut self.layout {
+            Layout::Decoded { data, next } => {
+                let dimension = self.dimension.unwrap_or(0);
+                if *next >= data.len() {
+                    return Ok(None);
+                }
+                *next += dimension;
+                return Ok(Some(&data[*next - dimension..*next]));
+            }
+            Layout::Matrix { remaining: 0 } => return Ok(None),
+            Layout::Matrix { remaining } => {
+                *remaining -= 1;
+                self.dimension.unwrap_or(0)
+            }
+            Layout::Records => {
+                let mut prefix = [0u8; 4];
+                if !read_exact_or_eof(&mut self.inner, &mut prefix, "record dimension")? {
+                    return Ok(None);
+                }
+                let declared = i32::from_le_bytes(prefix);
+                let actual = usize::try_from(declared).map_err(|_| {
+                    FormatError::InvalidHeader(format!("negative record dimension {}", declared))
+                })?;
+                match self.dimension {
+                    Some(expected) if expected != actual => {
+                        return Err(FormatError::DimensionMismatch { expected, actual })
+                    }
+                    _ => self.dimension = Some(actual),
+                }
+                actual
+            }
+        };
+
+        let len = dimension.checked_mul(self.element.size()).ok_or_else(|| {
+            FormatError::InvalidHeader(format!("vector dimension {} is too large", dimension))
+        })?;
+        read_to_len(&mut self.inner, len, &mut self.raw, "vector data")?;
+        self.element.decode(&self.raw, &mut self.vector);
+        Ok(Some(&self.vector))
+    }
+}
+
+/// Fill `buf` completely, returning `false` if the input was already at its end
+fn read_exact_or_eof<R: Read>(
+    reader: &mut R,
+    buf: &mut [u8],
+    context: &'static str,
+) -> Result<bool, FormatError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(FormatError::Truncated { context }),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
+
+struct NpyHeader {
+    element: Element,
+    shape: Vec<usize>,
+}
+
+fn read_npy_header<R: Read>(reader: &mut R) -> Result<NpyHeader, FormatError> {
+    let mut preamble = [0u8; 8];
+    if !read_exact_or_eof(reader, &mut preamble, "npy preamble")? || &preamble[..6] != NPY_MAGIC {
+        return Err(FormatError::BadMagic);
+    }
+
+    let header_len = match preamble[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            read_exact(reader, &mut len, "npy header length")?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            read_exact(reader, &mut len, "npy header length")?;
+            u32::from_le_bytes(len) as usize
+        }
+        version => {
+            return Err(FormatError::InvalidHeader(format!(
+                "unsupported .npy version {}",
+                version
+            )))
+        }
+    };
+
+    let mut header = Vec::new();
+    read_to_len(reader, header_len, &mut header, "npy header")?;
+    let header = String::from_utf8(header)
+        .map_err(|_| FormatError::InvalidHeader("header is not valid UTF-8".into()))?;
+    parse_npy_header(&header)
+}
+
+fn read_exact<R: Read>(
+    reader: &mut R,
+    buf: &mut [u8],
+    context: &'static str,
+) -> Result<(), FormatError> {
+    if read_exact_or_eof(reader, buf, context)? || buf.is_empty() {
+        Ok(())
+    } else {
+        Err(FormatError::Truncated { context })
+    }
+}
+
+/// Replace `buf` with exactly `len` bytes of input
+///
+/// The buffer grows only as data arrives, so a corrupt length read from the
+/// input cannot force a huge allocation up front.
+fn read_to_len<R: Read>(
+    reader: &mut R,
+    len: usize,
+    buf: &mut Vec<u8>,
+    context: &'static str,
+) -> Result<(), FormatError> {
+    buf.clear();
+    reader.by_ref().take(len as u64).read_to_end(buf)?;
+    if buf.len() == len {
+        Ok(())
+    } else {
+        Err(FormatError::Truncated { context })
+    }
+}
+
+/// Parse the Python dict literal describing an array, e.g.
+/// `{'descr': '<f4', 'fortran_order': False, 'shape': (3, 4), }`
+fn parse_npy_header(header: &str) -> Result<NpyHeader, FormatError> {
+    let value_after = |key: &str| -> Result<&str, FormatError> {
+        let pattern = format!("'{}':", key);
+        header
+            .find(&pattern)
+            .map(|i| header[i + pattern.len()..].trim_start())
+            .ok_or_else(|| FormatError::InvalidHeader(format!("missing '{}'", key)))
+    };
+
+    let descr = value_after("descr")?;
+    let descr = descr
+        .strip_prefix('\'')
+        .and_then(|rest| rest.split('\'').next())
+        .ok_or_else(|| FormatError::InvalidHeader("malformed 'descr'".into()))?;
+    let element = match descr {
+        "<f4" => Element::F32,
+        "<f2" => Element::F16,
+        ">f4" | ">f2" => return Err(FormatError::BigEndian(descr.to_string())),
+        other => return Err(FormatError::UnsupportedDtype(other.to_string())),
+    };
+
+    if value_after("fortran_order")?.starts_with("True") {
+        return Err(FormatError::FortranOrder);
+    }
+
+    let shape = value_after("shape")?;
+    let shape = shape
+        .strip_prefix('(')
+        .and_then(|rest| rest.split(')').next())
+        .ok_or_else(|| FormatError::InvalidHeader("malformed 'shape'".into()))?;
+    let shape = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| {
+            dim.trim_end_matches('L')
+                .parse()
+                .map_err(|_| FormatError::InvalidHeader(format!("invalid dimension '{}'", dim)))
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    Ok(NpyHeader { element, shape })
+}
+
+/// Interpret a shape as `(count, dimension)`; a 1-D array is a single vector
+fn matrix_shape(shape: &[usize]) -> Result<(usize, usize), FormatError> {
+    match *shape {
+        [dimension] if dimension > 0 => Ok((1, dimension)),
+        [count, dimension] if dimension > 0 => Ok((count, dimension)),
+        _ => Err(FormatError::InvalidShape(shape.to_vec())),
+    }
+}
+
+fn read_safetensors(bytes: &[u8], name: &str) -> Result<(Vec<usize>, Vec<f32>), FormatError> {
+    let tensors = SafeTensors::parse(bytes)?;
+    // Accept a file holding a single tensor under any name
+    let names = tensors.names();
+    let name = match names.as_slice() {
+        [only] => *only,
+        _ => name,
+    };
+    let info = tensors
+        .info(name)
+        .ok_or_else(|| FormatError::MissingTensor(name.to_string()))?;
+    if !matches!(
+        info.dtype,
+        Dtype::F64 | Dtype::F32 | Dtype::F16 | Dtype::BF16
+    ) {
+        return Err(FormatError::UnsupportedDtype(format!("{:?}", info.dtype)));
+    }
+    tensors.to_f32(name)
+}
+
+/// Decode a whole buffer into `(dimension, flat vectors)`
+pub fn read_vectors(bytes: &[u8], format: VectorFormat) -> Result<(usize, Vec<f32>), FormatError> {
+    let mut reader = VectorReader::new(bytes, format)?;
+    let mut data = Vec::new();
+    while let Some(vector) = reader.next_vector()? {
+        data.extend_from_slice(vector);
+    }
+    Ok((reader.dimension().unwrap_or(0), data))
+}
+
+/// Encode `data` (flat vectors of `dimension` floats) in `format`
+///
+/// `.npy` output uses float32; see [`write_npy`] for float16.
+pub fn write_vectors<W: Write>(
+    writer: &mut W,
+    data: &[f32],
+    dimension: usize,
+    format: VectorFormat,
+) -> Result<(), FormatError> {
+    check_ragged(data, dimension)?;
+    match format {
+        VectorFormat::Npy => write_npy(writer, data, dimension, NpyDtype::F32),
+        VectorFormat::Fvecs => {
+            let mut record = Vec::with_capacity(4 + 4 * dimension);
+            for vector in data.chunks_exact(dimension) {
+                record.clear();
+                record.extend_from_slice(&(dimension as i32).to_le_bytes());
+                for value in vector {
+                    record.extend_from_slice(&value.to_le_bytes());
+                }
+                writer.write_all(&record)?;
+            }
+            Ok(())
+        }
+        VectorFormat::Bvecs => {
+            let mut record = Vec::with_capacity(4 + dimension);
+            for vector in data.chunks_exact(dimension) {
+                record.clear();
+                record.extend_from_slice(&(dimension as i32).to_le_bytes());
+                for &value in vector {
+                    if !(0.0..=255.0).contains(&value) || value.fract() != 0.0 {
+                        return Err(FormatError::ValueOutOfRange {
+                            value,
+                            format: "bvecs (integers 0..=255)",
+                        });
+                    }
+                    record.push(value as u8);
+                }
+                writer.write_all(&record)?;
+            }
+            Ok(())
+        }
+        VectorFormat::Safetensors => {
+            let shape = [data.len() / dimension, dimension];
+            let bytes = serialize_f32(&[(SAFETENSORS_TENSOR, &shape, data)], &HashMap::new());
+            writer.write_all(&bytes)?;
+            Ok(())
+        }
+    }
+}
+
+/// Write a C-ordered `(count, dimension)` `.npy` array
+pub fn write_npy<W: Write>(
+    writer: &mut W,
+    data: &[f32],
+    dimension: usize,
+    dtype: NpyDtype,
+) -> Result<(), FormatError> {
+    check_ragged(data, dimension)?;
+    let descr = match dtype {
+        NpyDtype::F32 => "<f4",
+        NpyDtype::F16 => "<f2",
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}",
+        descr,
+        data.len() / dimension,
+        dimension
+    );
+    // Pad with spaces so the data starts on a 64-byte boundary, ending in a newline
+    let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    // Encode a row at a time so unbuffered writers see one call per vector
+    let mut row = Vec::with_capacity(4 * dimension);
+    for vector in data.chunks_exact(dimension) {
+        row.clear();
+        match dtype {
+            NpyDtype::F32 => {
+                for value in vector {
+                    row.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            NpyDtype::F16 => {
+                for &value in vector {
+                    // Infinities and NaN have float16 encodings; large finite values do not
+                    if value.abs() > F16_MAX && value.is_finite() {
+                        return Err(FormatError::ValueOutOfRange {
+                            value,
+                            format: "float16",
+                        });
+                    }
+                    row.extend_from_slice(&f32_to_f16(value).to_le_bytes());
+                }
+            }
+        }
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Check that `data` holds a whole number of vectors of `dimension`
+fn check_ragged(data: &[f32], dimension: usize) -> Result<(), FormatError> {
+    if dimension == 0 || !data.len().is_multiple_of(dimension) {
+        return Err(FormatError::RaggedData {
+            len: data.len(),
+            dimension,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: [f32; 6] = [0.5, -1.0, 2.0, 0.0, 3.0, 255.0];
+
+    fn round_trip(format: VectorFormat) -> (usize, Vec<f32>) {
+        let mut bytes = Vec::new();
+        write_vectors(&mut bytes, &DATA, 3, format).unwrap();
+        read_vectors(&bytes, format).unwrap()
+    }
+
+    #[test]
+    fn test_round_trips() {
+        for format in [
+            VectorFormat::Npy,
+            VectorFormat::Fvecs,
+            VectorFormat::Safetensors,
+        ] {
+            assert_eq!(round_trip(format), (3, DATA.to_vec()), "{:?}", format);
+        }
+
+        let integral = [1.0, 2.0, 3.0, 4.0];
+        let mut bytes = Vec::new();
+        write_vectors(&mut bytes, &integral, 2, VectorFormat::Bvecs).unwrap();
+        assert_eq!(bytes.len(), 2 * (4 + 2));
+        assert_eq!(
+            read_vectors(&bytes, VectorFormat::Bvecs).unwrap(),
+            (2, integral.to_vec())
+        );
+        let mut bytes = Vec::new();
+        assert!(matches!(
+            write_vectors(&mut bytes, &DATA, 3, VectorFormat::Bvecs),
+            Err(FormatError::ValueOutOfRange { value, .. }) if value == 0.5
+        ));
+    }
+
+    #[test]
+    fn test_npy_header_parsing() {
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, &DATA, 2, NpyDtype::F16).unwrap();
+        // Data starts on a 64-byte boundary, as NumPy writes it
+        assert_eq!((bytes.len() - DATA.len() * 2) % 64, 0);
+        assert_eq!(
+            read_vectors(&bytes, VectorFormat::Npy).unwrap(),
+            (2, DATA.to_vec())
+        );
+
+        let mut bytes = Vec::new();
+        write_npy(&mut bytes, &[65504.0, -65504.0], 2, NpyDtype::F16).unwrap();
+        assert_eq!(
+            read_vectors(&bytes, VectorFormat::Npy).unwrap(),
+            (2, vec![65504.0, -65504.0])
+        );
+        assert_eq!(
+            write_npy(&mut Vec::new(), &[1.0, -70000.0], 2, NpyDtype::F16),
+            Err(FormatError::ValueOutOfRange {
+                value: -70000.0,
+                format: "float16"
+            })
+        );
+
+        let header =
+            parse_npy_header("{'descr': '<f4', 'fortran_order': False, 'shape': (7,), }").unwrap();
+        assert_eq!(header.shape, vec![7]);
+        assert!(matches!(
+            parse_npy_header("{'descr': '>f4', 'fortran_order': False, 'shape': (2, 3), }"),
+            Err(FormatError::BigEndian(_))
+        ));
+        assert!(matches!(
+            parse_npy_header("{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }"),
+            Err(FormatError::UnsupportedDtype(_))
+        ));
+        assert!(matches!(
+            parse_npy_header("{'descr': '<f4', 'fortran_order': True, 'shape': (2, 3), }"),
+            Err(FormatError::FortranOrder)
+        ));
+        assert_eq!(
+            matrix_shape(&[2, 3, 4]),
+            Err(FormatError::InvalidShape(vec![2, 3, 4]))
+        );
+    }
+
+    #[test]
+    fn test_record_errors() {
+        let mut bytes = Vec::new();
+        write_vectors(&mut bytes, &DATA, 3, VectorFormat::Fvecs).unwrap();
+        let mut mixed = bytes[..16].to_vec();
+        write_vectors(&mut mixed, &DATA[..2], 2, VectorFormat::Fvecs).unwrap();
+        assert_eq!(
+            read_vectors(&mixed, VectorFormat::Fvecs),
+            Err(FormatError::DimensionMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        assert!(matches!(
+            read_vectors(&bytes[..bytes.len() - 1], VectorFormat::Fvecs),
+            Err(FormatError::Truncated { .. })
+        ));
+        assert_eq!(
+            read_vectors(b"not numpy", VectorFormat::Npy),
+            Err(FormatError::BadMagic)
+        );
+
+        let shape = [1, 3];
+        let tensors = [("a", &shape[..], &DATA[..3]), ("b", &shape[..], &DATA[3..])];
+        let bytes = serialize_f32(&tensors, &HashMap::new());
+        assert_eq!(
+            read_vectors(&bytes, VectorFormat::Safetensors),
+            Err(FormatError::MissingTensor(SAFETENSORS_TENSOR.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_one_write_per_vector() {
+        struct CountingWriter(usize);
+        impl Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0 += 1;
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = CountingWriter(0);
+        write_vectors(&mut writer, &DATA, 3, VectorFormat::Fvecs).unwrap();
+        assert_eq!(writer.0, 2);
+        // Magic, version, header length and header, then one write per row
+        let mut writer = CountingWriter(0);
+        write_npy(&mut writer, &DATA, 2, NpyDtype::F16).unwrap();
+        assert_eq!(writer.0, 4 + 3);
+    }
+
+    #[test]
+    fn test_corrupt_lengths_and_ragged_data() {
+        // A huge declared dimension is reported as truncated rather than allocated
+        let mut record = i32::MAX.to_le_bytes().to_vec();
+        record.extend_from_slice(&[0; 8]);
+        for format in [VectorFormat::Fvecs, VectorFormat::Bvecs] {
+            assert_eq!(
+                read_vectors(&record, format),
+                Err(FormatError::Truncated {
+                    context: "vector data"
+                })
+            );
+        }
+        let mut npy = NPY_MAGIC.to_vec();
+        npy.extend_from_slice(&[2, 0]);
+        npy.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            read_vectors(&npy, VectorFormat::Npy),
+            Err(FormatError::Truncated {
+                context: "npy header"
+            })
+        );
+
+        let mut bytes = Vec::new();
+        let ragged = Err(FormatError::RaggedData {
+            len: 6,
+            dimension: 4,
+        });
+        assert_eq!(
+            write_vectors(&mut bytes, &DATA, 4, VectorFormat::Fvecs),
+            ragged
+        );
+        assert_eq!(write_npy(&mut bytes, &DATA, 4, NpyDtype::F32), ragged);
+        assert!(matches!(
+            write_npy(&mut bytes, &DATA, 0, NpyDtype::F16),
+            Err(FormatError::RaggedData { dimension: 0, .. })
+        ));
+        assert!(bytes.is_empty());
+    }
+}
@@ -2,8 +2,15 @@
 //!
 //! Provides efficient algorithms for finding similar vectors using various distance metrics.
 
+use crate::dedup::{self, CatalogMetadata, DuplicateConfig, DuplicateGroup};
+use crate::embeddings::{
+    EmbeddingModelInfo, EmbeddingTransform, ModelMismatchError, Standardizer, Whitener,
+};
+use crate::error::Error;
+use crate::vector_io::{FormatError, VectorFormat, VectorReader};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::io::{Read, Write};
 use wasm_bindgen::prelude::*;
 
 /// Distance metrics for vector similarity
@@ -50,11 +57,13 @@ impl SearchResult {
 
 /// High-performance vector index for similarity search
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct VectorIndex {
     vectors: Vec<Vec<f32>>,
     dimension: usize,
     metric: DistanceMetric,
-    normalized: bool,
+    transform: Option<EmbeddingTransform>,
+    model: Option<EmbeddingModelInfo>,
 }
 
 #[wasm_bindgen]
@@ -66,39 +75,28 @@ impl VectorIndex {
             vectors: Vec::new(),
             dimension,
             metric,
-            normalized: false,
+            transform: None,
+            model: None,
         }
     }
 
     /// Add a vector to the index
-    pub fn add_vector(&mut self, vector: &[f32]) -> Result<usize, JsValue> {
-        if vector.len() != self.dimension {
-            return Err(JsValue::from_str(&format!(
-                "Vector dimension mismatch: expected {}, got {}",
-                self.dimension,
-                vector.len()
-            )));
-        }
-
-        let mut vec = vector.to_vec();
-
-        // Normalize if using cosine similarity
-        if self.metric == DistanceMetric::Cosine {
-            normalize_vector(&mut vec);
-        }
+    pub fn add_vector(&mut self, vector: &[f32]) -> Result<usize, Error> {
+        Error::check_dimension("add_vector", self.dimension, vector.len())?;
 
+        let vec = self.prepare(vector);
         self.vectors.push(vec);
         Ok(self.vectors.len() - 1)
     }
 
     /// Add multiple vectors in batch
-    pub fn add_vectors_batch(&mut self, vectors: &[f32], count: usize) -> Result<(), JsValue> {
+    pub fn add_vectors_batch(&mut self, vectors: &[f32], count: usize) -> Result<(), Error> {
         if vectors.len() != count * self.dimension {
-            return Err(JsValue::from_str(&format!(
-                "Invalid batch size: expected {} floats, got {}",
-                count * self.dimension,
-                vectors.len()
-            )));
+            return Err(Error::InvalidBatch {
+                operation: "add_vectors_batch",
+                len: vectors.len(),
+                dimension: self.dimension,
+            });
         }
 
         for i in 0..count {
@@ -112,46 +110,78 @@ impl VectorIndex {
     }
 
     /// Search for k nearest neighbors
-    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>, JsValue> {
-        if query.len() != self.dimension {
-            return Err(JsValue::from_str(&format!(
-                "Query dimension mismatch: expected {}, got {}",
-                self.dimension,
-                query.len()
-            )));
-        }
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchResult>, Error> {
+        Error::check_dimension("search", self.dimension, query.len())?;
 
         if self.vectors.is_empty() {
             return Ok(Vec::new());
         }
 
-        let mut query_vec = query.to_vec();
-        if self.metric == DistanceMetric::Cosine {
-            normalize_vector(&mut query_vec);
-        }
-
-        let mut results: Vec<SearchResult> = self.vectors
-            .iter()
-            .enumerate()
-            .map(|(id, vec)| {
-                let score = self.compute_similarity(&query_vec, vec);
-                SearchResult { id, score }
-            })
-            .collect();
+        let query_vec = self.prepare(query);
+        Ok(self.rank(&query_vec, k, &[]))
+    }
 
-        // Sort by score (descending for similarity, ascending for distance)
-        let reverse = matches!(self.metric, DistanceMetric::Cosine | DistanceMetric::DotProduct);
-        results.sort_by(|a, b| {
-            if reverse {
-                b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal)
-            } else {
-                a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal)
+    /// Search into caller-owned buffers, returning how many results were written
+    ///
+    /// Up to `min(ids.len(), scores.len())` results are written best first, so a
+    /// hot loop can reuse the same buffers without allocating per result. The
+    /// query itself is still transformed and normalized into one temporary vector.
+    pub fn search_into(
+        &self,
+        query: &[f32],
+        ids: &mut [u32],
+        scores: &mut [f32],
+    ) -> Result<usize, Error> {
+        Error::check_dimension("search_into", self.dimension, query.len())?;
+
+        let capacity = ids.len().min(scores.len());
+        let query_vec = self.prepare(query);
+        let mut written = 0;
+        for (id, vec) in self.vectors.iter().enumerate() {
+            let score = self.compute_similarity(&query_vec, vec);
+            // Insertion point after any equal scores keeps ties in ID order, like `search`
+            let position = scores[..written]
+                .iter()
+                .position(|&other| self.ranks_before(score, other))
+                .unwrap_or(written);
+            if position == capacity {
+                continue;
             }
-        });
+            let end = (written + 1).min(capacity);
+            ids.copy_within(position..end - 1, position + 1);
+            scores.copy_within(position..end - 1, position + 1);
+            ids[position] = id as u32;
+            scores[position] = score;
+            written = end;
+        }
+        Ok(written)
+    }
 
-        // Return top k results
-        results.truncate(k.min(results.len()));
-        Ok(results)
+    /// Replace the vector stored under `id`
+    pub fn set_vector(&mut self, id: usize, vector: &[f32]) -> Result<(), Error> {
+        Error::check_dimension("set_vector", self.dimension, vector.len())?;
+        let len = self.vectors.len();
+        let prepared = self.prepare(vector);
+        let slot = self.vectors.get_mut(id).ok_or(Error::IndexOutOfBounds {
+            operation: "set_vector",
+            index: id,
+            len,
+        })?;
+        *slot = prepared;
+        Ok(())
+    }
+
+    /// Remove the vector stored under `id`; vectors after it move down one ID
+    pub fn remove_vector(&mut self, id: usize) -> Result<(), Error> {
+        if id >= self.vectors.len() {
+            return Err(Error::IndexOutOfBounds {
+                operation: "remove_vector",
+                index: id,
+                len: self.vectors.len(),
+            });
+        }
+        self.vectors.remove(id);
+        Ok(())
     }
 
     /// Get the number of vectors in the index
@@ -169,13 +199,332 @@ impl VectorIndex {
         self.vectors.clear();
     }
 
-    /// Get a vector by ID
+    /// Get a vector by ID, as stored after any transform and normalization
     pub fn get_vector(&self, id: usize) -> Option<Vec<f32>> {
         self.vectors.get(id).cloned()
     }
+
+    /// Standardize vectors before indexing and querying
+    pub fn set_standardizer(&mut self, standardizer: &Standardizer) -> Result<(), Error> {
+        self.set_transform(EmbeddingTransform::Standardize(standardizer.clone()))
+    }
+
+    /// Whiten vectors before indexing and querying
+    pub fn set_whitener(&mut self, whitener: &Whitener) -> Result<(), Error> {
+        self.set_transform(EmbeddingTransform::Whiten(whitener.clone()))
+    }
+
+    /// Remove the configured transform (the index must be empty)
+    pub fn clear_transform(&mut self) -> Result<(), Error> {
+        self.ensure_empty()?;
+        self.transform = None;
+        Ok(())
+    }
+
+    pub fn has_transform(&self) -> bool {
+        self.transform.is_some()
+    }
+
+    /// Record the embedding model this index is built with
+    ///
+    /// The model must be set while the index is empty, since the provenance of
+    /// existing vectors is unknown; after that it can only be replaced by a
+    /// compatible one.
+    pub fn set_model(&mut self, model: &EmbeddingModelInfo) -> Result<(), Error> {
+        Error::check_dimension("set_model", self.dimension, model.dimension())?;
+        if !self.vectors.is_empty() {
+            if self.model.is_none() {
+                return Err(Error::InvalidState {
+                    operation: "set_model",
+                    message: "cannot tag an index that already holds untagged vectors".to_string(),
+                });
+            }
+            self.check_model(model)?;
+        }
+        self.model = Some(model.clone());
+        Ok(())
+    }
+
+    /// The embedding model this index is built with, if recorded
+    pub fn model(&self) -> Option<EmbeddingModelInfo> {
+        self.model.clone()
+    }
+
+    /// Add a vector produced by `model`, rejecting it if the index uses another model
+    ///
+    /// The first checked add into an empty, untagged index records `model`.
+    pub fn add_vector_checked(
+        &mut self,
+        vector: &[f32],
+        model: &EmbeddingModelInfo,
+    ) -> Result<usize, Error> {
+        self.adopt_model(model)?;
+        self.add_vector(vector)
+    }
+
+    /// Add a batch of vectors produced by `model`
+    pub fn add_vectors_batch_checked(
+        &mut self,
+        vectors: &[f32],
+        count: usize,
+        model: &EmbeddingModelInfo,
+    ) -> Result<(), Error> {
+        self.adopt_model(model)?;
+        self.add_vectors_batch(vectors, count)
+    }
+
+    /// Search with a query produced by `model`
+    pub fn search_checked(
+        &self,
+        query: &[f32],
+        k: usize,
+        model: &EmbeddingModelInfo,
+    ) -> Result<Vec<SearchResult>, Error> {
+        self.check_model(model)?;
+        self.search(query, k)
+    }
+
+    /// Add every vector in an encoded file, returning how many were added
+    ///
+    /// The file holds stored-space vectors, as written by `export_vectors`.
+    pub fn import_vectors(&mut self, bytes: &[u8], format: VectorFormat) -> Result<usize, Error> {
+        Ok(self.import_from(bytes, format)?)
+    }
+
+    /// Encode the stored vectors (after any transform and normalization)
+    pub fn export_vectors(&self, format: VectorFormat) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.export_to(&mut bytes, format)?;
+        Ok(bytes)
+    }
+
+    /// Group items whose vector similarity is at least `threshold`
+    ///
+    /// Euclidean and Manhattan distances are mapped to `1 / (1 + d)` so the
+    /// threshold is always a similarity. Every linked pair joins one group.
+    pub fn find_near_duplicates(&self, threshold: f32) -> Vec<DuplicateGroup> {
+        let mut config = DuplicateConfig::new(threshold);
+        config.set_weights(1.0, 0.0, 0.0);
+        dedup::find_groups(self, None, &config)
+    }
+
+    /// Group items by a blend of vector similarity and title/year agreement
+    pub fn find_near_duplicates_with_metadata(
+        &self,
+        metadata: &CatalogMetadata,
+        config: &DuplicateConfig,
+    ) -> Vec<DuplicateGroup> {
+        dedup::find_groups(self, Some(metadata), config)
+    }
 }
 
 impl VectorIndex {
+    /// Nearest neighbors of every query in a flat buffer, in query order
+    ///
+    /// With the `parallel` feature, large batches are spread across threads.
+    pub fn search_batch(&self, queries: &[f32], k: usize) -> Result<Vec<Vec<SearchResult>>, Error> {
+        Error::check_batch("search_batch", queries, self.dimension)?;
+        let search = |query: &[f32]| self.rank(&self.prepare(query), k, &[]);
+
+        #[cfg(feature = "parallel")]
+        if crate::parallel::should_split(queries.len() / self.dimension * self.vectors.len()) {
+            use rayon::prelude::*;
+            return Ok(queries.par_chunks(self.dimension).map(search).collect());
+        }
+        Ok(queries.chunks_exact(self.dimension).map(search).collect())
+    }
+
+    /// Apply `transform` to every vector added or queried from now on
+    ///
+    /// The index must be empty so stored vectors and queries always live in the
+    /// same space.
+    pub fn set_transform(&mut self, transform: EmbeddingTransform) -> Result<(), Error> {
+        self.ensure_empty()?;
+        Error::check_dimension("set_transform", self.dimension, transform.input_dimension())?;
+        self.transform = Some(transform);
+        Ok(())
+    }
+
+    /// The transform applied at index and query time, if any
+    pub fn transform(&self) -> Option<&EmbeddingTransform> {
+        self.transform.as_ref()
+    }
+
+    /// Stream vectors from `reader` into the index
+    ///
+    /// Vectors are read in the stored space, as `export_to` writes them: with a
+    /// transform set they must already be transformed, so they are checked
+    /// against the transform's output dimension and not transformed again.
+    /// Cosine indexes still normalize them, which leaves exported vectors as
+    /// they were. Either every vector is added or, on a decoding or dimension
+    /// error, none are.
+    pub fn import_from<R: Read>(
+        &mut self,
+        reader: R,
+        format: VectorFormat,
+    ) -> Result<usize, FormatError> {
+        let start = self.vectors.len();
+        let result = self.import_all(reader, format);
+        if result.is_err() {
+            self.vectors.truncate(start);
+        }
+        result
+    }
+
+    fn import_all<R: Read>(
+        &mut self,
+        reader: R,
+        format: VectorFormat,
+    ) -> Result<usize, FormatError> {
+        let dimension = self.stored_dimension();
+        let mut reader = VectorReader::new(reader, format)?;
+        let mut added = 0;
+        while let Some(vector) = reader.next_vector()? {
+            if vector.len() != dimension {
+                return Err(FormatError::DimensionMismatch {
+                    expected: dimension,
+                    actual: vector.len(),
+                });
+            }
+            let mut vec = vector.to_vec();
+            if self.metric == DistanceMetric::Cosine {
+                normalize_vector(&mut vec);
+            }
+            self.push_stored(vec);
+            added += 1;
+        }
+        Ok(added)
+    }
+
+    /// Write the stored vectors to `writer`
+    pub fn export_to<W: Write>(
+        &self,
+        writer: &mut W,
+        format: VectorFormat,
+    ) -> Result<(), FormatError> {
+        let data: Vec<f32> = self.vectors.iter().flatten().copied().collect();
+        crate::vector_io::write_vectors(writer, &data, self.stored_dimension(), format)
+    }
+
+    /// Dimension of the vectors as stored, after any transform
+    pub fn stored_dimension(&self) -> usize {
+        self.transform
+            .as_ref()
+            .map_or(self.dimension, EmbeddingTransform::output_dimension)
+    }
+
+    /// Check that vectors from `model` are comparable with this index
+    ///
+    /// Indexes without a recorded model accept any model.
+    pub fn check_model(&self, model: &EmbeddingModelInfo) -> Result<(), ModelMismatchError> {
+        match &self.model {
+            Some(expected) => model.check_compatible(expected),
+            None => Ok(()),
+        }
+    }
+
+    fn adopt_model(&mut self, model: &EmbeddingModelInfo) -> Result<(), Error> {
+        if self.model.is_none() && self.vectors.is_empty() {
+            return self.set_model(model);
+        }
+        Ok(self.check_model(model)?)
+    }
+
+    /// Append a vector that is already in the stored space (transformed and normalized)
+    pub(crate) fn push_stored(&mut self, vector: Vec<f32>) {
+        self.vectors.push(vector);
+    }
+
+    /// Drop every vector with an ID of `len` or above
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.vectors.truncate(len);
+    }
+
+    fn ensure_empty(&self) -> Result<(), Error> {
+        if !self.vectors.is_empty() {
+            return Err(Error::InvalidState {
+                operation: "change the transform",
+                message: "the index is not empty".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Distance metric used for scoring
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Stored vector by ID, in the space queries are compared in
+    pub(crate) fn stored(&self, id: usize) -> Option<&[f32]> {
+        self.vectors.get(id).map(Vec::as_slice)
+    }
+
+    /// Score every stored vector against an already prepared query and keep the
+    /// best `k`, skipping the IDs in `exclude`
+    pub(crate) fn rank(&self, query_vec: &[f32], k: usize, exclude: &[usize]) -> Vec<SearchResult> {
+        let score = |(id, vec): (usize, &Vec<f32>)| {
+            (!exclude.contains(&id)).then(|| SearchResult {
+                id,
+                score: self.compute_similarity(query_vec, vec),
+            })
+        };
+
+        #[cfg(feature = "parallel")]
+        let mut results: Vec<SearchResult> = if crate::parallel::should_split(self.vectors.len()) {
+            use rayon::prelude::*;
+            self.vectors
+                .par_iter()
+                .enumerate()
+                .filter_map(score)
+                .collect()
+        } else {
+            self.vectors.iter().enumerate().filter_map(score).collect()
+        };
+        #[cfg(not(feature = "parallel"))]
+        let mut results: Vec<SearchResult> =
+            self.vectors.iter().enumerate().filter_map(score).collect();
+
+        // Sort by score (descending for similarity, ascending for distance)
+        let reverse = matches!(
+            self.metric,
+            DistanceMetric::Cosine | DistanceMetric::DotProduct
+        );
+        results.sort_by(|a, b| {
+            if reverse {
+                b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal)
+            } else {
+                a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal)
+            }
+        });
+
+        // Return top k results
+        results.truncate(k.min(results.len()));
+        results
+    }
+
+    /// Map an input vector into the stored space: transform, then normalize for cosine
+    pub(crate) fn prepare(&self, vector: &[f32]) -> Vec<f32> {
+        let mut vec = match &self.transform {
+            Some(transform) => transform.apply(vector),
+            None => vector.to_vec(),
+        };
+
+        // Normalize if using cosine similarity
+        if self.metric == DistanceMetric::Cosine {
+            normalize_vector(&mut vec);
+        }
+        vec
+    }
+
+    /// Whether `score` ranks strictly ahead of `other` under this metric
+    fn ranks_before(&self, score: f32, other: f32) -> bool {
+        match self.metric {
+            DistanceMetric::Cosine | DistanceMetric::DotProduct => score > other,
+            DistanceMetric::Euclidean | DistanceMetric::Manhattan => score < other,
+        }
+    }
+
     /// Compute similarity/distance between two vectors based on the metric
     fn compute_similarity(&self, a: &[f32], b: &[f32]) -> f32 {
         match self.metric {
@@ -280,4 +629,202 @@ mod tests {
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].id, 0);
     }
+
+    #[test]
+    fn test_search_into_matches_search() {
+        let mut index = VectorIndex::new(2, DistanceMetric::Euclidean);
+        let query = [0.0, 0.0];
+        let (mut ids, mut scores) = ([7u32; 3], [7.0f32; 3]);
+        let written = index.search_into(&query, &mut ids, &mut scores).unwrap();
+        assert_eq!(written, 0);
+
+        // Vectors 1 and 3 tie, and must come back in ID order as with `search`
+        for vector in [[3.0, 0.0], [1.0, 0.0], [0.0, 2.0], [0.0, 1.0], [-0.5, 0.0]] {
+            index.add_vector(&vector).unwrap();
+        }
+        let expected = index.search(&query, 3).unwrap();
+        let written = index.search_into(&query, &mut ids, &mut scores).unwrap();
+        assert_eq!(written, 3);
+        assert_eq!(ids, [4, 1, 3]);
+        for (result, (&id, &score)) in expected.iter().zip(ids.iter().zip(&scores)) {
+            assert_eq!((result.id as u32, result.score), (id, score));
+        }
+
+        // The shorter buffer sets k, and the rest of the longer one is untouched
+        let (mut ids, mut scores) = ([7u32; 4], [7.0f32; 1]);
+        let written = index.search_into(&query, &mut ids, &mut scores).unwrap();
+        assert_eq!(written, 1);
+        assert_eq!(ids, [4, 7, 7, 7]);
+        assert_eq!(scores, [0.5]);
+
+        let Err(err) = index.search_into(&[0.0], &mut ids, &mut scores) else {
+            panic!("searched with a query of the wrong dimension");
+        };
+        assert_eq!(err.code(), "DIMENSION_MISMATCH");
+    }
+
+    #[test]
+    fn test_errors_are_structured() {
+        let mut index = VectorIndex::new(3, DistanceMetric::Cosine);
+        index.add_vector(&[1.0, 0.0, 0.0]).unwrap();
+
+        let err = index.search(&[1.0, 0.0], 2).unwrap_err();
+        assert_eq!(err.code(), "DIMENSION_MISMATCH");
+        assert_eq!(err.details()["expected"], 3);
+        let err = index.add_vectors_batch(&[1.0; 4], 2).unwrap_err();
+        assert_eq!(err.code(), "INVALID_BATCH");
+        assert_eq!(err.details()["len"], 4);
+    }
+
+    #[test]
+    fn test_search_batch_matches_single_search() {
+        // Large enough to take the threaded path under the `parallel` feature
+        let (dim, count) = (8, crate::parallel::PARALLEL_MIN_VECTORS + 100);
+        let mut rng = crate::utils::SeededRng::new(7);
+        let data: Vec<f32> = (0..dim * count).map(|_| rng.normal() as f32).collect();
+        let mut index = VectorIndex::new(dim, DistanceMetric::Euclidean);
+        index.add_vectors_batch(&data, count).unwrap();
+
+        let queries = &data[..dim * 3];
+        let batch = index.search_batch(queries, 5).unwrap();
+        assert_eq!(batch.len(), 3);
+        for (query, results) in queries.chunks(dim).zip(&batch) {
+            let single = index.search(query, 5).unwrap();
+            let ids: Vec<usize> = results.iter().map(|r| r.id).collect();
+            assert_eq!(ids, single.iter().map(|r| r.id).collect::<Vec<_>>());
+        }
+        assert_eq!(batch[1][0].id, 1);
+        assert!(index.search_batch(&data[..dim + 1], 5).is_err());
+    }
+
+    #[test]
+    fn test_transform_applied_to_index_and_query() {
+        let embeddings = [1.0, 10.0, 3.0, 10.5, 5.0, 9.5, 7.0, 10.0];
+        let standardizer = Standardizer::fit(&embeddings, 2).unwrap();
+
+        let mut index = VectorIndex::new(2, DistanceMetric::Euclidean);
+        index.set_standardizer(&standardizer).unwrap();
+        index.add_vectors_batch(&embeddings, 4).unwrap();
+
+        // The stored copy is standardized, and the raw query lands on it exactly
+        let stored = index.get_vector(1).unwrap();
+        assert_eq!(stored, standardizer.transform(&embeddings[2..4]).unwrap());
+        let results = index.search(&embeddings[2..4], 1).unwrap();
+        assert_eq!(results[0].id, 1);
+        assert!(results[0].score.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_model_compatibility() {
+        let minilm = EmbeddingModelInfo::lookup("minilm").unwrap();
+        let mut index = VectorIndex::new(384, DistanceMetric::Cosine);
+        index.add_vector_checked(&vec![0.5; 384], &minilm).unwrap();
+        assert_eq!(index.model(), Some(minilm.clone()));
+        assert!(index.check_model(&minilm).is_ok());
+
+        let retrained = EmbeddingModelInfo::new(
+            "minilm",
+            "2",
+            384,
+            true,
+            crate::embeddings::PoolingStrategy::Mean,
+        );
+        let err = index.check_model(&retrained).unwrap_err();
+        assert_eq!(err.field(), "version");
+        assert_eq!(err.expected(), minilm);
+        assert!(err.to_string().contains("minilm@2"));
+    }
+
+    #[test]
+    fn test_set_model_requires_empty_index() {
+        let minilm = EmbeddingModelInfo::lookup("minilm").unwrap();
+        let mut index = VectorIndex::new(384, DistanceMetric::Cosine);
+        index.add_vector(&vec![0.5; 384]).unwrap();
+        let Err(err) = index.set_model(&minilm) else {
+            panic!("tagged an index holding untagged vectors");
+        };
+        assert_eq!(err.code(), "INVALID_STATE");
+        assert_eq!(index.model(), None);
+
+        let mut tagged = VectorIndex::new(384, DistanceMetric::Cosine);
+        tagged.set_model(&minilm).unwrap();
+        tagged.add_vector(&vec![0.5; 384]).unwrap();
+        assert!(tagged.set_model(&minilm).is_ok());
+    }
+
+    #[test]
+    fn test_import_export() {
+        let mut source = VectorIndex::new(2, DistanceMetric::Euclidean);
+        source.add_vectors_batch(&[1.0, 2.0, 3.0, 4.0], 2).unwrap();
+        let npy = source.export_vectors(VectorFormat::Npy).unwrap();
+
+        let mut index = VectorIndex::new(2, DistanceMetric::Euclidean);
+        assert_eq!(index.import_vectors(&npy, VectorFormat::Npy).unwrap(), 2);
+        assert_eq!(index.get_vector(1), Some(vec![3.0, 4.0]));
+
+        // A wrong dimension leaves the index untouched
+        let mut wide = VectorIndex::new(3, DistanceMetric::Euclidean);
+        wide.add_vector(&[0.0, 0.0, 0.0]).unwrap();
+        assert_eq!(
+            wide.import_from(npy.as_slice(), VectorFormat::Npy),
+            Err(FormatError::DimensionMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(wide.size(), 1);
+    }
+
+    #[test]
+    fn test_import_export_round_trip_with_transform() {
+        let embeddings = [
+            1.0, 10.0, 0.5, 3.0, 10.5, 1.5, 5.0, 9.5, 0.0, 7.0, 10.0, 2.5, 2.0, 11.0, 1.0,
+        ];
+
+        // Standardized vectors come back exactly as stored, not standardized twice
+        let standardizer = Standardizer::fit(&embeddings, 3).unwrap();
+        let mut source = VectorIndex::new(3, DistanceMetric::Euclidean);
+        source.set_standardizer(&standardizer).unwrap();
+        source.add_vectors_batch(&embeddings, 5).unwrap();
+        let npy = source.export_vectors(VectorFormat::Npy).unwrap();
+
+        let mut restored = VectorIndex::new(3, DistanceMetric::Euclidean);
+        restored.set_standardizer(&standardizer).unwrap();
+        assert_eq!(restored.import_vectors(&npy, VectorFormat::Npy).unwrap(), 5);
+        for id in 0..5 {
+            assert_eq!(restored.get_vector(id), source.get_vector(id));
+        }
+
+        // PCA whitening exports at its output dimension and imports back at it
+        let whitener = Whitener::fit(
+            &embeddings,
+            3,
+            crate::embeddings::WhiteningMode::Pca,
+            Some(2),
+        )
+        .unwrap();
+        let mut source = VectorIndex::new(3, DistanceMetric::Cosine);
+        source.set_whitener(&whitener).unwrap();
+        source.add_vectors_batch(&embeddings, 5).unwrap();
+        assert_eq!(source.stored_dimension(), 2);
+        let fvecs = source.export_vectors(VectorFormat::Fvecs).unwrap();
+
+        let mut restored = VectorIndex::new(3, DistanceMetric::Cosine);
+        restored.set_whitener(&whitener).unwrap();
+        assert_eq!(
+            restored
+                .import_vectors(&fvecs, VectorFormat::Fvecs)
+                .unwrap(),
+            5
+        );
+        for id in 0..5 {
+            let (a, b) = (
+                restored.get_vector(id).unwrap(),
+                source.get_vector(id).unwrap(),
+            );
+            assert!(a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-6));
+        }
+        let results = restored.search(&embeddings[3..6], 1).unwrap();
+        assert_eq!(results[0].id, 1);
+    }
 }
@@ -2,10 +2,21 @@
 //!
 //! Provides ergonomic JavaScript API for the Rust functionality.
 
-use wasm_bindgen::prelude::*;
+use std::cell::{Cell, RefCell, RefMut};
+use std::rc::Rc;
+
+use crate::build::{self, BuildOptions, IndexBuild};
+use crate::clock::{Clock, SystemClock};
+use crate::dedup::{CatalogMetadata, DuplicateConfig, DuplicateGroup};
+use crate::embeddings::EmbeddingModelInfo;
+use crate::error::Error;
+use crate::metrics;
+use crate::pipeline::{Pipeline, PipelineOutput};
+use crate::utils::VectorBuffer;
+use crate::vector_io::VectorFormat;
+use crate::vector_search::{DistanceMetric, SearchResult, VectorIndex};
 use serde::{Deserialize, Serialize};
-use crate::vector_search::{VectorIndex, DistanceMetric, SearchResult};
-use crate::embeddings::{EmbeddingGenerator, EmbeddingConfig};
+use wasm_bindgen::prelude::*;
 
 /// JavaScript-friendly search results
 #[wasm_bindgen]
@@ -18,9 +29,18 @@ pub struct SearchResults {
 #[wasm_bindgen]
 impl SearchResults {
     /// Get results as JSON string
-    pub fn to_json(&self) -> Result<String, JsValue> {
-        serde_json::to_string(&self.results)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(&self.results).map_err(|e| Error::serialize("search results", e))
+    }
+
+    /// Result IDs in rank order, as a `Uint32Array`
+    pub fn ids(&self) -> Vec<u32> {
+        self.results.iter().map(|r| r.id as u32).collect()
+    }
+
+    /// Result scores in rank order, as a `Float32Array`
+    pub fn scores(&self) -> Vec<f32> {
+        self.results.iter().map(|r| r.score).collect()
     }
 
     /// Get query execution time in milliseconds
@@ -40,72 +60,484 @@ impl SearchResults {
 }
 
 /// High-level API for vector search with performance tracking
+///
+/// The index is shared with any running `build_index` so the build can keep
+/// adding vectors after the call that started it has returned. Each engine
+/// reports its own `index_size` gauge under its metrics name.
 #[wasm_bindgen]
 pub struct VectorSearchEngine {
-    index: VectorIndex,
+    index: Rc<RefCell<VectorIndex>>,
+    building: Rc<Cell<bool>>,
+    clock: Box<dyn Clock>,
+    metrics_name: String,
+}
+
+thread_local! {
+    /// Number of engines created so far, for default metrics names
+    static ENGINES_CREATED: Cell<u64> = const { Cell::new(0) };
 }
 
 #[wasm_bindgen]
 impl VectorSearchEngine {
     /// Create a new search engine
     #[wasm_bindgen(constructor)]
-    pub fn new(dimension: usize, metric_str: &str) -> Result<VectorSearchEngine, JsValue> {
+    pub fn new(dimension: usize, metric_str: &str) -> Result<VectorSearchEngine, Error> {
         let metric = match metric_str.to_lowercase().as_str() {
             "cosine" => DistanceMetric::Cosine,
             "euclidean" => DistanceMetric::Euclidean,
             "manhattan" => DistanceMetric::Manhattan,
             "dotproduct" => DistanceMetric::DotProduct,
-            _ => return Err(JsValue::from_str("Invalid metric. Use: cosine, euclidean, manhattan, or dotproduct")),
+            _ => {
+                return Err(Error::InvalidArgument {
+                    operation: "VectorSearchEngine::new",
+                    message: format!(
+                        "unknown metric {}; use cosine, euclidean, manhattan or dotproduct",
+                        metric_str
+                    ),
+                })
+            }
         };
 
         Ok(VectorSearchEngine {
-            index: VectorIndex::new(dimension, metric),
+            index: Rc::new(RefCell::new(VectorIndex::new(dimension, metric))),
+            building: Rc::new(Cell::new(false)),
+            clock: Box::new(SystemClock),
+            metrics_name: ENGINES_CREATED.with(|created| {
+                created.set(created.get() + 1);
+                format!("engine-{}", created.get())
+            }),
         })
     }
 
+    /// Name of the `engine` label on this engine's gauges (`engine-1`, ... by default)
+    #[wasm_bindgen(getter)]
+    pub fn metrics_name(&self) -> String {
+        self.metrics_name.clone()
+    }
+
+    /// Report this engine's gauges under `name` from now on
+    ///
+    /// Rejected with `INVALID_STATE` while `build_index` runs, since the build
+    /// keeps reporting under the name it started with.
+    pub fn set_metrics_name(&mut self, name: &str) -> Result<(), Error> {
+        let size = self.index_mut("set_metrics_name")?.size();
+        metrics::with_registry(|m| {
+            m.remove_engine(&self.metrics_name);
+            m.set_engine_gauge(name, "index_size", size as f64);
+        });
+        self.metrics_name = name.to_string();
+        Ok(())
+    }
+
     /// Add a vector from JavaScript array
-    pub fn add(&mut self, vector: Vec<f32>) -> Result<usize, JsValue> {
-        self.index.add_vector(&vector)
+    pub fn add(&mut self, vector: Vec<f32>) -> Result<usize, Error> {
+        self.timed_add("add", |index| index.add_vector(&vector))
     }
 
     /// Add multiple vectors in batch
-    pub fn add_batch(&mut self, vectors: Vec<f32>, count: usize) -> Result<(), JsValue> {
-        self.index.add_vectors_batch(&vectors, count)
+    pub fn add_batch(&mut self, vectors: Vec<f32>, count: usize) -> Result<(), Error> {
+        self.timed_add("add_batch", |index| {
+            index.add_vectors_batch(&vectors, count)
+        })
     }
 
     /// Search with performance tracking
-    pub fn search(&self, query: Vec<f32>, k: usize) -> Result<SearchResults, JsValue> {
-        let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window object"))?;
-        let performance = window.performance().ok_or_else(|| JsValue::from_str("No performance API"))?;
+    pub fn search(&self, query: Vec<f32>, k: usize) -> Result<SearchResults, Error> {
+        self.timed(|index| index.search(&query, k))
+    }
 
-        let start = performance.now();
-        let results = self.index.search(&query, k)?;
-        let query_time_ms = performance.now() - start;
+    /// Search for every query in a flat buffer, one `SearchResults` per query
+    ///
+    /// Each result's query time is the batch time averaged over its queries.
+    pub fn search_batch(&self, queries: Vec<f32>, k: usize) -> Result<Vec<SearchResults>, Error> {
+        self.timed_batch(&queries, k)
+    }
 
-        Ok(SearchResults {
-            results,
-            query_time_ms,
+    /// Add every vector in a `VectorBuffer` without copying it out of WASM memory
+    pub fn add_buffer(&mut self, buffer: &VectorBuffer) -> Result<(), Error> {
+        self.timed_add("add_buffer", |index| {
+            Error::check_dimension("add_buffer", index.dimension(), buffer.dimension())?;
+            index.add_vectors_batch(buffer.as_slice(), buffer.count())
+        })
+    }
+
+    /// Search for every vector in a `VectorBuffer`, one `SearchResults` per query
+    pub fn search_buffer(
+        &self,
+        buffer: &VectorBuffer,
+        k: usize,
+    ) -> Result<Vec<SearchResults>, Error> {
+        let dimension = self.index.borrow().dimension();
+        Error::check_dimension("search_buffer", dimension, buffer.dimension())?;
+        self.timed_batch(buffer.as_slice(), k)
+    }
+
+    /// Search into caller-owned `Uint32Array`/`Float32Array` buffers
+    ///
+    /// Returns how many results were written; `k` is the shorter buffer's length.
+    /// wasm-bindgen copies the query and both buffers into WASM memory and the
+    /// buffers back out, so this saves creating result objects, not copies; for
+    /// zero-copy queries use [`search_buffer`](Self::search_buffer).
+    pub fn search_into(
+        &self,
+        query: &[f32],
+        ids: &mut [u32],
+        scores: &mut [f32],
+    ) -> Result<usize, Error> {
+        self.index.borrow().search_into(query, ids, scores)
+    }
+
+    /// Record the embedding model the indexed vectors come from (before adding any)
+    pub fn set_model(&mut self, model: &EmbeddingModelInfo) -> Result<(), Error> {
+        self.index_mut("set_model")?.set_model(model)
+    }
+
+    /// Get the embedding model recorded for the index
+    pub fn model(&self) -> Option<EmbeddingModelInfo> {
+        self.index.borrow().model()
+    }
+
+    /// Add a vector, rejecting it with a `MODEL_MISMATCH` error if it comes from another model
+    pub fn add_checked(
+        &mut self,
+        vector: Vec<f32>,
+        model: &EmbeddingModelInfo,
+    ) -> Result<usize, Error> {
+        self.timed_add("add_checked", |index| {
+            index.add_vector_checked(&vector, model)
         })
     }
 
+    /// Add multiple vectors from `model` in batch
+    pub fn add_batch_checked(
+        &mut self,
+        vectors: Vec<f32>,
+        count: usize,
+        model: &EmbeddingModelInfo,
+    ) -> Result<(), Error> {
+        self.timed_add("add_batch_checked", |index| {
+            index.add_vectors_batch_checked(&vectors, count, model)
+        })
+    }
+
+    /// Search with a query from `model`, with performance tracking
+    pub fn search_checked(
+        &self,
+        query: Vec<f32>,
+        k: usize,
+        model: &EmbeddingModelInfo,
+    ) -> Result<SearchResults, Error> {
+        self.timed(|index| index.search_checked(&query, k, model))
+    }
+
+    /// Add all vectors from an `.npy`, `.fvecs`, `.bvecs` or safetensors buffer
+    ///
+    /// Vectors are taken as stored, after any transform, matching `export`.
+    pub fn import(&mut self, bytes: &[u8], format: VectorFormat) -> Result<usize, Error> {
+        self.timed_add("import", |index| index.import_vectors(bytes, format))
+    }
+
+    /// Export the indexed vectors in the given format
+    pub fn export(&self, format: VectorFormat) -> Result<Vec<u8>, Error> {
+        self.index.borrow().export_vectors(format)
+    }
+
+    /// Search with a combined query from a `QueryBuilder`, excluding its example IDs
+    pub fn query(&self, builder: &QueryBuilder, k: usize) -> Result<SearchResults, Error> {
+        self.timed(|index| builder.search_index(index, k))
+    }
+
     /// Get index statistics as JSON
-    pub fn stats(&self) -> Result<String, JsValue> {
+    pub fn stats(&self) -> Result<String, Error> {
+        let index = self.index.borrow();
         let stats = IndexStats {
-            size: self.index.size(),
-            dimension: self.index.dimension(),
+            size: index.size(),
+            dimension: index.dimension(),
+            model: index.model(),
         };
-        serde_json::to_string(&stats)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+        serde_json::to_string(&stats).map_err(|e| Error::serialize("index stats", e))
     }
 
     /// Clear all vectors
-    pub fn clear(&mut self) {
-        self.index.clear();
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.index_mut("clear")?.clear();
+        metrics::with_registry(|m| m.set_engine_gauge(&self.metrics_name, "index_size", 0.0));
+        Ok(())
+    }
+
+    /// Add a flat buffer of vectors without blocking the event loop
+    ///
+    /// Returns a `Promise` resolving to the number of vectors added. Vectors are
+    /// added `options.chunk_size` at a time, yielding through `setTimeout` once
+    /// each time slice is used up, so searches keep working during the build.
+    /// If the signal is aborted or a chunk fails, the promise rejects and every
+    /// vector the build added is removed. Other calls that modify the index are
+    /// rejected with `INVALID_STATE` until the build settles.
+    pub fn build_index(
+        &self,
+        vectors: Vec<f32>,
+        options: &BuildOptions,
+    ) -> Result<js_sys::Promise, Error> {
+        let build = IndexBuild::new(&*self.index_mut("build_index")?, vectors)?;
+        self.building.set(true);
+
+        let (index, building, options) = (
+            Rc::clone(&self.index),
+            Rc::clone(&self.building),
+            options.clone(),
+        );
+        let metrics_name = self.metrics_name.clone();
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            let result = build::run(&index, build, &options, &metrics_name).await;
+            building.set(false);
+            result
+                .map(|added| JsValue::from_f64(added as f64))
+                .map_err(JsValue::from)
+        }))
+    }
+
+    /// Whether a `build_index` call is still running
+    pub fn is_building(&self) -> bool {
+        self.building.get()
     }
 
     /// Get vector by ID
     pub fn get_vector(&self, id: usize) -> Option<Vec<f32>> {
-        self.index.get_vector(id)
+        self.index.borrow().get_vector(id)
+    }
+
+    /// Group indexed items whose vector similarity is at least `threshold`
+    pub fn find_near_duplicates(&self, threshold: f32) -> Vec<DuplicateGroup> {
+        self.index.borrow().find_near_duplicates(threshold)
+    }
+
+    /// Group indexed items by a blend of vector similarity and title/year agreement
+    pub fn find_near_duplicates_with_metadata(
+        &self,
+        metadata: &CatalogMetadata,
+        config: &DuplicateConfig,
+    ) -> Vec<DuplicateGroup> {
+        self.index
+            .borrow()
+            .find_near_duplicates_with_metadata(metadata, config)
+    }
+}
+
+impl VectorSearchEngine {
+    /// Replace the clock used to time searches (e.g. with a `FakeClock` in tests)
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Run a batch search and spread its time evenly over the queries
+    fn timed_batch(&self, queries: &[f32], k: usize) -> Result<Vec<SearchResults>, Error> {
+        let index = self.index.borrow();
+        let start = self.clock.now_ms();
+        let batch = index.search_batch(queries, k)?;
+        let query_time_ms = (self.clock.now_ms() - start) / batch.len().max(1) as f64;
+        metrics::with_registry(|m| {
+            for _ in &batch {
+                m.record_latency("search", query_time_ms);
+            }
+            m.increment("vectors_scanned", index.size() * batch.len());
+        });
+        Ok(batch
+            .into_iter()
+            .map(|results| SearchResults {
+                results,
+                query_time_ms,
+            })
+            .collect())
+    }
+
+    /// Add vectors through `add`, recording its latency under "add" and the new index size
+    fn timed_add<R, F>(&self, operation: &'static str, add: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut VectorIndex) -> Result<R, Error>,
+    {
+        let mut index = self.index_mut(operation)?;
+        let start = self.clock.now_ms();
+        let result = add(&mut index);
+        let elapsed = self.clock.now_ms() - start;
+        metrics::with_registry(|m| {
+            m.record_latency("add", elapsed);
+            m.set_engine_gauge(&self.metrics_name, "index_size", index.size() as f64);
+        });
+        result
+    }
+
+    /// Borrow the index for a modification, unless a build is running
+    fn index_mut(&self, operation: &'static str) -> Result<RefMut<'_, VectorIndex>, Error> {
+        if self.building.get() {
+            return Err(Error::InvalidState {
+                operation,
+                message: "an index build is in progress".to_string(),
+            });
+        }
+        Ok(self.index.borrow_mut())
+    }
+
+    /// Run a search against the index and time it with the engine's clock
+    fn timed<F>(&self, search: F) -> Result<SearchResults, Error>
+    where
+        F: FnOnce(&VectorIndex) -> Result<Vec<SearchResult>, Error>,
+    {
+        let index = self.index.borrow();
+        let start = self.clock.now_ms();
+        let results = search(&index)?;
+        let query_time_ms = self.clock.now_ms() - start;
+        metrics::with_registry(|m| {
+            m.record_latency("search", query_time_ms);
+            m.increment("vectors_scanned", index.size());
+        });
+
+        Ok(SearchResults {
+            results,
+            query_time_ms,
+        })
+    }
+}
+
+impl Drop for VectorSearchEngine {
+    fn drop(&mut self) {
+        metrics::with_registry(|m| m.remove_engine(&self.metrics_name));
+    }
+}
+
+/// Builds "more like these, less like that" queries from examples
+///
+/// Combines positive and negative examples Rocchio-style: the weighted mean of
+/// the positives minus the weighted mean of the negatives, normalized when the
+/// index uses cosine similarity. Examples can be IDs already in the index or raw
+/// vectors; example IDs never appear in the results.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct QueryBuilder {
+    positive_ids: Vec<usize>,
+    negative_ids: Vec<usize>,
+    positive_vectors: Vec<Vec<f32>>,
+    negative_vectors: Vec<Vec<f32>>,
+    positive_weight: f32,
+    negative_weight: f32,
+}
+
+#[wasm_bindgen]
+impl QueryBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        QueryBuilder {
+            positive_ids: Vec::new(),
+            negative_ids: Vec::new(),
+            positive_vectors: Vec::new(),
+            negative_vectors: Vec::new(),
+            positive_weight: 1.0,
+            negative_weight: 0.5,
+        }
+    }
+
+    /// Add an indexed vector as a positive example
+    pub fn like(&mut self, id: usize) {
+        self.positive_ids.push(id);
+    }
+
+    /// Add an indexed vector as a negative example
+    pub fn unlike(&mut self, id: usize) {
+        self.negative_ids.push(id);
+    }
+
+    /// Add a raw vector (e.g. an encoded text query) as a positive example
+    pub fn like_vector(&mut self, vector: Vec<f32>) {
+        self.positive_vectors.push(vector);
+    }
+
+    /// Add a raw vector as a negative example
+    pub fn unlike_vector(&mut self, vector: Vec<f32>) {
+        self.negative_vectors.push(vector);
+    }
+
+    /// Set the Rocchio weights of the positive and negative means (defaults 1.0 and 0.5)
+    pub fn set_weights(&mut self, positive: f32, negative: f32) {
+        self.positive_weight = positive;
+        self.negative_weight = negative;
+    }
+}
+
+impl Default for QueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryBuilder {
+    /// Combined query vector in the index's stored space
+    pub fn query_vector(&self, index: &VectorIndex) -> Result<Vec<f32>, Error> {
+        if self.positive_ids.is_empty() && self.positive_vectors.is_empty() {
+            return Err(Error::EmptyInput {
+                operation: "QueryBuilder::query_vector",
+            });
+        }
+
+        let positives = self.examples(index, &self.positive_ids, &self.positive_vectors)?;
+        let negatives = self.examples(index, &self.negative_ids, &self.negative_vectors)?;
+        let dimension = positives[0].len();
+
+        let mut query = vec![0.0f32; dimension];
+        for (examples, weight) in [
+            (&positives, self.positive_weight),
+            (&negatives, -self.negative_weight),
+        ] {
+            let scale = weight / examples.len().max(1) as f32;
+            for example in examples.iter() {
+                for (q, x) in query.iter_mut().zip(example) {
+                    *q += x * scale;
+                }
+            }
+        }
+
+        if index.metric() == DistanceMetric::Cosine {
+            crate::vector_search::normalize_vector(&mut query);
+        }
+        Ok(query)
+    }
+
+    /// Run the combined query against `index`, leaving out the example IDs
+    pub fn search_index(&self, index: &VectorIndex, k: usize) -> Result<Vec<SearchResult>, Error> {
+        let query = self.query_vector(index)?;
+        let exclude: Vec<usize> = self
+            .positive_ids
+            .iter()
+            .chain(&self.negative_ids)
+            .copied()
+            .collect();
+        Ok(index.rank(&query, k, &exclude))
+    }
+
+    /// Resolve example IDs and raw vectors to vectors in the stored space
+    fn examples(
+        &self,
+        index: &VectorIndex,
+        ids: &[usize],
+        vectors: &[Vec<f32>],
+    ) -> Result<Vec<Vec<f32>>, Error> {
+        let mut examples = Vec::with_capacity(ids.len() + vectors.len());
+        for &id in ids {
+            let stored = index.stored(id).ok_or(Error::IndexOutOfBounds {
+                operation: "QueryBuilder::query_vector",
+                index: id,
+                len: index.size(),
+            })?;
+            examples.push(stored.to_vec());
+        }
+        for vector in vectors {
+            Error::check_dimension(
+                "QueryBuilder::query_vector",
+                index.dimension(),
+                vector.len(),
+            )?;
+            examples.push(index.prepare(vector));
+        }
+        Ok(examples)
     }
 }
 
@@ -113,6 +545,7 @@ impl VectorSearchEngine {
 struct IndexStats {
     size: usize,
     dimension: usize,
+    model: Option<EmbeddingModelInfo>,
 }
 
 /// Batch processing utilities
@@ -133,81 +566,190 @@ impl BatchProcessor {
     }
 
     /// Process vectors in batches
-    pub fn process(&self, vectors: &[f32], operation: &str) -> Result<Vec<f32>, JsValue> {
-        let count = vectors.len() / self.dimension;
-        if vectors.len() != count * self.dimension {
-            return Err(JsValue::from_str("Invalid vector batch size"));
-        }
+    pub fn process(&self, vectors: &[f32], operation: &str) -> Result<Vec<f32>, Error> {
+        Error::check_batch("BatchProcessor::process", vectors, self.dimension)?;
 
         match operation {
             "normalize" => {
-                let mut result = vectors.to_vec();
-                crate::vector_search::normalize_vectors_batch(&mut result, self.dimension);
-                Ok(result)
+                let mut pipeline = Pipeline::new(self.dimension);
+                pipeline.normalize();
+                Ok(self.run(&pipeline, vectors)?.data())
             }
-            "centroid" => {
-                crate::embeddings::compute_centroid(vectors, self.dimension)
-            }
-            _ => Err(JsValue::from_str("Unknown operation")),
+            "centroid" => crate::embeddings::compute_centroid(vectors, self.dimension),
+            _ => Err(Error::NotFound {
+                kind: "operation",
+                name: operation.to_string(),
+            }),
         }
     }
+
+    /// Run a fitted pipeline over `vectors`, `batch_size` vectors at a time
+    pub fn run(&self, pipeline: &Pipeline, vectors: &[f32]) -> Result<PipelineOutput, Error> {
+        self.run_with(pipeline, vectors, |_, _| {})
+    }
+
+    /// Run a pipeline, calling `on_progress(processed, total)` after every batch
+    pub fn run_with_progress(
+        &self,
+        pipeline: &Pipeline,
+        vectors: &[f32],
+        on_progress: &js_sys::Function,
+    ) -> Result<PipelineOutput, Error> {
+        self.run_with(pipeline, vectors, |processed, total| {
+            // Progress reporting is best effort; a throwing callback does not abort the run
+            let _ = on_progress.call2(
+                &JsValue::NULL,
+                &JsValue::from_f64(processed as f64),
+                &JsValue::from_f64(total as f64),
+            );
+        })
+    }
+
+    /// Get the configured batch size
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
+
+impl BatchProcessor {
+    /// Run a pipeline with a native progress callback
+    pub fn run_with<F>(
+        &self,
+        pipeline: &Pipeline,
+        vectors: &[f32],
+        progress: F,
+    ) -> Result<PipelineOutput, Error>
+    where
+        F: FnMut(usize, usize),
+    {
+        Error::check_dimension("BatchProcessor::run", self.dimension, pipeline.dimension())?;
+        pipeline.run_chunked(vectors, self.batch_size, progress)
+    }
 }
 
 /// Memory-efficient vector storage with compression
+///
+/// Values are packed at 8, 4 or 2 bits each against per-dimension ranges set
+/// by [`calibrate`](Self::calibrate); an uncalibrated store assumes every
+/// dimension lies in [-1, 1], which suits normalized embeddings.
 #[wasm_bindgen]
 pub struct CompressedVectorStore {
     vectors: Vec<u8>,
     dimension: usize,
     count: usize,
-    compression_factor: u8,
+    bits: u8,
+    /// Lower bound and step size of every dimension
+    ranges: Vec<(f32, f32)>,
 }
 
 #[wasm_bindgen]
 impl CompressedVectorStore {
+    /// Create a store `compression_factor` times smaller than `f32` storage
+    ///
+    /// Factors up to 4 pack 8 bits per value, up to 8 pack 4 bits, and larger
+    /// factors pack 2 bits.
     #[wasm_bindgen(constructor)]
     pub fn new(dimension: usize, compression_factor: u8) -> Self {
-        CompressedVectorStore {
+        let bits = match compression_factor {
+            0..=4 => 8,
+            5..=8 => 4,
+            _ => 2,
+        };
+        let mut store = CompressedVectorStore {
             vectors: Vec::new(),
             dimension,
             count: 0,
-            compression_factor: compression_factor.max(1).min(8),
+            bits,
+            ranges: Vec::new(),
+        };
+        store.ranges = vec![store.range(-1.0, 1.0); dimension];
+        store
+    }
+
+    /// Set per-dimension ranges to the minimum and maximum of `sample`
+    pub fn calibrate(&mut self, sample: &[f32]) -> Result<(), Error> {
+        self.calibrate_percentile(sample, 0.0)
+    }
+
+    /// Set per-dimension ranges to the `percentile` and `1 - percentile` quantiles of `sample`
+    ///
+    /// Outliers beyond the range are clamped, trading them for finer steps
+    /// everywhere else. The store must still be empty.
+    pub fn calibrate_percentile(&mut self, sample: &[f32], percentile: f32) -> Result<(), Error> {
+        if self.count > 0 {
+            return Err(Error::InvalidState {
+                operation: "calibrate",
+                message: "the store already holds vectors".to_string(),
+            });
+        }
+        Error::check_batch("CompressedVectorStore::calibrate", sample, self.dimension)?;
+        if sample.is_empty() {
+            return Err(Error::EmptyInput {
+                operation: "CompressedVectorStore::calibrate",
+            });
         }
+
+        let count = sample.len() / self.dimension;
+        let percentile = percentile.clamp(0.0, 0.5);
+        let lower = (percentile * (count - 1) as f32).round() as usize;
+        let upper = count - 1 - lower;
+        let mut column: Vec<f32> = Vec::with_capacity(count);
+        for d in 0..self.dimension {
+            column.clear();
+            column.extend(sample.iter().skip(d).step_by(self.dimension).copied());
+            column.sort_by(|a, b| a.total_cmp(b));
+            self.ranges[d] = self.range(column[lower], column[upper]);
+        }
+        Ok(())
     }
 
     /// Add a vector with quantization
-    pub fn add(&mut self, vector: &[f32]) -> Result<(), JsValue> {
-        if vector.len() != self.dimension {
-            return Err(JsValue::from_str("Vector dimension mismatch"));
-        }
+    pub fn add(&mut self, vector: &[f32]) -> Result<(), Error> {
+        Error::check_dimension("CompressedVectorStore::add", self.dimension, vector.len())?;
 
-        // Simple quantization to u8
-        for &val in vector {
-            let quantized = ((val.clamp(-1.0, 1.0) + 1.0) * 127.5) as u8;
-            self.vectors.push(quantized);
+        let max_level = self.max_level();
+        let mut row = vec![0u8; self.row_bytes()];
+        for (d, (&value, &(lo, step))) in vector.iter().zip(&self.ranges).enumerate() {
+            let level = if step > 0.0 {
+                ((value - lo) / step).round().clamp(0.0, max_level as f32) as u8
+            } else {
+                0
+            };
+            let (byte, shift) = self.position(d);
+            row[byte] |= level << shift;
         }
+        self.vectors.extend_from_slice(&row);
 
         self.count += 1;
         Ok(())
     }
 
     /// Get a vector by ID (dequantized)
-    pub fn get(&self, id: usize) -> Result<Vec<f32>, JsValue> {
+    pub fn get(&self, id: usize) -> Result<Vec<f32>, Error> {
         if id >= self.count {
-            return Err(JsValue::from_str("Index out of bounds"));
+            return Err(Error::IndexOutOfBounds {
+                operation: "CompressedVectorStore::get",
+                index: id,
+                len: self.count,
+            });
         }
 
-        let start = id * self.dimension;
-        let end = start + self.dimension;
-
-        let result: Vec<f32> = self.vectors[start..end]
+        let row = &self.vectors[id * self.row_bytes()..(id + 1) * self.row_bytes()];
+        let result: Vec<f32> = self
+            .ranges
             .iter()
-            .map(|&b| (b as f32 / 127.5) - 1.0)
+            .enumerate()
+            .map(|(d, &(lo, step))| {
+                let (byte, shift) = self.position(d);
+                let level = (row[byte] >> shift) & self.max_level();
+                lo + level as f32 * step
+            })
             .collect();
 
         Ok(result)
     }
 
-    /// Get memory usage in bytes
+    /// Get memory usage of the packed vectors in bytes
     pub fn memory_usage(&self) -> usize {
         self.vectors.len()
     }
@@ -215,14 +757,43 @@ impl CompressedVectorStore {
     pub fn count(&self) -> usize {
         self.count
     }
+
+    /// Get the effective compression factor relative to `f32` storage
+    pub fn compression_factor(&self) -> u8 {
+        32 / self.bits
+    }
+
+    /// Bits stored per value (8, 4 or 2)
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+}
+
+impl CompressedVectorStore {
+    fn max_level(&self) -> u8 {
+        ((1u16 << self.bits) - 1) as u8
+    }
+
+    fn row_bytes(&self) -> usize {
+        (self.dimension * self.bits as usize).div_ceil(8)
+    }
+
+    /// Byte offset within a row and bit shift of dimension `d`
+    fn position(&self, d: usize) -> (usize, u32) {
+        let per_byte = 8 / self.bits as usize;
+        (d / per_byte, ((d % per_byte) * self.bits as usize) as u32)
+    }
+
+    /// Lower bound and quantization step covering `[lo, hi]`
+    fn range(&self, lo: f32, hi: f32) -> (f32, f32) {
+        (lo, (hi - lo).max(0.0) / self.max_level() as f32)
+    }
 }
 
 /// Utility functions exported to JavaScript
 #[wasm_bindgen]
-pub fn cosine_similarity_js(a: &[f32], b: &[f32]) -> Result<f32, JsValue> {
-    if a.len() != b.len() {
-        return Err(JsValue::from_str("Vectors must have same dimension"));
-    }
+pub fn cosine_similarity_js(a: &[f32], b: &[f32]) -> Result<f32, Error> {
+    Error::check_dimension("cosine_similarity", a.len(), b.len())?;
     Ok(crate::vector_search::cosine_similarity(a, b))
 }
 
@@ -234,10 +805,8 @@ pub fn normalize_vector_js(vector: Vec<f32>) -> Vec<f32> {
 }
 
 #[wasm_bindgen]
-pub fn dot_product_js(a: &[f32], b: &[f32]) -> Result<f32, JsValue> {
-    if a.len() != b.len() {
-        return Err(JsValue::from_str("Vectors must have same dimension"));
-    }
+pub fn dot_product_js(a: &[f32], b: &[f32]) -> Result<f32, Error> {
+    Error::check_dimension("dot_product", a.len(), b.len())?;
     Ok(crate::vector_search::dot_product(a, b))
 }
 
@@ -249,31 +818,212 @@ pub fn log(message: &str) {
 
 /// Get performance metrics
 #[wasm_bindgen]
-pub fn get_performance_metrics() -> Result<String, JsValue> {
-    let window = web_sys::window().ok_or_else(|| JsValue::from_str("No window"))?;
-    let performance = window.performance().ok_or_else(|| JsValue::from_str("No performance API"))?;
-
+pub fn get_performance_metrics() -> Result<String, Error> {
     let metrics = PerformanceMetrics {
-        now: performance.now(),
-        memory_used: js_sys::eval("performance.memory ? performance.memory.usedJSHeapSize : 0")
-            .unwrap_or(JsValue::from_f64(0.0))
-            .as_f64()
-            .unwrap_or(0.0) as usize,
+        now: SystemClock.now_ms(),
+        memory_used: metrics::js_heap_bytes(),
+        wasm_memory: metrics::wasm_memory_bytes(),
     };
 
-    serde_json::to_string(&metrics)
-        .map_err(|e| JsValue::from_str(&e.to_string()))
+    serde_json::to_string(&metrics).map_err(|e| Error::serialize("performance metrics", e))
 }
 
 #[derive(Serialize)]
 struct PerformanceMetrics {
     now: f64,
     memory_used: usize,
+    wasm_memory: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::FakeClock;
+
+    fn reconstruction_rmse(compression_factor: u8) -> (f32, usize) {
+        let mut rng = crate::utils::SeededRng::new(3);
+        // Unnormalized vectors with a different scale and offset per dimension
+        let vectors: Vec<f32> = (0..200 * 10)
+            .map(|i| rng.normal() as f32 * (1 + i % 10) as f32 + (i % 10) as f32 * 3.0)
+            .collect();
+
+        let mut store = CompressedVectorStore::new(10, compression_factor);
+        store.calibrate(&vectors).unwrap();
+        let mut squared_error = 0.0;
+        for (id, vector) in vectors.as_chunks::<10>().0.iter().enumerate() {
+            store.add(vector).unwrap();
+            for (a, b) in store.get(id).unwrap().iter().zip(vector) {
+                squared_error += (a - b) * (a - b);
+            }
+        }
+        (
+            (squared_error / vectors.len() as f32).sqrt(),
+            store.memory_usage(),
+        )
+    }
+
+    #[test]
+    fn test_compressed_store_packing() {
+        let (rmse8, bytes8) = reconstruction_rmse(4);
+        let (rmse4, bytes4) = reconstruction_rmse(8);
+        let (rmse2, bytes2) = reconstruction_rmse(16);
+
+        // 10 values per vector: 10, 5 and 3 bytes
+        assert_eq!((bytes8, bytes4, bytes2), (2000, 1000, 600));
+        // Data spans roughly 6 standard deviations of up to 10 per dimension
+        assert!(rmse8 < 0.05, "8-bit rmse {}", rmse8);
+        assert!(rmse4 < 0.8, "4-bit rmse {}", rmse4);
+        assert!(rmse2 < 4.0, "2-bit rmse {}", rmse2);
+        assert!(rmse8 < rmse4 && rmse4 < rmse2);
+
+        // Uncalibrated stores keep the [-1, 1] default for normalized vectors
+        let mut store = CompressedVectorStore::new(3, 8);
+        assert_eq!((store.bits(), store.compression_factor()), (4, 8));
+        store.add(&[-1.0, 0.0, 1.0]).unwrap();
+        let restored = store.get(0).unwrap();
+        assert!(restored
+            .iter()
+            .zip([-1.0, 0.0, 1.0])
+            .all(|(a, b)| (a - b).abs() <= 1.0 / 15.0));
+    }
+
+    #[test]
+    fn test_engine_search_is_timed_by_injected_clock() {
+        let mut engine = VectorSearchEngine::new(2, "cosine")
+            .unwrap()
+            .with_clock(FakeClock::with_step(1.5));
+        engine
+            .add_batch(vec![1.0, 0.0, 0.0, 1.0, 0.7, 0.7], 3)
+            .unwrap();
+
+        let results = engine.search(vec![1.0, 0.1], 2).unwrap();
+        assert_eq!(results.query_time(), 1.5);
+        assert_eq!(results.ids(), vec![0, 2]);
+        assert_eq!(results.scores().len(), 2);
+
+        let batch = engine.search_batch(vec![1.0, 0.1, 0.1, 1.0], 1).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!((batch[0].ids(), batch[1].ids()), (vec![0], vec![1]));
+        assert_eq!(batch[0].query_time(), 0.75);
+
+        // Each libtest thread has its own registry
+        let registry = metrics::metrics();
+        let search = registry.latency("search").unwrap();
+        assert_eq!((search.count(), search.max_ms()), (3, 1.5));
+        assert_eq!(registry.latency_quantile("add", 0.5), Some(1.5));
+        assert_eq!(registry.counter("vectors_scanned"), 9.0);
+        assert_eq!(
+            registry.engine_gauge(&engine.metrics_name(), "index_size"),
+            Some(3.0)
+        );
+    }
+
+    #[test]
+    fn test_engine_finds_near_duplicates() {
+        let mut engine = VectorSearchEngine::new(2, "cosine").unwrap();
+        engine
+            .add_batch(vec![1.0, 0.0, 0.99, 0.1, 0.98, 0.2, 0.0, 1.0], 4)
+            .unwrap();
+        let groups = engine.find_near_duplicates(0.97);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].members(), vec![0, 1, 2]);
+
+        let mut metadata = CatalogMetadata::new();
+        metadata.set(0, Some("The Matrix".into()), Some(1999));
+        metadata.set(1, Some("Matrix, The".into()), Some(1999));
+        metadata.set(2, Some("The Matrix Reloaded".into()), Some(2003));
+        let groups =
+            engine.find_near_duplicates_with_metadata(&metadata, &DuplicateConfig::new(0.9));
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].members(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_engines_keep_their_own_gauges() {
+        let mut catalog = VectorSearchEngine::new(2, "cosine").unwrap();
+        catalog.set_metrics_name("catalog").unwrap();
+        let mut recent = VectorSearchEngine::new(2, "cosine").unwrap();
+        catalog.add_batch(vec![1.0, 0.0, 0.0, 1.0], 2).unwrap();
+        recent.add(vec![1.0, 0.0]).unwrap();
+        recent.clear().unwrap();
+
+        let size = |engine: &str| metrics::metrics().engine_gauge(engine, "index_size");
+        let recent_name = recent.metrics_name();
+        assert!(recent_name.starts_with("engine-"));
+        assert_eq!(
+            (size("catalog"), size(&recent_name)),
+            (Some(2.0), Some(0.0))
+        );
+
+        drop(recent);
+        assert_eq!((size("catalog"), size(&recent_name)), (Some(2.0), None));
+
+        // A running build keeps the name it started with
+        catalog.building.set(true);
+        let err = catalog.set_metrics_name("renamed").unwrap_err();
+        assert_eq!(err.code(), "INVALID_STATE");
+        assert_eq!((size("catalog"), size("renamed")), (Some(2.0), None));
+    }
+
+    #[test]
+    fn test_vector_buffer_round_trip() {
+        let mut buffer = VectorBuffer::new(2, 3).unwrap();
+        assert_eq!((buffer.count(), buffer.len()), (3, 6));
+        buffer.fill(&[1.0, 0.0, 0.0, 1.0], 0).unwrap();
+        buffer.fill(&[0.7, 0.7], 2).unwrap();
+        assert_eq!(buffer.as_slice()[4..], [0.7, 0.7]);
+        for (values, first) in [
+            (&[1.0][..], 0),
+            (&[0.0; 4][..], 2),
+            (&[0.0; 2][..], usize::MAX),
+        ] {
+            assert!(buffer.fill(values, first).is_err());
+        }
+
+        let mut engine = VectorSearchEngine::new(2, "cosine").unwrap();
+        engine.add_buffer(&buffer).unwrap();
+        assert_eq!(engine.index.borrow().size(), 3);
+
+        let results = engine.search_buffer(&buffer, 1).unwrap();
+        let ids: Vec<u32> = results.iter().flat_map(|r| r.ids()).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        let wrong = VectorBuffer::new(3, 1).unwrap();
+        let err = engine.add_buffer(&wrong).unwrap_err();
+        assert_eq!(err.code(), "DIMENSION_MISMATCH");
+        assert!(VectorBuffer::new(0, 4).is_err());
+        let Err(err) = VectorBuffer::new(usize::MAX / 2, 3) else {
+            panic!("allocated an overflowing buffer");
+        };
+        assert_eq!(err.code(), "INVALID_ARGUMENT");
+        assert!(VectorBuffer::new(4, usize::MAX / 8).is_err());
+        assert!(VectorBuffer::from_vec(vec![0.0; 5], 2).is_err());
+    }
+
+    #[test]
+    fn test_query_builder() {
+        let mut index = VectorIndex::new(3, DistanceMetric::Cosine);
+        index.add_vector(&[1.0, 0.0, 0.0]).unwrap(); // 0: heist
+        index.add_vector(&[0.0, 1.0, 0.0]).unwrap(); // 1: romance
+        index.add_vector(&[0.0, 0.0, 1.0]).unwrap(); // 2: horror
+        index.add_vector(&[0.7, 0.7, 0.0]).unwrap(); // 3: heist + romance
+        index.add_vector(&[0.7, 0.0, 0.7]).unwrap(); // 4: heist + horror
+
+        // Like heist and romance, but not horror
+        let mut builder = QueryBuilder::new();
+        builder.like(0);
+        builder.like_vector(vec![0.0, 2.0, 0.0]);
+        builder.unlike(2);
+
+        let query = builder.query_vector(&index).unwrap();
+        let norm: f32 = query.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+        assert!(query[2] < 0.0);
+
+        let results = builder.search_index(&index, 3).unwrap();
+        let ids: Vec<usize> = results.iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![3, 1, 4]);
+    }
 
     #[test]
     fn test_compressed_store() {
@@ -283,7 +1033,7 @@ mod tests {
         store.add(&[1.0, 0.0, -1.0]).unwrap();
 
         assert_eq!(store.count(), 2);
-        assert_eq!(store.memory_usage(), 6); // 2 vectors * 3 dims
+        assert_eq!(store.memory_usage(), 4); // 2 vectors * 3 dims at 4 bits, rows byte-aligned
 
         let vec = store.get(0).unwrap();
         assert_eq!(vec.len(), 3);